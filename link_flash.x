@@ -207,11 +207,22 @@ SECTIONS
     __euninit = .;
   } > RAM
 
-  /* Place the heap right after `.uninit` in RAM */
-  PROVIDE(__sheap = __euninit);
+  /* ### .noretain */
+  /* Uninitialized buffers whose RAM blocks may be powered down in deep sleep, see `pmc::set_noretain_power_down` */
+  .noretain (NOLOAD) : ALIGN(4)
+  {
+    . = ALIGN(4);
+    __snoretain = .;
+    *(.noretain .noretain.*);
+    . = ALIGN(4);
+    __enoretain = .;
+  } > RAM
+
+  /* Place the heap right after `.noretain` in RAM */
+  PROVIDE(__sheap = __enoretain);
 
   /* Place stack end at the end of allocated RAM */
-  PROVIDE(_stack_end = __euninit);
+  PROVIDE(_stack_end = __enoretain);
 
   /* ## .got */
   /* Dynamic relocations are unsupported. This section is only used to detect relocatable code in
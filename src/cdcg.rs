@@ -1,28 +1,487 @@
 //! Core Domain Clock Generator
 
-use core::mem::MaybeUninit;
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicU32, Ordering};
 
+use critical_section::Mutex;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 use npcx490m_pac::lfcg::lfcgctl2::XtOscSlEn;
 use npcx490m_pac::{Hfcg, Lfcg, Shm};
 
+use crate::timer::capture::{Channel, InputCapture};
+use crate::timer::low_level::ClockSource;
+use crate::timer::MultiFunctionInstance;
+
 const LFCLK: u32 = 32_768;
 
+/// The LFCLK frequency measured by [calibrate_lfclk], or 0 if it has not been measured
+static LFCLK_MEASURED: AtomicU32 = AtomicU32::new(0);
+
 /// Frozen clock frequencies
-static mut CLOCKS: MaybeUninit<Clocks> = MaybeUninit::uninit();
+static CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+/// The config the clocks were last programmed with
+static CONFIG: Mutex<RefCell<Option<Config>>> = Mutex::new(RefCell::new(None));
 
-/// Set the frozen clock frequencies
+/// The maximum number of drivers that can be re-tuned by [set_core_frequency] at the same time
+const MAX_CLOCK_CHANGE_HANDLERS: usize = 16;
+
+/// A driver whose timing depends on a bus clock, re-tuned by [set_core_frequency].
 ///
-/// Safety: May only be used before or in the [init_clocks] function.
-unsafe fn set_clocks(clocks: Clocks) {
+/// Register the hook with a [ClockChangeRegistration] for as long as the driver lives.
+pub(crate) trait ClockChangeHook {
+    /// Let the peripheral finish what it is doing at the old clocks, called with the `context` of the registration.
+    fn prepare(_context: u32) {}
+
+    /// Re-tune the timing to the new clocks, called with the `context` of the registration.
+    fn retune(context: u32);
+}
+
+#[derive(Copy, Clone)]
+struct ClockChangeHandler {
+    key: usize,
+    prepare: fn(u32),
+    retune: fn(u32),
+    context: u32,
+    /// The number of [ClockBusy] guards of the driver
+    busy: u8,
+}
+
+/// Drivers to re-tune after the clocks changed
+static CLOCK_CHANGE_HANDLERS: Mutex<RefCell<[Option<ClockChangeHandler>; MAX_CLOCK_CHANGE_HANDLERS]>> =
+    Mutex::new(RefCell::new([None; MAX_CLOCK_CHANGE_HANDLERS]));
+
+/// Set the frozen clock frequencies
+fn set_clocks(clocks: Clocks) {
     #[cfg(feature = "defmt")]
     defmt::debug!("cdcg: {:?}", clocks);
-    CLOCKS = MaybeUninit::new(clocks);
+    critical_section::with(|cs| CLOCKS.borrow(cs).set(Some(clocks)));
 }
 
 /// Safety: May only be used after the [init_clocks] function.
-pub(crate) unsafe fn get_clocks() -> &'static Clocks {
-    #[allow(clippy::deref_addrof)]
-    (*&raw mut CLOCKS).assume_init_ref()
+pub(crate) unsafe fn get_clocks() -> Clocks {
+    clocks()
+}
+
+/// Get the frozen clock frequencies, panicking when the HAL has not been initialized yet.
+fn clocks() -> Clocks {
+    critical_section::with(|cs| CLOCKS.borrow(cs).get())
+        .expect("The clocks are only known after the HAL has been initialized")
+}
+
+/// Register `H` to be called with `context` around clock changes, see [set_core_frequency].
+///
+/// The `key` identifies the driver instance, registering the same key again replaces its handler.
+/// Panics if too many handlers are registered.
+pub(crate) fn on_clock_change<H: ClockChangeHook>(key: usize, context: u32) {
+    critical_section::with(|cs| {
+        let mut handlers = CLOCK_CHANGE_HANDLERS.borrow_ref_mut(cs);
+        let slot = match handlers.iter().position(|h| h.is_some_and(|h| h.key == key)) {
+            Some(slot) => slot,
+            None => handlers
+                .iter()
+                .position(Option::is_none)
+                .expect("Too many drivers registered for clock changes"),
+        };
+
+        handlers[slot] = Some(ClockChangeHandler {
+            key,
+            prepare: H::prepare,
+            retune: H::retune,
+            context,
+            busy: 0,
+        });
+    });
+}
+
+/// Remove the handler registered with [on_clock_change] for `key`, if any.
+pub(crate) fn remove_clock_change(key: usize) {
+    critical_section::with(|cs| {
+        for slot in CLOCK_CHANGE_HANDLERS.borrow_ref_mut(cs).iter_mut() {
+            if slot.is_some_and(|h| h.key == key) {
+                *slot = None;
+            }
+        }
+    });
+}
+
+/// Keeps a handler registered with [on_clock_change] until dropped.
+pub(crate) struct ClockChangeRegistration {
+    key: usize,
+}
+
+impl ClockChangeRegistration {
+    pub(crate) fn new<H: ClockChangeHook>(key: usize, context: u32) -> Self {
+        on_clock_change::<H>(key, context);
+        Self { key }
+    }
+
+    /// Mark the driver busy until the returned guard is dropped, so [set_core_frequency] fails instead of re-tuning
+    /// it in the middle of a transfer.
+    pub(crate) fn busy(&self) -> ClockBusy {
+        update_busy(self.key, |busy| busy + 1);
+        ClockBusy { key: self.key }
+    }
+}
+
+/// Keeps a driver registered with a [ClockChangeRegistration] busy until dropped.
+pub(crate) struct ClockBusy {
+    key: usize,
+}
+
+impl Drop for ClockBusy {
+    fn drop(&mut self) {
+        update_busy(self.key, |busy| busy - 1);
+    }
+}
+
+fn update_busy(key: usize, f: impl Fn(u8) -> u8) {
+    critical_section::with(|cs| {
+        for h in CLOCK_CHANGE_HANDLERS.borrow_ref_mut(cs).iter_mut().flatten() {
+            if h.key == key {
+                h.busy = f(h.busy);
+            }
+        }
+    });
+}
+
+impl Drop for ClockChangeRegistration {
+    fn drop(&mut self) {
+        remove_clock_change(self.key);
+    }
+}
+
+/// Errors when changing the clocks at runtime
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockError {
+    /// The requested frequency can not be reached with the multiplier
+    OutOfRange,
+    /// The resulting configuration is invalid
+    InvalidConfig(ConfigError),
+    /// The time driver is clocked from an APB clock, which would change
+    UsedByTimeDriver,
+    /// A driver that depends on a bus clock is transferring data
+    Busy,
+}
+
+/// Reprogram the `HFCG` multiplier at runtime so the core clock `CLK` runs at about `hz`, and returns the actual
+/// frequency.
+///
+/// The dividers of the [Config] passed to `init` are kept, so all bus clocks scale along with the `CLK`. Afterwards
+/// the drivers that depend on a bus clock, like the UART and I2C drivers, re-tune their timing to the new frequency.
+/// Fails with [ClockError::Busy] while an I2C transaction is in progress, and waits for the UART transmitters to
+/// finish the data in their FIFO. A byte that a UART is receiving during the change is lost.
+pub fn set_core_frequency(hz: u32) -> Result<u32, ClockError> {
+    let mut config = critical_section::with(|cs| CONFIG.borrow_ref(cs).clone())
+        .expect("The clocks can only be changed after the HAL has been initialized");
+
+    let vosc_div = match config.vosc_mode {
+        VoscClockMode::ExtendedFrequency => 1,
+        VoscClockMode::Normal => 2,
+        VoscClockMode::Mhz40 => 3,
+    };
+    let prescaler = config.core_clock_prescaler.div_value();
+
+    let mult_m = (u64::from(hz) * u64::from(vosc_div * prescaler)).div_ceil(u64::from(LFCLK));
+    config.mult_m = u16::try_from(mult_m)
+        .ok()
+        .filter(|&mult_m| mult_m > 0)
+        .ok_or(ClockError::OutOfRange)?;
+
+    config.validate().map_err(ClockError::InvalidConfig)?;
+    let clk = LFCLK * u32::from(config.mult_m) / vosc_div / prescaler;
+
+    #[cfg(feature = "_time-driver")]
+    if crate::time_driver::uses_apb() && clk != core_hz() {
+        return Err(ClockError::UsedByTimeDriver);
+    }
+
+    let handlers = critical_section::with(|cs| *CLOCK_CHANGE_HANDLERS.borrow_ref(cs));
+    for h in handlers.iter().flatten() {
+        (h.prepare)(h.context);
+    }
+
+    critical_section::with(|cs| {
+        let handlers = CLOCK_CHANGE_HANDLERS.borrow_ref(cs);
+        if handlers.iter().flatten().any(|h| h.busy > 0) {
+            return Err(ClockError::Busy);
+        }
+
+        init_clocks(config);
+        Ok(())
+    })?;
+    crate::delay::calibrate();
+
+    for h in handlers.iter().flatten() {
+        (h.retune)(h.context);
+    }
+
+    Ok(core_hz())
+}
+
+/// Frequency of the core clock `CLK` in Hz.
+///
+/// Like all frequency queries, this panics when called before the HAL has been initialized.
+#[must_use]
+pub fn core_hz() -> u32 {
+    clocks().clk
+}
+
+/// Frequency of the `MCLK` in Hz.
+#[must_use]
+pub fn mclk_hz() -> u32 {
+    clocks().mclk
+}
+
+/// Frequency of the `FMCLK` in Hz.
+#[must_use]
+pub fn fmclk_hz() -> u32 {
+    clocks().fmclk
+}
+
+/// Frequency of the `MCLKD` in Hz.
+#[must_use]
+pub fn mclkd_hz() -> u32 {
+    clocks().mclkd
+}
+
+/// Frequency of the `APB1_CLK` in Hz.
+#[must_use]
+pub fn apb1_hz() -> u32 {
+    clocks().apb1_clk
+}
+
+/// Frequency of the `APB2_CLK` in Hz.
+#[must_use]
+pub fn apb2_hz() -> u32 {
+    clocks().apb2_clk
+}
+
+/// Frequency of the `APB3_CLK` in Hz.
+#[must_use]
+pub fn apb3_hz() -> u32 {
+    clocks().apb3_clk
+}
+
+/// Frequency of the `APB4_CLK` in Hz.
+#[must_use]
+pub fn apb4_hz() -> u32 {
+    clocks().apb4_clk
+}
+
+/// Frequency of the `AHB6_CLK` in Hz, or `None` if it is not enabled.
+#[must_use]
+pub fn ahb6_hz() -> Option<u32> {
+    clocks().ahb6_clk
+}
+
+/// Frequency of the `FIU0_CLK` in Hz, or `None` if it is not enabled.
+#[must_use]
+pub fn fiu0_hz() -> Option<u32> {
+    clocks().fiu0_clk
+}
+
+/// Frequency of the `FIU1_CLK` in Hz, or `None` if it is not enabled.
+#[must_use]
+pub fn fiu1_hz() -> Option<u32> {
+    clocks().fiu1_clk
+}
+
+/// Frequency of the low frequency clock `LFCLK` in Hz.
+///
+/// This is the frequency measured by [calibrate_lfclk], or the nominal 32.768 kHz if it has not been measured.
+#[must_use]
+pub fn lfclk_hz() -> u32 {
+    match LFCLK_MEASURED.load(Ordering::Relaxed) {
+        0 => LFCLK,
+        measured => measured,
+    }
+}
+
+/// The source currently used for the low frequency clock `LFCLK`.
+#[must_use]
+pub fn lf_clock_source() -> LfClockSource {
+    // Safety: only read.
+    let lfcg = unsafe { Lfcg::steal() };
+    lfcg.lfcgctl2().read().xt_osc_sl_en().variant().into()
+}
+
+/// Summary of the clock bring-up, for debugging boards with marginal crystals.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockReport {
+    /// The source selected for the `LFCLK`
+    ///
+    /// The `LFCLK` only switches to a selected crystal once it is stable, and the chip does not report when that
+    /// happened. Use [calibrate_lfclk] or [LfClockOut] to check the crystal on a board.
+    pub lf_clock_source: LfClockSource,
+    /// Whether the crystal oscillator is connected to its pins (`LFCGCTL2.XT_OSC`)
+    ///
+    /// Using `PL05` as a GPIO disconnects it, after which a crystal selected as [LfClockSource::ExternalOscillator]
+    /// can never start, and the `LFCLK` stays on the internal oscillator.
+    pub crystal_connected: bool,
+    /// Whether the high frequency clock generator has finished loading its configuration
+    pub hfcg_locked: bool,
+    /// Frequency of the core clock `CLK` in Hz
+    pub core_hz: u32,
+    /// Frequency of the `MCLK` in Hz
+    pub mclk_hz: u32,
+    /// Frequency of the `APB1_CLK` in Hz
+    pub apb1_hz: u32,
+    /// Frequency of the `APB2_CLK` in Hz
+    pub apb2_hz: u32,
+    /// Frequency of the `APB3_CLK` in Hz
+    pub apb3_hz: u32,
+    /// Frequency of the `APB4_CLK` in Hz
+    pub apb4_hz: u32,
+    /// Frequency of the `AHB6_CLK` in Hz, or `None` if it is not enabled
+    pub ahb6_hz: Option<u32>,
+}
+
+/// Describe the current state of the clocks.
+///
+/// The report taken right after the clocks were brought up is kept by the mode marker returned from the init
+/// functions, see for example [Standalone::clock_report](crate::Standalone::clock_report). With the `defmt` feature it
+/// is also logged.
+#[must_use]
+pub fn clock_report() -> ClockReport {
+    let clocks = clocks();
+    // Safety: only read.
+    let hfcg = unsafe { Hfcg::steal() };
+    // Safety: only read.
+    let lfcg = unsafe { Lfcg::steal() };
+
+    ClockReport {
+        lf_clock_source: lf_clock_source(),
+        crystal_connected: lfcg.lfcgctl2().read().xt_osc().bit_is_set(),
+        hfcg_locked: hfcg.hfcgctrl().read().clk_chng().bit_is_clear(),
+        core_hz: clocks.clk,
+        mclk_hz: clocks.mclk,
+        apb1_hz: clocks.apb1_clk,
+        apb2_hz: clocks.apb2_clk,
+        apb3_hz: clocks.apb3_clk,
+        apb4_hz: clocks.apb4_clk,
+        ahb6_hz: clocks.ahb6_clk,
+    }
+}
+
+/// The number of underflows of the `LFCLK` counter, 2 s each, after which the reference is given up on
+const REFERENCE_TIMEOUT: u64 = 2;
+
+/// A reason [calibrate_lfclk] could not measure the `LFCLK`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationError {
+    /// The reference frequency or the number of periods is 0
+    InvalidReference,
+    /// Channel A of the capture does not count the `LFCLK`
+    NotCountingLfClock,
+    /// No edge of the reference arrived within two underflows of the counter, about 4 s
+    NoReference,
+    /// The `LFCLK` did not tick during the measurement
+    LfClockStopped,
+}
+
+/// Measure the frequency of the low frequency clock `LFCLK` against a reference signal of `reference_hz` on the
+/// `TAn` pin of `capture`.
+///
+/// All other clocks of the chip are multiplied from the `LFCLK` by the HFCG, so only a reference from outside the
+/// chip can tell how far the `LFCLK` is off, like a 1 Hz pulse from the host or the clock of a TCXO. Channel A of
+/// `capture` must count the `LFCLK`, see [ClockConfig::low_frequency].
+///
+/// Counts the `LFCLK` over `periods` periods of the reference. The resolution is one `LFCLK` cycle over the whole
+/// measurement, about 31 ppm when measuring for a second. The result is returned and reported by [lfclk_hz] from then
+/// on. The wait for each edge of the reference is bounded by the `LFCLK` itself, so it does not end when both the
+/// reference and the `LFCLK` stop.
+///
+/// [ClockConfig::low_frequency]: crate::timer::low_level::ClockConfig::low_frequency
+pub async fn calibrate_lfclk<T: MultiFunctionInstance>(
+    capture: &mut InputCapture<'_, T>,
+    reference_hz: u32,
+    periods: u32,
+) -> Result<u32, CalibrationError> {
+    if reference_hz == 0 || periods == 0 {
+        return Err(CalibrationError::InvalidReference);
+    }
+    if capture.clock_source(Channel::A) != ClockSource::SlowSpeedClock {
+        return Err(CalibrationError::NotCountingLfClock);
+    }
+
+    // Start at a new edge of the reference, not one captured before this call.
+    capture.discard_capture(Channel::A);
+    let start = capture
+        .capture_within(Channel::A, REFERENCE_TIMEOUT)
+        .await
+        .ok_or(CalibrationError::NoReference)?;
+    let mut end = start;
+    for _ in 0..periods {
+        end = capture
+            .capture_within(Channel::A, REFERENCE_TIMEOUT)
+            .await
+            .ok_or(CalibrationError::NoReference)?;
+    }
+
+    let lf_cycles = end - start;
+    if lf_cycles == 0 {
+        return Err(CalibrationError::LfClockStopped);
+    }
+
+    let measured =
+        (u128::from(lf_cycles) * u128::from(reference_hz) / u128::from(periods)).min(u128::from(u32::MAX)) as u32;
+
+    #[cfg(feature = "defmt")]
+    defmt::debug!("cdcg: measured LFCLK at {} Hz", measured);
+
+    LFCLK_MEASURED.store(measured, Ordering::Relaxed);
+    Ok(measured)
+}
+
+/// Pin that can output the `LFCLK`, named 32KOUT in the datasheet.
+pub type LfClockOutPin = crate::peripherals::PJ06;
+
+/// Exports the low frequency clock `LFCLK` on the [LfClockOutPin], for example to measure its accuracy during board
+/// bring-up.
+///
+/// The 32KOUT function only outputs the `LFCLK`, there is no source select or divider for it. The HFCG multiplies the
+/// `LFCLK` into all other clocks, so the accuracy measured on this pin in ppm also holds for the core and bus clocks.
+pub struct LfClockOut<'d> {
+    _pin: PeripheralRef<'d, LfClockOutPin>,
+}
+
+impl<'d> LfClockOut<'d> {
+    /// Route the `LFCLK` to the pin.
+    pub fn new(pin: impl Peripheral<P = LfClockOutPin> + 'd) -> Self {
+        into_ref!(pin);
+
+        // Note(cs): other peripherals might also be modifying the devalt registers at the same time.
+        critical_section::with(|_| {
+            // Safety: we own the pin, and only change its functions.
+            let sysconfig = unsafe { crate::pac::Sysconfig::steal() };
+            sysconfig.devalt2().modify(|_, w| w.i2c4_0_sl().clear_bit());
+            sysconfig.devaltb().modify(|_, w| w.rxd_sl().clear_bit());
+            sysconfig.devaltj().modify(|_, w| w.cr_sin2_sl().clear_bit());
+            sysconfig.devalta().modify(|_, w| w._32k_out_sl().set_bit());
+        });
+
+        Self { _pin: pin }
+    }
+
+    /// The frequency of the exported clock in Hz, see [lfclk_hz].
+    #[must_use]
+    pub fn frequency(&self) -> u32 {
+        lfclk_hz()
+    }
+}
+
+impl Drop for LfClockOut<'_> {
+    fn drop(&mut self) {
+        // Note(cs): other peripherals might also be modifying devalta at the same time.
+        critical_section::with(|_| {
+            unsafe { crate::pac::Sysconfig::steal() }
+                .devalta()
+                .modify(|_, w| w._32k_out_sl().clear_bit());
+        });
+    }
 }
 
 /// Clock config paramters
@@ -67,6 +526,31 @@ pub struct Config {
 
 impl Default for Config {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reason a clock [Config] is rejected by [Config::validate]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The [Config::mult_m] is 0
+    MultiplierZero,
+    /// The `CLK` is outside of the 4 MHz to 120 MHz range
+    CoreClockOutOfRange,
+    /// The AHB6 and FIU dividers use div 2 and div 3 at the same time
+    AhbDividerConflict,
+    /// A `FIUm_CLK` is faster than half the `CLK` while the `CLK` is faster than 60 MHz
+    FiuClockTooFast,
+    /// The divider of the APB bus with this number (1-4) is not a multiple of the [Config::core_clock_prescaler]
+    ApbDividerNotMultiple(u8),
+    /// The clock of the APB bus with this number (1-4) is outside of its valid range, or faster than the `CLK`
+    ApbClockOutOfRange(u8),
+}
+
+impl Config {
+    /// The default configuration, running the `CLK` at about 22.5 MHz with the `LFCLK` taken from the FRCLK.
+    pub const fn new() -> Self {
         Self {
             lf_clock_source: LfClockSource::FreeRunningClock,
             mult_m: 0x0ABA,
@@ -85,6 +569,176 @@ impl Default for Config {
             mclkd_divider: MclkdDivider::Div1,
         }
     }
+
+    /// Check the configuration against the limits of the datasheet.
+    ///
+    /// This is done by `init` as well, but can be evaluated at compile time for a configuration built in a `const`:
+    ///
+    /// ```rust,ignore
+    /// const CLOCKS: cdcg::Config = cdcg::Config::new().with_apb1_divider(cdcg::MclkDivider::Div6);
+    /// const _: () = assert!(CLOCKS.validate().is_ok());
+    /// ```
+    pub const fn validate(&self) -> Result<(), ConfigError> {
+        if self.mult_m == 0 {
+            return Err(ConfigError::MultiplierZero);
+        }
+
+        let voscclock = LFCLK * self.mult_m as u32;
+        let mclk = match self.vosc_mode {
+            VoscClockMode::ExtendedFrequency => voscclock,
+            VoscClockMode::Normal => voscclock / 2,
+            VoscClockMode::Mhz40 => voscclock / 3,
+        };
+        let prescaler = self.core_clock_prescaler.div_value();
+        let clk = mclk / prescaler;
+
+        if clk < 4_000_000 || clk > 120_000_000 {
+            return Err(ConfigError::CoreClockOutOfRange);
+        }
+
+        let ahb6_dividers = [self.ahb6_divider, self.fiu0_divider, self.fiu1_divider];
+        let mut div2_used = false;
+        let mut div3_used = false;
+        let mut i = 0;
+        while i < ahb6_dividers.len() {
+            match ahb6_dividers[i] {
+                Some(AhbDivider::Div2) => div2_used = true,
+                Some(AhbDivider::Div3) => div3_used = true,
+                _ => {}
+            }
+            i += 1;
+        }
+        if div2_used && div3_used {
+            return Err(ConfigError::AhbDividerConflict);
+        }
+
+        if clk > 60_000_000 {
+            let fiu_dividers = [self.fiu0_divider, self.fiu1_divider];
+            let mut i = 0;
+            while i < fiu_dividers.len() {
+                if let Some(div) = fiu_dividers[i] {
+                    if clk / div.div_value() > clk / 2 {
+                        return Err(ConfigError::FiuClockTooFast);
+                    }
+                }
+                i += 1;
+            }
+        }
+
+        let apb_dividers = [
+            self.apb1_divider,
+            self.apb2_divider,
+            self.apb3_divider,
+            self.apb4_divider,
+        ];
+        let max_apb_clocks = if mclk > 60_000_000 {
+            let half = if mclk / 2 < 60_000_000 { mclk / 2 } else { 60_000_000 };
+            [half, half, half, if mclk < 120_000_000 { mclk } else { 120_000_000 }]
+        } else {
+            [mclk; 4]
+        };
+        let min_apb_clocks = [4_000_000, 8_000_000, 12_500_000, 8_000_000];
+
+        let mut i = 0;
+        while i < apb_dividers.len() {
+            let div = apb_dividers[i].div_value();
+            if div % prescaler != 0 {
+                return Err(ConfigError::ApbDividerNotMultiple(i as u8 + 1));
+            }
+
+            let apb_clk = mclk / div;
+            if apb_clk > clk || apb_clk < min_apb_clocks[i] || apb_clk > max_apb_clocks[i] {
+                return Err(ConfigError::ApbClockOutOfRange(i as u8 + 1));
+            }
+            i += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Set the [Config::lf_clock_source].
+    #[must_use]
+    pub const fn with_lf_clock_source(mut self, source: LfClockSource) -> Self {
+        self.lf_clock_source = source;
+        self
+    }
+
+    /// Set the [Config::mult_m].
+    #[must_use]
+    pub const fn with_mult_m(mut self, mult_m: u16) -> Self {
+        self.mult_m = mult_m;
+        self
+    }
+
+    /// Set the [Config::vosc_mode].
+    #[must_use]
+    pub const fn with_vosc_mode(mut self, mode: VoscClockMode) -> Self {
+        self.vosc_mode = mode;
+        self
+    }
+
+    /// Set the [Config::core_clock_prescaler].
+    #[must_use]
+    pub const fn with_core_clock_prescaler(mut self, prescaler: MclkDivider) -> Self {
+        self.core_clock_prescaler = prescaler;
+        self
+    }
+
+    /// Set the [Config::ahb6_divider].
+    #[must_use]
+    pub const fn with_ahb6_divider(mut self, divider: Option<AhbDivider>) -> Self {
+        self.ahb6_divider = divider;
+        self
+    }
+
+    /// Set the [Config::fiu0_divider].
+    #[must_use]
+    pub const fn with_fiu0_divider(mut self, divider: Option<AhbDivider>) -> Self {
+        self.fiu0_divider = divider;
+        self
+    }
+
+    /// Set the [Config::fiu1_divider].
+    #[must_use]
+    pub const fn with_fiu1_divider(mut self, divider: Option<AhbDivider>) -> Self {
+        self.fiu1_divider = divider;
+        self
+    }
+
+    /// Set the [Config::apb1_divider].
+    #[must_use]
+    pub const fn with_apb1_divider(mut self, divider: MclkDivider) -> Self {
+        self.apb1_divider = divider;
+        self
+    }
+
+    /// Set the [Config::apb2_divider].
+    #[must_use]
+    pub const fn with_apb2_divider(mut self, divider: MclkDivider) -> Self {
+        self.apb2_divider = divider;
+        self
+    }
+
+    /// Set the [Config::apb3_divider].
+    #[must_use]
+    pub const fn with_apb3_divider(mut self, divider: MclkDivider) -> Self {
+        self.apb3_divider = divider;
+        self
+    }
+
+    /// Set the [Config::apb4_divider].
+    #[must_use]
+    pub const fn with_apb4_divider(mut self, divider: MclkDivider) -> Self {
+        self.apb4_divider = divider;
+        self
+    }
+
+    /// Set the [Config::mclkd_divider].
+    #[must_use]
+    pub const fn with_mclkd_divider(mut self, divider: MclkdDivider) -> Self {
+        self.mclkd_divider = divider;
+        self
+    }
 }
 
 pub(crate) fn init_clocks(config: Config) {
@@ -132,16 +786,11 @@ pub(crate) fn init_clocks(config: Config) {
         VoscClockMode::Mhz40 => voscclock / 3,
     };
 
-    let clk = mclk / config.core_clock_prescaler.div_value();
-
-    let ahb6_dividers = [config.ahb6_divider, config.fiu0_divider, config.fiu1_divider];
-    let ahb6_clock_div2_used = ahb6_dividers.iter().flatten().any(|&div| div == AhbDivider::Div2);
-    let ahb6_clock_div3_used = ahb6_dividers.iter().flatten().any(|&div| div == AhbDivider::Div3);
+    if let Err(error) = config.validate() {
+        panic!("Invalid clock configuration: {:?}", error);
+    }
 
-    assert!(
-        ahb6_dividers.iter().all(Option::is_none) || ahb6_clock_div2_used != ahb6_clock_div3_used,
-        "The ahb6_divider, fiu0_divider & fiu1_divider cannot use div 2 and div 3 divisions at the same time"
-    );
+    let clk = mclk / config.core_clock_prescaler.div_value();
 
     let ahb6_clk = config.ahb6_divider.map(|div| clk / div.div_value());
     let fiu0_clk = config.fiu0_divider.map(|div| clk / div.div_value());
@@ -154,63 +803,6 @@ pub(crate) fn init_clocks(config: Config) {
 
     let mclkd = mclk / config.mclkd_divider.div_value();
 
-    assert!(apb4_clk <= clk, "APB clock must not exceed the CLK");
-    assert!(apb3_clk <= clk, "APB clock must not exceed the CLK");
-    assert!(apb2_clk <= clk, "APB clock must not exceed the CLK");
-    assert!(apb1_clk <= clk, "APB clock must not exceed the CLK");
-
-    assert!(clk <= 120_000_000, "Max CLK speed is 120 MHz");
-    assert!(clk >= 4_000_000, "Min CLK speed is 4 MHz");
-
-    if clk > 60_000_000 {
-        assert!(
-            fiu0_clk.unwrap_or(0) <= clk / 2,
-            "FIUm_CLK frequency must be slower than half CLK if CLK is > 60 MHz"
-        );
-        assert!(
-            fiu1_clk.unwrap_or(0) <= clk / 2,
-            "FIUm_CLK frequency must be slower than half CLK if CLK is > 60 MHz"
-        );
-    }
-
-    let apb_dividers = [
-        config.apb1_divider,
-        config.apb2_divider,
-        config.apb3_divider,
-        config.apb4_divider,
-    ];
-    for apb_divider in apb_dividers {
-        assert!(
-            apb_divider.div_value() % config.core_clock_prescaler.div_value() == 0,
-            "APB divider must be a multiple of the `core_clock_prescaler`"
-        );
-    }
-
-    let max_apb_clocks = if mclk > 60_000_000 {
-        [
-            (mclk / 2).min(60_000_000),
-            (mclk / 2).min(60_000_000),
-            (mclk / 2).min(60_000_000),
-            mclk.min(120_000_000),
-        ]
-    } else {
-        [mclk; 4]
-    };
-    let min_apb_clocks = [4_000_000, 8_000_000, 12_500_000, 8_000_000];
-
-    for (i, ((apb_clk, max), min)) in [apb1_clk, apb2_clk, apb3_clk, apb4_clk]
-        .into_iter()
-        .zip(max_apb_clocks)
-        .zip(min_apb_clocks)
-        .enumerate()
-    {
-        assert!(
-            (min..=max).contains(&apb_clk),
-            "APB{} clock is {apb_clk} hz, but must be in range: {min}..={max}",
-            i + 1
-        );
-    }
-
     // When changing prescalers we need to make sure APB is never higher than CLK, so we first slow them down and then possibly speed them back up
     let current_apb1_divider = hfcg.hfcbcd1().read().apb1div().bits();
     let current_apb2_divider = hfcg.hfcbcd1().read().apb2div().bits();
@@ -286,34 +878,34 @@ pub(crate) fn init_clocks(config: Config) {
     hfcg.hfcgctrl().modify(|_, w| w.load().set_bit());
     while hfcg.hfcgctrl().read().clk_chng().bit_is_set() {}
 
-    unsafe {
-        set_clocks(Clocks {
-            voscclock,
-            mclk,
-            fmclk,
-            sio_clk: 24_000_000,
-            clk,
+    set_clocks(Clocks {
+        voscclock,
+        mclk,
+        fmclk,
+        sio_clk: 24_000_000,
+        clk,
 
-            ahb6_clk,
-            fiu0_clk,
-            fiu1_clk,
+        ahb6_clk,
+        fiu0_clk,
+        fiu1_clk,
 
-            apb4_clk,
-            apb3_clk,
-            apb2_clk,
-            apb1_clk,
+        apb4_clk,
+        apb3_clk,
+        apb2_clk,
+        apb1_clk,
 
-            mclkd,
-        });
-    }
+        mclkd,
+    });
 
     if !host_access_stalled {
         shm.shm_ctl().modify(|_, w| w.stall_host().clear_bit());
     }
+
+    critical_section::with(|cs| CONFIG.borrow(cs).replace(Some(config)));
 }
 
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) struct Clocks {
     pub(crate) voscclock: u32,
@@ -353,7 +945,8 @@ pub enum VoscClockMode {
 
 /// The main clock source of the microcontroller.
 /// This clock source powers the LFCLK.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum LfClockSource {
     /// Use the ~930KHz FRCLK as the source of the low frequency clock (LFCLK)
     FreeRunningClock,
@@ -361,6 +954,15 @@ pub enum LfClockSource {
     ExternalOscillator,
 }
 
+impl From<XtOscSlEn> for LfClockSource {
+    fn from(value: XtOscSlEn) -> Self {
+        match value {
+            XtOscSlEn::Lfcg => LfClockSource::FreeRunningClock,
+            XtOscSlEn::Xtosc => LfClockSource::ExternalOscillator,
+        }
+    }
+}
+
 impl From<LfClockSource> for XtOscSlEn {
     fn from(value: LfClockSource) -> Self {
         match value {
@@ -397,7 +999,7 @@ pub enum MclkDivider {
 }
 
 impl MclkDivider {
-    fn div_value(&self) -> u32 {
+    const fn div_value(&self) -> u32 {
         *self as u32 + 1
     }
 }
@@ -415,7 +1017,7 @@ pub enum AhbDivider {
 }
 
 impl AhbDivider {
-    fn div_value(&self) -> u32 {
+    const fn div_value(&self) -> u32 {
         *self as u32 + 1
     }
 }
@@ -433,7 +1035,7 @@ pub enum MclkdDivider {
 }
 
 impl MclkdDivider {
-    fn div_value(&self) -> u32 {
+    const fn div_value(&self) -> u32 {
         *self as u32 + 1
     }
 }
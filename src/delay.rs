@@ -0,0 +1,73 @@
+//! Calibrated busy-waiting
+//!
+//! [block_for] spins in a loop whose duration was measured against the core clock with the DWT cycle counter when the
+//! HAL was initialized, and again after every [set_core_frequency](crate::cdcg::set_core_frequency). It needs neither
+//! a timer nor embassy-time, for hold times of a few microseconds at most. The HAL uses it for the setup and hold times
+//! of [ChipSelect](crate::gpio::ChipSelect), the hardware controllers time their bus conditions themselves.
+//!
+//! Interrupts that fire during the wait lengthen it, the wait is never shorter than requested.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+use cortex_m::peripheral::DWT;
+
+/// The number of iterations timed when calibrating
+const CALIBRATION_LOOPS: u32 = 1024;
+
+/// The core clock cycles per iteration of [spin], times 256
+static CYCLES_PER_LOOP_X256: AtomicU32 = AtomicU32::new(256);
+
+/// Spin for `loops` iterations.
+#[inline(never)]
+fn spin(loops: u32) {
+    for _ in 0..loops {
+        core::hint::spin_loop();
+    }
+}
+
+/// Measure the duration of an iteration of [spin], requires the cycle counter to run.
+pub(crate) fn calibrate() {
+    let cycles = critical_section::with(|_| {
+        // Warm up the flash cache, so its misses are not measured.
+        spin(CALIBRATION_LOOPS / 16);
+
+        let start = DWT::cycle_count();
+        spin(CALIBRATION_LOOPS);
+        DWT::cycle_count().wrapping_sub(start)
+    });
+
+    let per_loop = (cycles * 256 / CALIBRATION_LOOPS).max(1);
+    CYCLES_PER_LOOP_X256.store(per_loop, Ordering::Relaxed);
+}
+
+/// Busy-wait for at least `duration`.
+///
+/// Durations up to a few seconds are supported, longer durations wait for their multiples of a second in turns.
+pub fn block_for(duration: Duration) {
+    let per_loop = u64::from(CYCLES_PER_LOOP_X256.load(Ordering::Relaxed));
+    let hz = u64::from(crate::cdcg::core_hz());
+
+    let mut secs = duration.as_secs();
+    let mut cycles = (u64::from(duration.subsec_nanos()) * hz).div_ceil(1_000_000_000);
+    loop {
+        let loops = (cycles * 256).div_ceil(per_loop);
+        spin(u32::try_from(loops).unwrap_or(u32::MAX));
+
+        if secs == 0 {
+            return;
+        }
+        secs -= 1;
+        cycles = hz;
+    }
+}
+
+/// Busy-wait for at least `ns` nanoseconds.
+pub fn block_for_ns(ns: u32) {
+    block_for(Duration::from_nanos(u64::from(ns)));
+}
+
+/// Busy-wait for at least `us` microseconds.
+pub fn block_for_us(us: u32) {
+    block_for(Duration::from_micros(u64::from(us)));
+}
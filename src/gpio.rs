@@ -1,9 +1,28 @@
 //! Implementation for the GPIO peripheral
+//!
+//! ## Pin ownership
+//! Every ball of the chip is a separate peripheral in [crate::peripherals]. Drivers that mux a ball to an
+//! alternate function take that specific ball by value or by mutable reference, just like the GPIO drivers here.
+//! So a ball can't be claimed by two drivers at once, which the compiler checks. Drivers with several
+//! possible pin assignments only accept valid combinations, like [crate::i2c::ValidI2CConfig].
+//!
+//! The alternate function of a ball is selected in the `DEVALT` registers when a driver is created.
+//! Creating a GPIO driver for a ball switches it back to its GPIO function.
+//!
+//! ## Pin options that can't be configured
+//! The GPIO ports only control the direction, output type, pull resistors and VDD-present behavior of a pin.
+//! The NPCX490 has no per-pin drive strength or slew rate controls, so these can't be configured here.
+//! Where a softer drive is needed, use [OutputOpenDrain] with an external pullup instead.
+//!
+//! Likewise the input buffer type is fixed per pin, and there is no hardware debounce on GPIO inputs.
+//! Switch inputs need to be debounced in software.
 
 use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
 
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 use paste::paste;
+use sealed::SealedPin;
 
 /// The level of a pin
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -33,6 +52,47 @@ impl From<bool> for Level {
     }
 }
 
+/// The pull resistor configuration of a pin
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pull {
+    /// No pull resistor, the pin floats when not driven
+    #[default]
+    None,
+    /// Pull the pin up to the supply voltage
+    Up,
+    /// Pull the pin down to ground
+    Down,
+}
+
+fn set_pull(pin: &AnyPin, pull: Pull) {
+    critical_section::with(|_| {
+        let regs = pin.port();
+
+        match pull {
+            Pull::None => {
+                regs.px_pull().modify(|_, w| w.pin(pin.pin()).disabled());
+            }
+            Pull::Up => {
+                regs.px_pud().modify(|_, w| w.pin(pin.pin()).pull_up());
+                regs.px_pull().modify(|_, w| w.pin(pin.pin()).enabled());
+            }
+            Pull::Down => {
+                regs.px_pud().modify(|_, w| w.pin(pin.pin()).pull_down());
+                regs.px_pull().modify(|_, w| w.pin(pin.pin()).enabled());
+            }
+        }
+    });
+}
+
+fn lock(pin: &AnyPin) {
+    critical_section::with(|_| {
+        let regs = pin.port();
+
+        regs.px_lock_ctl().modify(|_, w| w.pin(pin.pin()).set_bit());
+    });
+}
+
 pub(crate) mod sealed {
     pub trait SealedPin {
         #[must_use]
@@ -41,11 +101,32 @@ pub(crate) mod sealed {
         #[must_use]
         fn port(&self) -> &'static crate::pac::gpio0::RegisterBlock;
 
+        /// The function that muxes the ball to its GPIO function.
+        #[must_use]
+        fn pin_function(&self) -> fn(crate::pac::Sysconfig);
+
+        /// The function that changes whether the ball is low voltage.
+        #[must_use]
+        fn low_voltage_function(&self) -> fn(crate::pac::Sysconfig, bool);
+
+        /// What the ball can be used as, kept when the pin is degraded into an [AnyPin](super::AnyPin).
+        #[must_use]
+        fn kind(&self) -> super::PinKind;
+
+        /// Panics if the pin can not be used as `kind`, which can only happen for an [AnyPin](super::AnyPin).
+        fn assert_kind(&self, kind: super::PinKind) {
+            assert!(self.kind() >= kind, "The pin can not be used as {:?}", kind);
+        }
+
         // Not ideal to mark this unsafe, but PeripheralRef is missing DerefMut...
         /// Safety assumptions:
         /// caller must ensure it has a mutable reference to the pin or
         /// has otherwise elimnated contention.
-        unsafe fn set_pin_function(&self, cs: critical_section::CriticalSection);
+        unsafe fn set_pin_function(&self, _cs: critical_section::CriticalSection) {
+            // Safety:
+            // We have the unique reference to the pin and are within a critical section, so no contention
+            (self.pin_function())(unsafe { crate::pac::Sysconfig::steal() });
+        }
 
         // Not ideal to mark this unsafe, but PeripheralRef is missing DerefMut...
         /// Change whether the pin is low voltage, may do nothing on pins without
@@ -55,40 +136,104 @@ pub(crate) mod sealed {
         /// has otherwise elimnated contention.
         /// caller must ensure this is not called to enable low voltage when the pin
         /// is in a state where it can be damaged by doing that.
-        unsafe fn set_low_voltage(&self, cs: critical_section::CriticalSection, state: bool);
+        unsafe fn set_low_voltage(&self, _cs: critical_section::CriticalSection, state: bool) {
+            // Safety:
+            // We have the unique reference to the pin and are within a critical section, so no contention
+            (self.low_voltage_function())(unsafe { crate::pac::Sysconfig::steal() }, state);
+        }
     }
 
     pub trait SealedInputPin {}
 
+    pub trait SealedGroupOutputPin {
+        fn any_pin(&self) -> &super::AnyPin;
+
+        /// Whether the pin is driven by its direction instead of its output latch.
+        fn is_simulated_open_drain(&self) -> bool {
+            false
+        }
+    }
+
     pub trait SealedLowVoltagePin {}
 }
 
-struct AnyPin {
+/// What a ball can be used as, from least to most capable.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum PinKind {
+    /// An output only pin, see [Pin]
+    Output,
+    /// A pin that can also be an input, see [InputPin]
+    Input,
+    /// An input pin that supports low voltage, see [LowVoltagePin]
+    LowVoltage,
+}
+
+/// A type-erased GPIO pin.
+///
+/// Created with [Pin::degrade], this allows pins to be stored in arrays or passed around
+/// without being generic over the concrete pin type.
+///
+/// The pin remembers what the ball it was degraded from can be used as. It implements [InputPin] and
+/// [LowVoltagePin], so it can be passed to any driver, but the driver panics when it is created if the ball does not
+/// support what it is used for.
+pub struct AnyPin {
     pin: u8,
     port: &'static crate::pac::gpio0::RegisterBlock,
+    pin_function: fn(crate::pac::Sysconfig),
+    low_voltage_function: fn(crate::pac::Sysconfig, bool),
+    kind: PinKind,
 }
 
-impl AnyPin {
-    #[must_use]
+impl core::fmt::Debug for AnyPin {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AnyPin")
+            .field("port", &PortId::from_regs(self.port))
+            .field("pin", &self.pin)
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AnyPin {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "AnyPin {{ port: {}, pin: {} }}",
+            PortId::from_regs(self.port),
+            self.pin
+        )
+    }
+}
+
+impl sealed::SealedPin for AnyPin {
     fn pin(&self) -> u8 {
         self.pin
     }
 
-    #[must_use]
     fn port(&self) -> &'static crate::pac::gpio0::RegisterBlock {
         self.port
     }
-}
 
-impl<T: Pin> From<T> for AnyPin {
-    fn from(pin: T) -> Self {
-        AnyPin {
-            pin: pin.pin(),
-            port: pin.port(),
-        }
+    fn pin_function(&self) -> fn(crate::pac::Sysconfig) {
+        self.pin_function
+    }
+
+    fn low_voltage_function(&self) -> fn(crate::pac::Sysconfig, bool) {
+        self.low_voltage_function
+    }
+
+    fn kind(&self) -> PinKind {
+        self.kind
     }
 }
 
+impl Pin for AnyPin {}
+impl sealed::SealedInputPin for AnyPin {}
+impl InputPin for AnyPin {}
+impl sealed::SealedLowVoltagePin for AnyPin {}
+impl LowVoltagePin for AnyPin {}
+
 // Allow use of PeripheralRef to do lifetime management
 impl Peripheral for AnyPin {
     type P = AnyPin;
@@ -97,21 +242,37 @@ impl Peripheral for AnyPin {
         AnyPin {
             pin: self.pin,
             port: self.port,
+            pin_function: self.pin_function,
+            low_voltage_function: self.low_voltage_function,
+            kind: self.kind,
         }
     }
 }
 
 /// This pin can only be an output
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct OutputOnly {}
 /// This pin can be both input and output
+///
+/// Outputs of such pins keep their input buffer enabled, so the level on the pin can be read back.
+/// Together with [OutputOpenDrain] this can be used for wired-AND lines like SMBALERT#.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct InputCapable {}
 
 /// This pin can do pull-down and pull-up
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct CanPullUp {}
 /// This pin can only do pull-down
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PullDownOnly {}
 
 /// A pin configured in input mode
+///
+/// Pins with a WakeUpInput channel can wake up the chip from a low power state, see [Input::enable_wake].
 pub struct Input<'d, T> {
     pin: PeripheralRef<'d, AnyPin>,
     _phantom: PhantomData<T>,
@@ -121,6 +282,7 @@ impl<'d> Input<'d, CanPullUp> {
     /// Configure the pin in input mode
     pub fn new(pin: impl Peripheral<P = impl InputPin + 'd> + 'd) -> Self {
         into_ref!(pin);
+        pin.assert_kind(PinKind::Input);
 
         critical_section::with(|cs| {
             // Safety:
@@ -139,6 +301,13 @@ impl<'d> Input<'d, CanPullUp> {
         }
     }
 
+    /// Configure the pin in input mode with the given pull resistor configuration
+    pub fn new_with_pull(pin: impl Peripheral<P = impl InputPin + 'd> + 'd, pull: Pull) -> Self {
+        let mut input = Self::new(pin);
+        input.set_pull(pull);
+        input
+    }
+
     /// Enable the pullup on the pin
     pub fn enable_pullup(&mut self) {
         critical_section::with(|_| {
@@ -149,6 +318,11 @@ impl<'d> Input<'d, CanPullUp> {
         });
     }
 
+    /// Change the pull resistor configuration of the pin
+    pub fn set_pull(&mut self, pull: Pull) {
+        set_pull(&self.pin, pull);
+    }
+
     /// Degrade into a pin that can only do pulldown.
     /// This doesn't change anything to the hardware, but can make it easier in the typesystem to deal with.
     #[must_use]
@@ -164,6 +338,7 @@ impl<'d> Input<'d, PullDownOnly> {
     /// Put a low voltage pin in input name
     pub fn new_lowvoltage(pin: impl Peripheral<P = impl LowVoltagePin + 'd> + 'd) -> Self {
         into_ref!(pin);
+        pin.assert_kind(PinKind::LowVoltage);
 
         critical_section::with(|cs| {
             let regs = pin.port();
@@ -187,7 +362,21 @@ impl<'d> Input<'d, PullDownOnly> {
     }
 }
 
+impl<'d, T> Input<'d, T> {
+    /// Lock the configuration of the pin until the next reset.
+    #[must_use]
+    pub fn lock(self) -> LockedPin<'d> {
+        lock(&self.pin);
+        LockedPin { pin: self.pin }
+    }
+}
+
 impl<T> Input<'_, T> {
+    /// The type-erased pin, for drivers building on top of [Input]
+    pub(crate) fn any_pin(&self) -> &AnyPin {
+        &self.pin
+    }
+
     /// Disable any pullup or pulldown on this pin
     pub fn disable_pull(&mut self) {
         critical_section::with(|_| {
@@ -266,6 +455,7 @@ impl<'d> OutputOpenDrain<'d, InputCapable> {
     /// Configure a pin as open-drain
     pub fn new(pin: impl Peripheral<P = impl InputPin + 'd> + 'd, level: Level) -> Self {
         into_ref!(pin);
+        pin.assert_kind(PinKind::Input);
 
         critical_section::with(|cs| {
             // Safety:
@@ -287,6 +477,34 @@ impl<'d> OutputOpenDrain<'d, InputCapable> {
         }
     }
 
+    /// Configure a low voltage pin as open-drain
+    pub fn new_lowvoltage(pin: impl Peripheral<P = impl LowVoltagePin + 'd> + 'd, level: Level) -> Self {
+        into_ref!(pin);
+        pin.assert_kind(PinKind::LowVoltage);
+
+        critical_section::with(|cs| {
+            let regs = pin.port();
+
+            // Required for low voltage operation
+            regs.px_pud().modify(|_, w| w.pin(pin.pin()).pull_down());
+            regs.px_otype().modify(|_, w| w.pin(pin.pin()).opendrain());
+            // Latch the level before switching to output, so the pin never drives a stale level
+            regs.px_dout().modify(|_, w| w.pin(pin.pin()).bit(level.into()));
+            regs.px_dir().modify(|_, w| w.pin(pin.pin()).output());
+
+            // Safety:
+            // We have a mutable reference to the pin through PeripheralRef
+            // Everything is put in a safe state for low voltage above
+            unsafe { pin.set_pin_function(cs) };
+            unsafe { pin.set_low_voltage(cs, true) };
+        });
+
+        OutputOpenDrain {
+            pin: pin.map_into(),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Degrade the pin into an [OutputOnly] pin.
     /// This doesn't change anything in the hardware, but can be nice for managing the typesystem.
     pub fn degrade(self) -> OutputOpenDrain<'d, OutputOnly> {
@@ -297,6 +515,15 @@ impl<'d> OutputOpenDrain<'d, InputCapable> {
     }
 }
 
+impl<'d, T> OutputOpenDrain<'d, T> {
+    /// Lock the configuration and level of the pin until the next reset.
+    #[must_use]
+    pub fn lock(self) -> LockedPin<'d> {
+        lock(&self.pin);
+        LockedPin { pin: self.pin }
+    }
+}
+
 impl<T> OutputOpenDrain<'_, T> {
     /// Set the pin low
     pub fn set_low(&mut self) {
@@ -330,7 +557,7 @@ impl<T> OutputOpenDrain<'_, T> {
 
     /// Returns true if the pin is set low
     #[must_use]
-    pub fn is_set_low(&mut self) -> bool {
+    pub fn is_set_low(&self) -> bool {
         let regs = self.pin.port();
 
         regs.px_dout().read().pin(self.pin.pin()).is_low()
@@ -338,12 +565,18 @@ impl<T> OutputOpenDrain<'_, T> {
 
     /// Returns true if the pin is set high
     #[must_use]
-    pub fn is_set_high(&mut self) -> bool {
+    pub fn is_set_high(&self) -> bool {
         let regs = self.pin.port();
 
         regs.px_dout().read().pin(self.pin.pin()).is_high()
     }
 
+    /// Returns the level the output of the pin is set to, regardless of the level sensed on the pin
+    #[must_use]
+    pub fn get_output_level(&self) -> Level {
+        self.is_set_high().into()
+    }
+
     /// Toggles the pin. If it was previously high, it will now be low and vice versa.
     pub fn toggle(&mut self) {
         critical_section::with(|_| {
@@ -388,11 +621,49 @@ impl OutputOpenDrain<'_, InputCapable> {
             regs.px_envdd().modify(|_, w| w.pin(self.pin.pin()).enabled());
         })
     }
+
+    /// Enable the internal pullup, so the line is pulled high when it is not driven low.
+    ///
+    /// Useful for short buses without external pullups, but note the internal pullup is weak.
+    pub fn enable_pullup(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_pud().modify(|_, w| w.pin(self.pin.pin()).pull_up());
+            regs.px_pull().modify(|_, w| w.pin(self.pin.pin()).enabled());
+        });
+    }
+
+    /// Disable the internal pullup, leaving the line to float when not driven low.
+    pub fn disable_pull(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_pull().modify(|_, w| w.pin(self.pin.pin()).disabled());
+        });
+    }
+}
+
+/// What an [Output] does with the pin when it is dropped
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DropBehavior {
+    /// Leave the pin as it is, driving the level it was last set to
+    #[default]
+    Keep,
+    /// Stop driving the pin, leaving it floating
+    HighZ,
+    /// Drive the pin to this level
+    Level(Level),
 }
 
 /// A pin configured as output
+///
+/// The level is latched before the pin is switched to output, so construction doesn't glitch.
+/// What happens to the pin when this is dropped can be chosen with [Output::set_drop_behavior].
 pub struct Output<'d, T> {
     pin: PeripheralRef<'d, AnyPin>,
+    on_drop: DropBehavior,
     _phantom: PhantomData<T>,
 }
 
@@ -417,6 +688,7 @@ impl<'d> Output<'d, OutputOnly> {
 
         Output {
             pin: pin.map_into(),
+            on_drop: DropBehavior::Keep,
             _phantom: PhantomData,
         }
     }
@@ -426,6 +698,7 @@ impl<'d> Output<'d, InputCapable> {
     /// Configure a pin as output
     pub fn new(pin: impl Peripheral<P = impl InputPin + 'd> + 'd, level: Level) -> Self {
         into_ref!(pin);
+        pin.assert_kind(PinKind::Input);
 
         critical_section::with(|cs| {
             // Safety:
@@ -443,6 +716,7 @@ impl<'d> Output<'d, InputCapable> {
 
         Output {
             pin: pin.map_into(),
+            on_drop: DropBehavior::Keep,
             _phantom: PhantomData,
         }
     }
@@ -450,6 +724,7 @@ impl<'d> Output<'d, InputCapable> {
     /// Configure a pin as output
     pub fn new_lowvoltage(pin: impl Peripheral<P = impl LowVoltagePin + 'd> + 'd, level: Level) -> Self {
         into_ref!(pin);
+        pin.assert_kind(PinKind::LowVoltage);
 
         critical_section::with(|cs| {
             let regs = pin.port();
@@ -457,8 +732,9 @@ impl<'d> Output<'d, InputCapable> {
             // Required for low voltage operation
             regs.px_pud().modify(|_, w| w.pin(pin.pin()).pull_down());
             regs.px_otype().modify(|_, w| w.pin(pin.pin()).opendrain());
-            regs.px_dir().modify(|_, w| w.pin(pin.pin()).output());
+            // Latch the level before switching to output, so the pin never drives a stale level
             regs.px_dout().modify(|_, w| w.pin(pin.pin()).bit(level.into()));
+            regs.px_dir().modify(|_, w| w.pin(pin.pin()).output());
 
             // Safety:
             // We have a mutable reference to the pin through PeripheralRef
@@ -469,6 +745,7 @@ impl<'d> Output<'d, InputCapable> {
 
         Self {
             pin: pin.map_into(),
+            on_drop: DropBehavior::Keep,
             _phantom: PhantomData,
         }
     }
@@ -477,14 +754,39 @@ impl<'d> Output<'d, InputCapable> {
     /// This doesn't change anything in hardware, but can make the pin nicer to work with in the typesystem.
     #[must_use]
     pub fn degrade(self) -> Output<'d, OutputOnly> {
+        let this = ManuallyDrop::new(self);
+
         Output {
-            pin: self.pin,
+            // Safety: `this` is never dropped, so the pin is only moved out once
+            pin: unsafe { core::ptr::read(&this.pin) },
+            on_drop: this.on_drop,
             _phantom: PhantomData,
         }
     }
 }
 
+impl<'d, T> Output<'d, T> {
+    /// Lock the configuration and level of the pin until the next reset.
+    ///
+    /// The drop behavior is not applied, as the pin can no longer be changed.
+    #[must_use]
+    pub fn lock(self) -> LockedPin<'d> {
+        let this = ManuallyDrop::new(self);
+        lock(&this.pin);
+
+        LockedPin {
+            // Safety: `this` is never dropped, so the pin is only moved out once
+            pin: unsafe { core::ptr::read(&this.pin) },
+        }
+    }
+}
+
 impl<T> Output<'_, T> {
+    /// Choose what happens to the pin when this driver is dropped
+    pub fn set_drop_behavior(&mut self, behavior: DropBehavior) {
+        self.on_drop = behavior;
+    }
+
     /// Set the pin low
     pub fn set_low(&mut self) {
         critical_section::with(|_| {
@@ -517,20 +819,26 @@ impl<T> Output<'_, T> {
 
     /// Returns true if the pin is set to low
     #[must_use]
-    pub fn is_set_low(&mut self) -> bool {
+    pub fn is_set_low(&self) -> bool {
         let regs = self.pin.port();
 
         regs.px_dout().read().pin(self.pin.pin()).is_low()
     }
 
-    /// Returns true if the pin is set to low
+    /// Returns true if the pin is set to high
     #[must_use]
-    pub fn is_set_high(&mut self) -> bool {
+    pub fn is_set_high(&self) -> bool {
         let regs = self.pin.port();
 
         regs.px_dout().read().pin(self.pin.pin()).is_high()
     }
 
+    /// Returns the level the output of the pin is set to, regardless of the level sensed on the pin
+    #[must_use]
+    pub fn get_output_level(&self) -> Level {
+        self.is_set_high().into()
+    }
+
     /// Toggle the pin. If it was previously high, it will now be low and vice versa.
     pub fn toggle(&mut self) {
         critical_section::with(|_| {
@@ -542,7 +850,39 @@ impl<T> Output<'_, T> {
     }
 }
 
+impl<T> Drop for Output<'_, T> {
+    fn drop(&mut self) {
+        match self.on_drop {
+            DropBehavior::Keep => {}
+            DropBehavior::HighZ => critical_section::with(|_| {
+                let regs = self.pin.port();
+
+                // An undriven open-drain output floats, which works for output-only pins as well
+                regs.px_dout().modify(|_, w| w.pin(self.pin.pin()).set_bit());
+                regs.px_otype().modify(|_, w| w.pin(self.pin.pin()).opendrain());
+            }),
+            DropBehavior::Level(level) => self.set_value(level),
+        }
+    }
+}
+
 impl Output<'_, InputCapable> {
+    /// Returns true if the pin senses a low level
+    #[must_use]
+    pub fn is_low(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_low()
+    }
+
+    /// Returns true if the pin senses a high level
+    #[must_use]
+    pub fn is_high(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_high()
+    }
+
     /// Make the pin be driven always, even when the core is unpowered.
     /// This is the default.
     pub fn drive_always(&self) {
@@ -561,108 +901,1185 @@ impl Output<'_, InputCapable> {
     }
 }
 
-impl<T> embedded_hal::digital::ErrorType for Input<'_, T> {
-    type Error = core::convert::Infallible;
+/// A chip select line driven by an [Output], with consistent setup and hold times.
+///
+/// The delays are busy-waits on the core clock, rounded up to whole cycles.
+pub struct ChipSelect<'d, T> {
+    pin: Output<'d, T>,
+    active: Level,
+    setup_ns: u32,
+    hold_ns: u32,
 }
 
-impl<T> embedded_hal::digital::InputPin for Input<'_, T> {
-    fn is_high(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_high())
+impl<'d, T> ChipSelect<'d, T> {
+    /// Use the output as chip select that is asserted at the `active` level. The chip select is deasserted immediately.
+    pub fn new(mut pin: Output<'d, T>, active: Level) -> Self {
+        pin.set_value(inactive_level(active));
+
+        Self {
+            pin,
+            active,
+            setup_ns: 0,
+            hold_ns: 0,
+        }
     }
 
-    fn is_low(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_low())
+    /// Set the time between asserting the chip select and returning from [Self::assert]
+    #[must_use]
+    pub fn with_setup_ns(mut self, setup_ns: u32) -> Self {
+        self.setup_ns = setup_ns;
+        self
     }
-}
 
-impl<T> embedded_hal::digital::ErrorType for Output<'_, T> {
-    type Error = core::convert::Infallible;
-}
+    /// Set the time between calling [Self::deassert] and deasserting the chip select
+    #[must_use]
+    pub fn with_hold_ns(mut self, hold_ns: u32) -> Self {
+        self.hold_ns = hold_ns;
+        self
+    }
 
-impl<T> embedded_hal::digital::OutputPin for Output<'_, T> {
-    fn set_low(&mut self) -> Result<(), Self::Error> {
-        (*self).set_low();
-        Ok(())
+    /// Assert the chip select, and wait for the setup time
+    pub fn assert(&mut self) {
+        self.pin.set_value(self.active);
+        delay_ns(self.setup_ns);
     }
 
-    fn set_high(&mut self) -> Result<(), Self::Error> {
-        (*self).set_high();
-        Ok(())
+    /// Wait for the hold time, and deassert the chip select
+    pub fn deassert(&mut self) {
+        delay_ns(self.hold_ns);
+        self.pin.set_value(inactive_level(self.active));
     }
-}
 
-impl<T> embedded_hal::digital::StatefulOutputPin for Output<'_, T> {
-    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_set_high())
+    /// Returns true if the chip select is asserted
+    #[must_use]
+    pub fn is_asserted(&self) -> bool {
+        self.pin.get_output_level() == self.active
     }
 
-    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_set_low())
+    /// Run `f` with the chip select asserted, including the setup and hold times
+    pub fn with_asserted<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        self.assert();
+        let result = f();
+        self.deassert();
+        result
     }
 
-    fn toggle(&mut self) -> Result<(), Self::Error> {
-        (*self).toggle();
-        Ok(())
+    /// Release the underlying [Output], leaving the chip select at its current level
+    pub fn into_inner(self) -> Output<'d, T> {
+        self.pin
     }
 }
 
-impl<T> embedded_hal::digital::ErrorType for OutputOpenDrain<'_, T> {
-    type Error = core::convert::Infallible;
+fn inactive_level(active: Level) -> Level {
+    match active {
+        Level::Low => Level::High,
+        Level::High => Level::Low,
+    }
 }
 
-impl<T> embedded_hal::digital::OutputPin for OutputOpenDrain<'_, T> {
-    fn set_low(&mut self) -> Result<(), Self::Error> {
-        (*self).set_low();
-        Ok(())
+fn delay_ns(ns: u32) {
+    if ns == 0 {
+        return;
     }
 
-    fn set_high(&mut self) -> Result<(), Self::Error> {
-        (*self).set_high();
-        Ok(())
-    }
+    crate::delay::block_for_ns(ns);
 }
 
-impl<T> embedded_hal::digital::StatefulOutputPin for OutputOpenDrain<'_, T> {
-    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_set_high())
+/// A pin of which the configuration has been locked until the next reset.
+///
+/// Useful for security relevant straps, like write protect outputs, that must not be changed by later code.
+/// The hardware rejects any changes to the pin, so it can only be observed.
+pub struct LockedPin<'d> {
+    pin: PeripheralRef<'d, AnyPin>,
+}
+
+impl LockedPin<'_> {
+    /// Returns true if the pin senses a low level
+    #[must_use]
+    pub fn is_low(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_low()
     }
 
-    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_set_low())
+    /// Returns true if the pin senses a high level
+    #[must_use]
+    pub fn is_high(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_high()
     }
 
-    fn toggle(&mut self) -> Result<(), Self::Error> {
-        (*self).toggle();
-        Ok(())
+    /// Returns true if the output of the pin is set low
+    #[must_use]
+    pub fn is_set_low(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_dout().read().pin(self.pin.pin()).is_low()
     }
-}
 
-impl embedded_hal::digital::InputPin for OutputOpenDrain<'_, InputCapable> {
-    fn is_high(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_high())
+    /// Returns true if the output of the pin is set high
+    #[must_use]
+    pub fn is_set_high(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_dout().read().pin(self.pin.pin()).is_high()
     }
 
-    fn is_low(&mut self) -> Result<bool, Self::Error> {
-        Ok((*self).is_low())
+    /// Returns the level the output of the pin is set to, regardless of the level sensed on the pin
+    #[must_use]
+    pub fn get_output_level(&self) -> Level {
+        self.is_set_high().into()
     }
 }
 
-/// A marker trait implemented for all pins that can function as an input but only support low voltage
-pub trait LowVoltagePin: InputPin + sealed::SealedLowVoltagePin {}
+/// A pin that can be switched between input and output at runtime.
+///
+/// Useful for protocols that turn the line around mid-transaction, like 1-Wire.
+pub struct Flex<'d> {
+    pin: PeripheralRef<'d, AnyPin>,
+    simulated_open_drain: bool,
+}
 
-/// A marker trait implemented for all pins that can function as an input
-pub trait InputPin: Pin + sealed::SealedInputPin {}
+impl<'d> Flex<'d> {
+    /// Take the pin for flexible use. The pin starts out as an input.
+    pub fn new(pin: impl Peripheral<P = impl InputPin + 'd> + 'd) -> Self {
+        into_ref!(pin);
+        pin.assert_kind(PinKind::Input);
 
-/// A marker trait implemented for all pins
-pub trait Pin: sealed::SealedPin {}
+        critical_section::with(|cs| {
+            // Safety:
+            // We have a mutable reference to the pin through PeripheralRef
+            unsafe { pin.set_low_voltage(cs, false) };
+            unsafe { pin.set_pin_function(cs) };
 
-macro_rules! impl_pin {
-    ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr) => {
-        impl_pin!($peripheral, $port, $pin, $pin_function, |_, _| {});
-    };
-    ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
-        impl sealed::SealedPin for crate::peripherals::$peripheral {
-            fn pin(&self) -> u8 {
+            let regs = pin.port();
+
+            regs.px_dir().modify(|_, w| w.pin(pin.pin()).input());
+        });
+
+        Flex {
+            pin: pin.map_into(),
+            simulated_open_drain: false,
+        }
+    }
+
+    /// Stop driving the pin and use it as an input.
+    pub fn set_as_input(&mut self) {
+        self.simulated_open_drain = false;
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).input());
+        });
+    }
+
+    /// Drive the pin as a push-pull output.
+    ///
+    /// The pin is driven with the level last set with [Self::set_value] and friends,
+    /// so set the level first to switch direction without a glitch.
+    pub fn set_as_output(&mut self) {
+        self.simulated_open_drain = false;
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_otype().modify(|_, w| w.pin(self.pin.pin()).pushpull());
+            regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).output());
+        });
+    }
+
+    /// Drive the pin as an open-drain output.
+    ///
+    /// The pin is driven with the level last set with [Self::set_value] and friends,
+    /// so set the level first to switch direction without a glitch.
+    pub fn set_as_open_drain(&mut self) {
+        self.simulated_open_drain = false;
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_otype().modify(|_, w| w.pin(self.pin.pin()).opendrain());
+            regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).output());
+        });
+    }
+
+    /// Drive the pin as a simulated open-drain output, for pins that do not support true open-drain.
+    ///
+    /// Instead of changing the output level, the pin is driven low by making it a push-pull output and released by
+    /// making it an input. The output latch is kept low. Use [Self::enable_pullup] or an external pullup to pull the
+    /// line high when released.
+    ///
+    /// The output starts out released (high).
+    pub fn set_as_simulated_open_drain(&mut self) {
+        self.simulated_open_drain = true;
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).input());
+            regs.px_otype().modify(|_, w| w.pin(self.pin.pin()).pushpull());
+            regs.px_dout().modify(|_, w| w.pin(self.pin.pin()).clear_bit());
+        });
+    }
+
+    /// Returns true if the pin is in simulated open-drain mode
+    #[must_use]
+    pub fn is_simulated_open_drain(&self) -> bool {
+        self.simulated_open_drain
+    }
+
+    /// Returns true if the pin senses a low level, regardless of direction
+    #[must_use]
+    pub fn is_low(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_low()
+    }
+
+    /// Returns true if the pin senses a high level, regardless of direction
+    #[must_use]
+    pub fn is_high(&self) -> bool {
+        let regs = self.pin.port();
+
+        regs.px_din().read().pin(self.pin.pin()).is_high()
+    }
+
+    /// Returns the current level of the pin
+    #[must_use]
+    pub fn level(&self) -> Level {
+        self.is_high().into()
+    }
+
+    /// Set the output level low. Only has effect on the pin while it is an output.
+    pub fn set_low(&mut self) {
+        self.set_value(Level::Low);
+    }
+
+    /// Set the output level high. Only has effect on the pin while it is an output.
+    pub fn set_high(&mut self) {
+        self.set_value(Level::High);
+    }
+
+    /// Set the output level. Only has effect on the pin while it is an output.
+    ///
+    /// In simulated open-drain mode, this drives the pin low or releases it.
+    pub fn set_value(&mut self, value: Level) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            if self.simulated_open_drain {
+                // Set direction, the output latch stays low
+                match value {
+                    Level::Low => regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).output()),
+                    Level::High => regs.px_dir().modify(|_, w| w.pin(self.pin.pin()).input()),
+                };
+            } else {
+                // Set data
+                regs.px_dout().modify(|_, w| w.pin(self.pin.pin()).bit(value.into()));
+            }
+        });
+    }
+
+    /// Returns true if the output level is set low
+    #[must_use]
+    pub fn is_set_low(&self) -> bool {
+        !self.is_set_high()
+    }
+
+    /// Returns true if the output level is set high
+    ///
+    /// In simulated open-drain mode, this returns true if the pin is released.
+    #[must_use]
+    pub fn is_set_high(&self) -> bool {
+        let regs = self.pin.port();
+
+        if self.simulated_open_drain {
+            regs.px_dir().read().pin(self.pin.pin()).is_input()
+        } else {
+            regs.px_dout().read().pin(self.pin.pin()).is_high()
+        }
+    }
+
+    /// Returns the level the output of the pin is set to, regardless of the level sensed on the pin
+    #[must_use]
+    pub fn get_output_level(&self) -> Level {
+        self.is_set_high().into()
+    }
+
+    /// Toggle the output level. If it was previously high, it will now be low and vice versa.
+    pub fn toggle(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            if self.simulated_open_drain {
+                regs.px_dir()
+                    .modify(|r, w| w.pin(self.pin.pin()).bit(r.pin(self.pin.pin()).is_input()));
+            } else {
+                regs.px_dout()
+                    .modify(|r, w| w.pin(self.pin.pin()).bit(r.pin(self.pin.pin()).is_low()));
+            }
+        });
+    }
+
+    /// Enable the pullup on the pin
+    pub fn enable_pullup(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_pud().modify(|_, w| w.pin(self.pin.pin()).pull_up());
+            regs.px_pull().modify(|_, w| w.pin(self.pin.pin()).enabled());
+        });
+    }
+
+    /// Enable the pulldown on the pin
+    pub fn enable_pulldown(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_pud().modify(|_, w| w.pin(self.pin.pin()).pull_down());
+            regs.px_pull().modify(|_, w| w.pin(self.pin.pin()).enabled());
+        });
+    }
+
+    /// Disable any pullup or pulldown on this pin
+    pub fn disable_pull(&mut self) {
+        critical_section::with(|_| {
+            let regs = self.pin.port();
+
+            regs.px_pull().modify(|_, w| w.pin(self.pin.pin()).disabled());
+        });
+    }
+
+    /// Change the pull resistor configuration of the pin
+    pub fn set_pull(&mut self, pull: Pull) {
+        set_pull(&self.pin, pull);
+    }
+}
+
+/// A marker trait implemented for all output drivers that can be part of a [PinGroup]
+pub trait GroupOutputPin: sealed::SealedGroupOutputPin {}
+
+impl<T> sealed::SealedGroupOutputPin for Output<'_, T> {
+    fn any_pin(&self) -> &AnyPin {
+        &self.pin
+    }
+}
+impl<T> GroupOutputPin for Output<'_, T> {}
+
+impl<T> sealed::SealedGroupOutputPin for OutputOpenDrain<'_, T> {
+    fn any_pin(&self) -> &AnyPin {
+        &self.pin
+    }
+}
+impl<T> GroupOutputPin for OutputOpenDrain<'_, T> {}
+
+impl sealed::SealedGroupOutputPin for Flex<'_> {
+    fn any_pin(&self) -> &AnyPin {
+        &self.pin
+    }
+
+    fn is_simulated_open_drain(&self) -> bool {
+        self.simulated_open_drain
+    }
+}
+impl GroupOutputPin for Flex<'_> {}
+
+/// A group of up to `N` output pins, possibly on different ports, that are set at once.
+///
+/// All levels are written within a single critical section, with a single write per port.
+/// This keeps the skew between the pins small, for example when strobing keyboard scan columns.
+///
+/// A [Flex] in simulated open-drain mode is driven through its direction like [Flex::set_value] does, with a second
+/// write to the direction register of its port.
+pub struct PinGroup<'a, const N: usize> {
+    /// The pins, and whether they are in simulated open-drain mode
+    pins: [Option<(&'a AnyPin, bool)>; N],
+    len: usize,
+}
+
+impl<'a, const N: usize> PinGroup<'a, N> {
+    /// Create an empty group
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            pins: [None; N],
+            len: 0,
+        }
+    }
+
+    /// Add a pin to the group. Pins are indexed in the order they are added.
+    ///
+    /// Panics if the group is already full.
+    #[must_use]
+    pub fn add(mut self, pin: &'a mut impl GroupOutputPin) -> Self {
+        assert!(self.len < N, "PinGroup is full");
+        self.pins[self.len] = Some((pin.any_pin(), pin.is_simulated_open_drain()));
+        self.len += 1;
+        self
+    }
+
+    /// Returns the number of pins in the group
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no pins in the group
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Set the level of every pin in the group, `levels[i]` being the level of the i-th added pin.
+    ///
+    /// Panics if the number of levels doesn't match the number of pins.
+    pub fn set_levels(&mut self, levels: &[Level]) {
+        assert_eq!(levels.len(), self.len, "Number of levels must match the number of pins");
+
+        let pins = &self.pins[..self.len];
+
+        critical_section::with(|_| {
+            for (i, (pin, _)) in pins.iter().flatten().enumerate() {
+                let port = pin.port();
+
+                // Each port is handled when its first pin is encountered
+                if pins[..i].iter().flatten().any(|(p, _)| core::ptr::eq(p.port(), port)) {
+                    continue;
+                }
+
+                let port_pins = || {
+                    pins[i..]
+                        .iter()
+                        .flatten()
+                        .zip(&levels[i..])
+                        .filter(|((p, _), _)| core::ptr::eq(p.port(), port))
+                };
+
+                let (mask, value) = port_pins().filter(|((_, simulated), _)| !simulated).fold(
+                    (0u8, 0u8),
+                    |(mask, value), ((p, _), &level)| {
+                        (mask | 1 << p.pin(), value | (u8::from(bool::from(level)) << p.pin()))
+                    },
+                );
+                if mask != 0 {
+                    port.px_dout()
+                        .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | value) });
+                }
+
+                // Simulated open-drain pins are outputs (driving their low latch) for low, and inputs for high
+                let (mask, output) = port_pins().filter(|((_, simulated), _)| *simulated).fold(
+                    (0u8, 0u8),
+                    |(mask, output), ((p, _), &level)| {
+                        (mask | 1 << p.pin(), output | (u8::from(level == Level::Low) << p.pin()))
+                    },
+                );
+                if mask != 0 {
+                    port.px_dir()
+                        .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | output) });
+                }
+            }
+        });
+    }
+
+    /// Set all pins in the group to the same level
+    pub fn set_all(&mut self, level: Level) {
+        self.set_levels(&[level; N][..self.len]);
+    }
+
+    /// Set all pins in the group low
+    pub fn set_all_low(&mut self) {
+        self.set_all(Level::Low);
+    }
+
+    /// Set all pins in the group high
+    pub fn set_all_high(&mut self) {
+        self.set_all(Level::High);
+    }
+}
+
+impl<const N: usize> Default for PinGroup<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Driver for all 8 pins of a GPIO port, for reading and writing the whole port at once.
+///
+/// Only ports of which all pins are bonded out and input capable can be used this way.
+/// Bit `n` of the values read and written corresponds to pin `n` of the port.
+pub struct Port<'d> {
+    port: &'static crate::pac::gpio0::RegisterBlock,
+    _pins: [PeripheralRef<'d, AnyPin>; 8],
+}
+
+impl<'d> Port<'d> {
+    fn new_inner(pins: [PeripheralRef<'d, AnyPin>; 8]) -> Self {
+        let port = pins[0].port();
+
+        critical_section::with(|cs| {
+            for pin in &pins {
+                // Safety:
+                // We have a mutable reference to the pins through PeripheralRef
+                unsafe { pin.set_low_voltage(cs, false) };
+                unsafe { pin.set_pin_function(cs) };
+            }
+
+            port.px_dir().write(|w| unsafe { w.bits(0x00) });
+        });
+
+        Port { port, _pins: pins }
+    }
+
+    /// Set the direction of every pin of the port. A set bit makes the pin a push-pull output,
+    /// a cleared bit makes it an input.
+    ///
+    /// Outputs are driven with the value last written with [Self::write],
+    /// so write the value first to switch direction without a glitch.
+    pub fn set_direction(&mut self, outputs: u8) {
+        critical_section::with(|_| {
+            self.port.px_otype().write(|w| unsafe { w.bits(0x00) });
+            self.port.px_dir().write(|w| unsafe { w.bits(outputs) });
+        });
+    }
+
+    /// Make all pins of the port inputs
+    pub fn set_as_input(&mut self) {
+        self.set_direction(0x00);
+    }
+
+    /// Make all pins of the port push-pull outputs
+    pub fn set_as_output(&mut self) {
+        self.set_direction(0xFF);
+    }
+
+    /// Read the levels sensed on all pins of the port
+    #[must_use]
+    pub fn read(&self) -> u8 {
+        self.port.px_din().read().bits()
+    }
+
+    /// Set the output levels of all pins of the port in a single write
+    pub fn write(&mut self, value: u8) {
+        self.port.px_dout().write(|w| unsafe { w.bits(value) });
+    }
+
+    /// Set the output levels of only the pins selected by `mask` in a single write
+    pub fn modify(&mut self, mask: u8, value: u8) {
+        critical_section::with(|_| {
+            self.port
+                .px_dout()
+                .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | (value & mask)) });
+        });
+    }
+
+    /// Returns the output levels the pins of the port are set to
+    #[must_use]
+    pub fn output_value(&self) -> u8 {
+        self.port.px_dout().read().bits()
+    }
+}
+
+macro_rules! impl_port {
+    ($port:ident, $p0:ident, $p1:ident, $p2:ident, $p3:ident, $p4:ident, $p5:ident, $p6:ident, $p7:ident) => {
+        paste! {
+            impl<'d> Port<'d> {
+                #[doc = "Take all pins of [`" $port "`](crate::pac::" $port "). All pins start out as inputs."]
+                #[allow(clippy::too_many_arguments)]
+                pub fn [<new_ $port:lower>](
+                    p0: impl Peripheral<P = $p0> + 'd,
+                    p1: impl Peripheral<P = $p1> + 'd,
+                    p2: impl Peripheral<P = $p2> + 'd,
+                    p3: impl Peripheral<P = $p3> + 'd,
+                    p4: impl Peripheral<P = $p4> + 'd,
+                    p5: impl Peripheral<P = $p5> + 'd,
+                    p6: impl Peripheral<P = $p6> + 'd,
+                    p7: impl Peripheral<P = $p7> + 'd,
+                ) -> Self {
+                    into_ref!(p0, p1, p2, p3, p4, p5, p6, p7);
+
+                    Self::new_inner([
+                        p0.map_into(),
+                        p1.map_into(),
+                        p2.map_into(),
+                        p3.map_into(),
+                        p4.map_into(),
+                        p5.map_into(),
+                        p6.map_into(),
+                        p7.map_into(),
+                    ])
+                }
+            }
+
+            impl [<$port Pins>] {
+                /// Turn all pins of the port into a [Port] driver. All pins start out as inputs.
+                pub fn into_port(self) -> Port<'static> {
+                    Port::[<new_ $port:lower>](self.p0, self.p1, self.p2, self.p3, self.p4, self.p5, self.p6, self.p7)
+                }
+            }
+        }
+    };
+}
+
+impl_port!(Gpio0, Gpio00, Gpio01, Gpio02, Gpio03, Gpio04, Gpio05, Gpio06, Gpio07);
+impl_port!(Gpio1, Gpio10, Gpio11, Gpio12, Gpio13, Gpio14, Gpio15, Gpio16, Gpio17);
+impl_port!(Gpio2, Gpio20, Gpio21, Gpio22, Gpio23, Gpio24, Gpio25, Gpio26, Gpio27);
+impl_port!(Gpio4, Gpio40, Gpio41, Gpio42, Gpio43, Gpio44, Gpio45, Gpio46, Gpio47);
+impl_port!(Gpio5, Gpio50, Gpio51, Gpio52, Gpio53, Gpio54, Gpio55, Gpio56, Gpio57);
+impl_port!(Gpio9, Gpio90, Gpio91, Gpio92, Gpio93, Gpio94, Gpio95, Gpio96, Gpio97);
+impl_port!(Gpioa, Gpioa0, Gpioa1, Gpioa2, Gpioa3, Gpioa4, Gpioa5, Gpioa6, Gpioa7);
+impl_port!(Gpioc, Gpioc0, Gpioc1, Gpioc2, Gpioc3, Gpioc4, Gpioc5, Gpioc6, Gpioc7);
+
+/// Identifies one of the GPIO ports
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PortId {
+    /// [`Gpio0`](crate::pac::Gpio0)
+    Gpio0,
+    /// [`Gpio1`](crate::pac::Gpio1)
+    Gpio1,
+    /// [`Gpio2`](crate::pac::Gpio2)
+    Gpio2,
+    /// [`Gpio3`](crate::pac::Gpio3)
+    Gpio3,
+    /// [`Gpio4`](crate::pac::Gpio4)
+    Gpio4,
+    /// [`Gpio5`](crate::pac::Gpio5)
+    Gpio5,
+    /// [`Gpio6`](crate::pac::Gpio6)
+    Gpio6,
+    /// [`Gpio7`](crate::pac::Gpio7)
+    Gpio7,
+    /// [`Gpio8`](crate::pac::Gpio8)
+    Gpio8,
+    /// [`Gpio9`](crate::pac::Gpio9)
+    Gpio9,
+    /// [`Gpioa`](crate::pac::Gpioa)
+    Gpioa,
+    /// [`Gpiob`](crate::pac::Gpiob)
+    Gpiob,
+    /// [`Gpioc`](crate::pac::Gpioc)
+    Gpioc,
+    /// [`Gpiod`](crate::pac::Gpiod)
+    Gpiod,
+    /// [`Gpioe`](crate::pac::Gpioe)
+    Gpioe,
+    /// [`Gpiof`](crate::pac::Gpiof)
+    Gpiof,
+}
+
+impl PortId {
+    const ALL: [PortId; 16] = [
+        PortId::Gpio0,
+        PortId::Gpio1,
+        PortId::Gpio2,
+        PortId::Gpio3,
+        PortId::Gpio4,
+        PortId::Gpio5,
+        PortId::Gpio6,
+        PortId::Gpio7,
+        PortId::Gpio8,
+        PortId::Gpio9,
+        PortId::Gpioa,
+        PortId::Gpiob,
+        PortId::Gpioc,
+        PortId::Gpiod,
+        PortId::Gpioe,
+        PortId::Gpiof,
+    ];
+
+    fn from_regs(regs: &'static crate::pac::gpio0::RegisterBlock) -> Option<PortId> {
+        Self::ALL.into_iter().find(|port| core::ptr::eq(port.regs(), regs))
+    }
+
+    fn regs(self) -> &'static crate::pac::gpio0::RegisterBlock {
+        let ptr = match self {
+            PortId::Gpio0 => crate::pac::Gpio0::ptr(),
+            PortId::Gpio1 => crate::pac::Gpio1::ptr(),
+            PortId::Gpio2 => crate::pac::Gpio2::ptr(),
+            PortId::Gpio3 => crate::pac::Gpio3::ptr(),
+            PortId::Gpio4 => crate::pac::Gpio4::ptr(),
+            PortId::Gpio5 => crate::pac::Gpio5::ptr(),
+            PortId::Gpio6 => crate::pac::Gpio6::ptr(),
+            PortId::Gpio7 => crate::pac::Gpio7::ptr(),
+            PortId::Gpio8 => crate::pac::Gpio8::ptr(),
+            PortId::Gpio9 => crate::pac::Gpio9::ptr(),
+            PortId::Gpioa => crate::pac::Gpioa::ptr(),
+            PortId::Gpiob => crate::pac::Gpiob::ptr(),
+            PortId::Gpioc => crate::pac::Gpioc::ptr(),
+            PortId::Gpiod => crate::pac::Gpiod::ptr(),
+            PortId::Gpioe => crate::pac::Gpioe::ptr(),
+            PortId::Gpiof => crate::pac::Gpiof::ptr(),
+        };
+
+        // Safety:
+        // the pac ptr functions return pointers to memory that is used for registers for the 'static lifetime
+        // and the created reference is shared.
+        unsafe { &*ptr }
+    }
+}
+
+/// Snapshot of the direction, pull and output latch configuration of a GPIO port.
+///
+/// Used to park pins in a low leakage configuration before entering deep sleep, and to restore them afterwards.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortState {
+    port: PortId,
+    dout: u8,
+    dir: u8,
+    pull: u8,
+    pud: u8,
+    otype: u8,
+}
+
+impl PortState {
+    /// The port this state was captured from
+    #[must_use]
+    pub fn port(&self) -> PortId {
+        self.port
+    }
+}
+
+/// Capture the configuration of the given ports, to be restored later with [restore_state].
+///
+/// The configuration is read regardless of which drivers own the pins of the ports.
+#[must_use]
+pub fn save_state<const N: usize>(ports: [PortId; N]) -> [PortState; N] {
+    critical_section::with(|_| {
+        ports.map(|port| {
+            let regs = port.regs();
+
+            PortState {
+                port,
+                dout: regs.px_dout().read().bits(),
+                dir: regs.px_dir().read().bits(),
+                pull: regs.px_pull().read().bits(),
+                pud: regs.px_pud().read().bits(),
+                otype: regs.px_otype().read().bits(),
+            }
+        })
+    })
+}
+
+/// Restore the configuration of ports previously captured with [save_state].
+///
+/// The output latch and output type are restored before the direction, so outputs come back without a glitch.
+/// Note that this overwrites the configuration of all pins of the ports, including those owned by drivers
+/// that reconfigured their pins after the state was saved.
+pub fn restore_state(states: &[PortState]) {
+    critical_section::with(|_| {
+        for state in states {
+            let regs = state.port.regs();
+
+            regs.px_dout().write(|w| unsafe { w.bits(state.dout) });
+            regs.px_otype().write(|w| unsafe { w.bits(state.otype) });
+            regs.px_pud().write(|w| unsafe { w.bits(state.pud) });
+            regs.px_pull().write(|w| unsafe { w.bits(state.pull) });
+            regs.px_dir().write(|w| unsafe { w.bits(state.dir) });
+        }
+    });
+}
+
+macro_rules! impl_port_pins {
+    ($port:ident, $($pin:literal),*) => {
+        paste! {
+            #[doc = "All pins of [`" $port "`](crate::pac::" $port "), as split off from [Peripherals](crate::Peripherals) by [split_port](crate::split_port)."]
+            #[allow(missing_docs)]
+            pub struct [<$port Pins>] {
+                $(pub [<p $pin>]: [<$port $pin>],)*
+            }
+        }
+    };
+}
+
+impl_port_pins!(Gpio0, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio1, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio2, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio3, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio4, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio5, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio6, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio7, 0, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpio8, 0, 1, 2, 3, 5, 6, 7);
+impl_port_pins!(Gpio9, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpioa, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpiob, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpioc, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpiod, 0, 1, 2, 3, 4, 5, 6, 7);
+impl_port_pins!(Gpioe, 0, 1, 2, 3, 4, 5, 7);
+impl_port_pins!(Gpiof, 0, 1, 2, 3, 4, 5);
+
+/// Split the pins of a single GPIO port off from [Peripherals](crate::Peripherals).
+///
+/// The pins are moved out of the peripherals into a per-port struct like [Gpio6Pins](crate::gpio::Gpio6Pins),
+/// whose fields are named after the pin number within the port. This makes it easier for board support crates
+/// to hand off groups of pins to tasks.
+///
+/// ```rust,ignore
+/// let (p, _) = embassy_npcx::init_espi(Config::default());
+/// let gpio6 = embassy_npcx::split_port!(p, Gpio6);
+/// let button = Input::new(gpio6.p3);
+/// ```
+#[macro_export]
+macro_rules! split_port {
+    ($p:ident, Gpio0) => {
+        $crate::gpio::Gpio0Pins {
+            p0: $p.PE07,
+            p1: $p.PE06,
+            p2: $p.PF07,
+            p3: $p.PD09,
+            p4: $p.PD11,
+            p5: $p.PC11,
+            p6: $p.PB10,
+            p7: $p.PB11,
+        }
+    };
+    ($p:ident, Gpio1) => {
+        $crate::gpio::Gpio1Pins {
+            p0: $p.PC10,
+            p1: $p.PC09,
+            p2: $p.PB09,
+            p3: $p.PC08,
+            p4: $p.PC06,
+            p5: $p.PC07,
+            p6: $p.PB08,
+            p7: $p.PB07,
+        }
+    };
+    ($p:ident, Gpio2) => {
+        $crate::gpio::Gpio2Pins {
+            p0: $p.PB06,
+            p1: $p.PB05,
+            p2: $p.PC05,
+            p3: $p.PC04,
+            p4: $p.PC03,
+            p5: $p.PB04,
+            p6: $p.PB03,
+            p7: $p.PA04,
+        }
+    };
+    ($p:ident, Gpio3) => {
+        $crate::gpio::Gpio3Pins {
+            p0: $p.PA03,
+            p1: $p.PA02,
+            p2: $p.PE04,
+            p3: $p.PD05,
+            p4: $p.PB02,
+            p5: $p.PK02,
+            p6: $p.PD04,
+            p7: $p.PC01,
+        }
+    };
+    ($p:ident, Gpio4) => {
+        $crate::gpio::Gpio4Pins {
+            p0: $p.PE05,
+            p1: $p.PC02,
+            p2: $p.PD03,
+            p3: $p.PE02,
+            p4: $p.PE03,
+            p5: $p.PF02,
+            p6: $p.PH01,
+            p7: $p.PJ01,
+        }
+    };
+    ($p:ident, Gpio5) => {
+        $crate::gpio::Gpio5Pins {
+            p0: $p.PG10,
+            p1: $p.PK01,
+            p2: $p.PL01,
+            p3: $p.PL02,
+            p4: $p.PK03,
+            p5: $p.PM01,
+            p6: $p.PM02,
+            p7: $p.PL03,
+        }
+    };
+    ($p:ident, Gpio6) => {
+        $crate::gpio::Gpio6Pins {
+            p0: $p.PG06,
+            p1: $p.PK04,
+            p2: $p.PH02,
+            p3: $p.PJ02,
+            p4: $p.PG04,
+            p5: $p.PH04,
+            p6: $p.PG02,
+            p7: $p.PJ03,
+        }
+    };
+    ($p:ident, Gpio7) => {
+        $crate::gpio::Gpio7Pins {
+            p0: $p.PJ04,
+            p2: $p.PM04,
+            p3: $p.PG05,
+            p4: $p.PH05,
+            p5: $p.PJ06,
+            p6: $p.PJ05,
+            p7: $p.PK06,
+        }
+    };
+    ($p:ident, Gpio8) => {
+        $crate::gpio::Gpio8Pins {
+            p0: $p.PK05,
+            p1: $p.PM07,
+            p2: $p.PD06,
+            p3: $p.PD07,
+            p5: $p.PJ08,
+            p6: $p.PJ09,
+            p7: $p.PK07,
+        }
+    };
+    ($p:ident, Gpio9) => {
+        $crate::gpio::Gpio9Pins {
+            p0: $p.PK08,
+            p1: $p.PK09,
+            p2: $p.PL08,
+            p3: $p.PE11,
+            p4: $p.PM11,
+            p5: $p.PM12,
+            p6: $p.PG12,
+            p7: $p.PL10,
+        }
+    };
+    ($p:ident, Gpioa) => {
+        $crate::gpio::GpioaPins {
+            p0: $p.PG11,
+            p1: $p.PL12,
+            p2: $p.PF12,
+            p3: $p.PK12,
+            p4: $p.PH11,
+            p5: $p.PK11,
+            p6: $p.PF11,
+            p7: $p.PJ11,
+        }
+    };
+    ($p:ident, Gpiob) => {
+        $crate::gpio::GpiobPins {
+            p0: $p.PL11,
+            p1: $p.PD08,
+            p2: $p.PK10,
+            p3: $p.PJ10,
+            p4: $p.PB12,
+            p5: $p.PC12,
+            p6: $p.PL09,
+            p7: $p.PJ07,
+        }
+    };
+    ($p:ident, Gpioc) => {
+        $crate::gpio::GpiocPins {
+            p0: $p.PH08,
+            p1: $p.PH09,
+            p2: $p.PH10,
+            p3: $p.PG09,
+            p4: $p.PG08,
+            p5: $p.PH07,
+            p6: $p.PD10,
+            p7: $p.PF10,
+        }
+    };
+    ($p:ident, Gpiod) => {
+        $crate::gpio::GpiodPins {
+            p0: $p.PF09,
+            p1: $p.PF08,
+            p2: $p.PG07,
+            p3: $p.PE10,
+            p4: $p.PA09,
+            p5: $p.PA10,
+            p6: $p.PH03,
+            p7: $p.PH06,
+        }
+    };
+    ($p:ident, Gpioe) => {
+        $crate::gpio::GpioePins {
+            p0: $p.PF04,
+            p1: $p.PF03,
+            p2: $p.PA11,
+            p3: $p.PL07,
+            p4: $p.PL06,
+            p5: $p.PA12,
+            p7: $p.PL05,
+        }
+    };
+    ($p:ident, Gpiof) => {
+        $crate::gpio::GpiofPins {
+            p0: $p.PD02,
+            p1: $p.PG03,
+            p2: $p.PF06,
+            p3: $p.PF05,
+            p4: $p.PE09,
+            p5: $p.PE08,
+        }
+    };
+}
+
+impl<T> embedded_hal::digital::ErrorType for Input<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+impl<T> embedded_hal::digital::InputPin for Input<'_, T> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_low())
+    }
+}
+
+impl<T> embedded_hal::digital::ErrorType for Output<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+impl<T> embedded_hal::digital::OutputPin for Output<'_, T> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (*self).set_low();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (*self).set_high();
+        Ok(())
+    }
+}
+
+impl<T> embedded_hal::digital::StatefulOutputPin for Output<'_, T> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_high())
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_low())
+    }
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        (*self).toggle();
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::InputPin for Output<'_, InputCapable> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_low())
+    }
+}
+
+impl<T> embedded_hal::digital::ErrorType for OutputOpenDrain<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+impl<T> embedded_hal::digital::OutputPin for OutputOpenDrain<'_, T> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (*self).set_low();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (*self).set_high();
+        Ok(())
+    }
+}
+
+impl<T> embedded_hal::digital::StatefulOutputPin for OutputOpenDrain<'_, T> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_high())
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_low())
+    }
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        (*self).toggle();
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::InputPin for OutputOpenDrain<'_, InputCapable> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_low())
+    }
+}
+
+impl embedded_hal::digital::ErrorType for Flex<'_> {
+    type Error = core::convert::Infallible;
+}
+
+impl embedded_hal::digital::InputPin for Flex<'_> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_low())
+    }
+}
+
+impl embedded_hal::digital::OutputPin for Flex<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (*self).set_low();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (*self).set_high();
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::StatefulOutputPin for Flex<'_> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_high())
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok((*self).is_set_low())
+    }
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        (*self).toggle();
+        Ok(())
+    }
+}
+
+/// A marker trait implemented for all pins that can function as an input but only support low voltage
+pub trait LowVoltagePin: InputPin + sealed::SealedLowVoltagePin {}
+
+/// A marker trait implemented for all pins that can function as an input
+pub trait InputPin: Pin + sealed::SealedInputPin {}
+
+/// A marker trait implemented for all pins
+pub trait Pin: sealed::SealedPin + Into<AnyPin> {
+    /// Erase the concrete type of the pin, turning it into an [AnyPin].
+    ///
+    /// The [AnyPin] keeps the input and low voltage capabilities of the pin, so a degraded input pin can still be used
+    /// as an [Input] or [Flex].
+    #[must_use]
+    fn degrade(self) -> AnyPin {
+        AnyPin {
+            pin: self.pin(),
+            port: self.port(),
+            pin_function: self.pin_function(),
+            low_voltage_function: self.low_voltage_function(),
+            kind: self.kind(),
+        }
+    }
+}
+
+macro_rules! impl_pin {
+    ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr) => {
+        impl_pin!($peripheral, $port, $pin, $pin_function, |_, _| {});
+    };
+    ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
+        impl_pin!(@kind Output, $peripheral, $port, $pin, $pin_function, $low_voltage_function);
+    };
+    (@kind $kind:ident, $peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
+        impl sealed::SealedPin for crate::peripherals::$peripheral {
+            fn pin(&self) -> u8 {
                 $pin
             }
 
@@ -675,26 +2092,26 @@ macro_rules! impl_pin {
                 unsafe { &*ptr }
             }
 
-            unsafe fn set_pin_function(&self, _cs: critical_section::CriticalSection) {
-                // Safety:
-                // We have the unique reference to the pin and are within a critical section, so no contention
-                fn internal_set(f: impl FnOnce(crate::pac::Sysconfig)) {
-                    f(unsafe { crate::pac::Sysconfig::steal() });
-                }
-                internal_set($pin_function);
+            fn pin_function(&self) -> fn(crate::pac::Sysconfig) {
+                $pin_function
             }
 
-            unsafe fn set_low_voltage(&self, _cs: critical_section::CriticalSection, state: bool) {
-                // Safety:
-                // We have the unique reference to the pin and are within a critical section, so no contention
-                fn internal_set(f: impl FnOnce(crate::pac::Sysconfig, bool), state: bool) {
-                    f(unsafe { crate::pac::Sysconfig::steal() }, state);
-                }
-                internal_set($low_voltage_function, state);
+            fn low_voltage_function(&self) -> fn(crate::pac::Sysconfig, bool) {
+                $low_voltage_function
+            }
+
+            fn kind(&self) -> PinKind {
+                PinKind::$kind
             }
         }
         impl Pin for crate::peripherals::$peripheral {}
 
+        impl From<crate::peripherals::$peripheral> for AnyPin {
+            fn from(pin: crate::peripherals::$peripheral) -> Self {
+                pin.degrade()
+            }
+        }
+
         paste! {
             #[doc = "Type alias to tie [`" $port "`](crate::pac::" $port ") pin " $pin " to ball [`" $peripheral "`](crate::peripherals::" $peripheral ")"]
             pub type [<$port $pin>] = crate::peripherals::$peripheral;
@@ -707,7 +2124,10 @@ macro_rules! impl_input_pin {
         impl_input_pin!($peripheral, $port, $pin, $pin_function, |_, _| {});
     };
     ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
-        impl_pin!($peripheral, $port, $pin, $pin_function, $low_voltage_function);
+        impl_input_pin!(@kind Input, $peripheral, $port, $pin, $pin_function, $low_voltage_function);
+    };
+    (@kind $kind:ident, $peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
+        impl_pin!(@kind $kind, $peripheral, $port, $pin, $pin_function, $low_voltage_function);
         impl sealed::SealedInputPin for crate::peripherals::$peripheral {}
         impl InputPin for crate::peripherals::$peripheral {}
     };
@@ -715,7 +2135,7 @@ macro_rules! impl_input_pin {
 
 macro_rules! impl_lowvoltage_pin {
     ($peripheral:ident, $port:ident, $pin:expr, $pin_function:expr, $low_voltage_function:expr) => {
-        impl_input_pin!($peripheral, $port, $pin, $pin_function, $low_voltage_function);
+        impl_input_pin!(@kind LowVoltage, $peripheral, $port, $pin, $pin_function, $low_voltage_function);
         impl sealed::SealedLowVoltagePin for crate::peripherals::$peripheral {}
         impl LowVoltagePin for crate::peripherals::$peripheral {}
     };
@@ -5,23 +5,64 @@ use core::ops::{Deref, DerefMut};
 
 use embassy_hal_internal::Peripheral;
 
-use crate::gpio::{CanPullUp, Input, InputPin, LowVoltagePin, PullDownOnly};
-use crate::miwu::{Edge, InterruptHandler, Level, WakeUp, WakeUpInput};
+use crate::gpio::sealed::SealedPin;
+use crate::gpio::{CanPullUp, Input, InputPin, LowVoltagePin, Pull, PullDownOnly};
+use crate::miwu::{AnyWakeUpInput, Edge, InterruptHandler, Level, Mode, WakeUp, WakeUpInput};
 
 mod sealed {
     pub trait SealedAwaitableInputPin {}
+
+    pub trait SealedPinWakeUpInput {
+        /// The port and pin number of the GPIO pin associated with this WakeUpInput.
+        fn pin_location() -> (&'static crate::pac::gpio0::RegisterBlock, u8);
+    }
 }
 
 /// GPIO pins that have an WakeUpInput channel associated with them.
 pub trait AwaitableInputPin: sealed::SealedAwaitableInputPin {}
 
+/// WakeUpInput channels that are associated with a GPIO pin.
+pub trait PinWakeUpInput: WakeUpInput + sealed::SealedPinWakeUpInput {}
+
+/// Configuration of an [AwaitableInput]
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The pull resistor configuration of the pin
+    pub pull: Pull,
+    /// If true, the pin uses 1.8V low voltage input detection.
+    /// Only for [AwaitableInput::new_lowvoltage_with_config], and can't be combined with a pull-up.
+    pub low_voltage: bool,
+    /// Only accept levels and edges after the input has been stable for this long, see [DebouncedInput].
+    /// The MIWU has no input filtering, so this is done in software.
+    #[cfg(feature = "time")]
+    pub debounce: Option<embassy_time::Duration>,
+}
+
+/// Error returned when an [AwaitableInput] can't be configured as requested
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// Low voltage pins only support a pull-down resistor
+    PullUpNotSupported,
+    /// Low voltage input detection was requested from a constructor for pins that can get a pull-up.
+    /// Use [AwaitableInput::new_lowvoltage_with_config] instead.
+    LowVoltageNotSupported,
+}
+
 /// Driver for GPIO input pins and their WakeUpInput channel.
 ///
 /// Dereferences to the underlying [Input] driver instance,
 /// but also implements [embedded_hal_async::digital::Wait].
+///
+/// The pairing of the pin with its WakeUpInput channel is checked at compile time, which makes this
+/// also the way to use a pin only as wake-up source, see [AwaitableInput::enable_wake].
 pub struct AwaitableInput<'d, T> {
     pin: Input<'d, T>,
     wui: WakeUp<'d>,
+    #[cfg(feature = "time")]
+    debounce: Option<embassy_time::Duration>,
 }
 
 impl<'d> AwaitableInput<'d, CanPullUp> {
@@ -36,14 +77,68 @@ impl<'d> AwaitableInput<'d, CanPullUp> {
         WUI: WakeUpInput + 'd,
         (PIN, WUI): AwaitableInputPin,
     {
-        AwaitableInput {
-            pin: Input::new(pin),
-            wui: WakeUp::new(wui, irqs),
+        AwaitableInput::from_parts(Input::new(pin), WakeUp::new(wui, irqs))
+    }
+}
+
+impl<'d> AwaitableInput<'d, CanPullUp> {
+    /// Create a new input that can be awaited, configured by [Config]
+    ///
+    /// Returns [ConfigError::LowVoltageNotSupported] when low voltage input detection is requested, as the pin type
+    /// would still allow enabling the pull-up afterwards.
+    pub fn new_with_config<PIN, WUI>(
+        pin: impl Peripheral<P = PIN> + 'd,
+        wui: impl Peripheral<P = WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<WUI::Interrupt, InterruptHandler<WUI>>,
+        config: Config,
+    ) -> Result<Self, ConfigError>
+    where
+        PIN: InputPin + 'd,
+        WUI: WakeUpInput + 'd,
+        (PIN, WUI): AwaitableInputPin,
+    {
+        if config.low_voltage {
+            return Err(ConfigError::LowVoltageNotSupported);
         }
+
+        let input = AwaitableInput::from_parts(Input::new_with_pull(pin, config.pull), WakeUp::new(wui, irqs));
+        Ok(input.with_config(config))
     }
 }
 
 impl<'d> AwaitableInput<'d, PullDownOnly> {
+    /// Create a new input on a low voltage capable pin that can be awaited, configured by [Config]
+    ///
+    /// The pin uses low voltage input detection if [Config::low_voltage] is set, and can't get a pull-up either way.
+    /// Returns [ConfigError::PullUpNotSupported] when a pull-up is requested.
+    pub fn new_lowvoltage_with_config<PIN, WUI>(
+        pin: impl Peripheral<P = PIN> + 'd,
+        wui: impl Peripheral<P = WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<WUI::Interrupt, InterruptHandler<WUI>>,
+        config: Config,
+    ) -> Result<Self, ConfigError>
+    where
+        PIN: LowVoltagePin + 'd,
+        WUI: WakeUpInput + 'd,
+        (PIN, WUI): AwaitableInputPin,
+    {
+        if config.pull == Pull::Up {
+            return Err(ConfigError::PullUpNotSupported);
+        }
+
+        let mut pin = match config.low_voltage {
+            true => Input::new_lowvoltage(pin),
+            false => Input::new(pin).degrade(),
+        };
+        match config.pull {
+            Pull::Down => pin.enable_pulldown(),
+            _ => pin.disable_pull(),
+        }
+
+        let input = AwaitableInput::from_parts(pin, WakeUp::new(wui, irqs));
+        Ok(input.with_config(config))
+    }
+
     /// Create a new input that can be awaited
     pub fn new_lowvoltage<PIN, WUI>(
         pin: impl Peripheral<P = PIN> + 'd,
@@ -55,13 +150,213 @@ impl<'d> AwaitableInput<'d, PullDownOnly> {
         WUI: WakeUpInput + 'd,
         (PIN, WUI): AwaitableInputPin,
     {
+        AwaitableInput::from_parts(Input::new_lowvoltage(pin), WakeUp::new(wui, irqs))
+    }
+}
+
+impl<'d, T> AwaitableInput<'d, T> {
+    fn from_parts(pin: Input<'d, T>, wui: WakeUp<'d>) -> Self {
         AwaitableInput {
-            pin: Input::new_lowvoltage(pin),
-            wui: WakeUp::new(wui, irqs),
+            pin,
+            wui,
+            #[cfg(feature = "time")]
+            debounce: None,
+        }
+    }
+
+    #[allow(unused_mut)]
+    fn with_config(mut self, _config: Config) -> Self {
+        #[cfg(feature = "time")]
+        {
+            self.debounce = _config.debounce;
+        }
+        self
+    }
+
+    /// Make an existing [Input] awaitable, using its WakeUpInput channel.
+    ///
+    /// The configuration of the input, like its pull resistor, is kept.
+    /// Panics if `wui` is not the WakeUpInput channel associated with the pin of `input`.
+    pub fn from_input<WUI>(
+        input: Input<'d, T>,
+        wui: impl Peripheral<P = WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<WUI::Interrupt, InterruptHandler<WUI>>,
+    ) -> Self
+    where
+        WUI: PinWakeUpInput + 'd,
+    {
+        let (port, pin) = WUI::pin_location();
+        assert!(
+            core::ptr::eq(input.any_pin().port(), port) && input.any_pin().pin() == pin,
+            "WakeUpInput does not belong to the pin"
+        );
+
+        AwaitableInput::from_parts(input, WakeUp::new(wui, irqs))
+    }
+
+    /// Split into the plain [Input] and its [WakeUp] channel, for example to only use the channel while sleeping.
+    ///
+    /// The channel is left as is, use [WakeUp::disable] to stop it from triggering.
+    pub fn into_parts(self) -> (Input<'d, T>, WakeUp<'d>) {
+        (self.pin, self.wui)
+    }
+
+    /// Release the WakeUpInput channel, disabling it, and return the plain [Input].
+    pub fn into_input(self) -> Input<'d, T> {
+        self.pin
+    }
+
+    /// Return a stream of the times of the [Edge]s on this pin, see [WakeUp::edge_stream].
+    #[cfg(feature = "miwu-edge-stream")]
+    pub fn edge_stream(&mut self, edge: Edge) -> crate::miwu::EdgeStream<'_, 'd> {
+        self.wui.edge_stream(edge)
+    }
+}
+
+impl<T> AwaitableInput<'_, T> {
+    /// Enable waking up from a low power state when the signalling condition [Mode] occurs on this pin.
+    ///
+    /// This only arms the WakeUpInput channel associated with the pin, no task needs to be awaiting it.
+    /// As with any [WakeUp], the interrupt handler disables the channel once it has been triggered,
+    /// so it needs to be re-enabled before entering the next low power state.
+    ///
+    /// Awaiting the input replaces this configuration, and disables the channel when done.
+    pub fn enable_wake(&mut self, mode: impl Into<Mode>) {
+        self.wui.enable(mode);
+    }
+
+    /// Disable waking up from a low power state on this pin, and clear any pending wake-up.
+    pub fn disable_wake(&mut self) {
+        self.wui.disable();
+        self.wui.clear_pending();
+    }
+
+    /// Returns true if this pin has triggered a wake-up since it was enabled with [Self::enable_wake]
+    #[must_use]
+    pub fn is_wake_pending(&self) -> bool {
+        self.wui.is_pending()
+    }
+
+    /// Wait for the given [Edge] on the input.
+    ///
+    /// Only edges occurring after this function has been called are considered.
+    /// With [Config::debounce] set, the input must be stable at the level before and after the edge.
+    pub async fn wait_for_edge(&mut self, edge: Edge) {
+        #[cfg(feature = "time")]
+        if let Some(debounce) = self.debounce {
+            self.wait_for_stable_edge(edge, debounce).await;
+            return;
+        }
+
+        self.wui.wait_for(edge).await;
+    }
+
+    /// Wait for the given [Level] on the input, see [Self::wait_for_edge] for debouncing.
+    async fn wait_for_level(&mut self, level: Level) {
+        #[cfg(feature = "time")]
+        if let Some(debounce) = self.debounce {
+            self.wait_for_stable(level == Level::High, debounce).await;
+            return;
+        }
+
+        if self.is_high() != (level == Level::High) {
+            self.wui.wait_for(level).await;
+        }
+    }
+
+    #[cfg(feature = "time")]
+    async fn wait_for_stable(&mut self, high: bool, debounce: embassy_time::Duration) {
+        loop {
+            if self.is_high() != high {
+                let level = if high { Level::High } else { Level::Low };
+                self.wui.wait_for(level).await;
+            }
+
+            embassy_time::Timer::after(debounce).await;
+
+            if self.is_high() == high {
+                return;
+            }
+        }
+    }
+
+    #[cfg(feature = "time")]
+    async fn wait_for_stable_edge(&mut self, edge: Edge, debounce: embassy_time::Duration) {
+        match edge {
+            Edge::Rising => {
+                self.wait_for_stable(false, debounce).await;
+                self.wait_for_stable(true, debounce).await;
+            }
+            Edge::Falling => {
+                self.wait_for_stable(true, debounce).await;
+                self.wait_for_stable(false, debounce).await;
+            }
+            Edge::Any => {
+                let high = self.is_high();
+                self.wait_for_stable(!high, debounce).await;
+            }
         }
     }
 }
 
+impl<T> Input<'_, T> {
+    /// Enable waking up from a low power state when the signalling condition [Mode] occurs on this pin.
+    ///
+    /// This arms the WakeUpInput channel `W` associated with the pin, which `irqs` binds the interrupt handler of,
+    /// without claiming the channel or awaiting it. Use an [AwaitableInput] to await the pin in software.
+    /// The interrupt handler disables the channel once it has been triggered, so it needs to be re-enabled before
+    /// entering the next low power state.
+    ///
+    /// Panics if `W` is not the WakeUpInput channel associated with the pin.
+    pub fn enable_wake<W: PinWakeUpInput>(
+        &mut self,
+        mode: impl Into<Mode>,
+        _irqs: impl crate::interrupt::typelevel::Binding<W::Interrupt, InterruptHandler<W>>,
+    ) {
+        let (port, pin) = W::pin_location();
+        assert!(
+            core::ptr::eq(self.any_pin().port(), port) && self.any_pin().pin() == pin,
+            "WakeUpInput does not belong to the pin"
+        );
+
+        // Safety: _irqs ensures an interrupt handler is bound
+        unsafe {
+            use crate::interrupt::typelevel::Interrupt;
+            W::Interrupt::enable();
+        }
+
+        // Safety: the channel is only driven through the pin, which this input owns.
+        let mut wui = unsafe { WakeUp::steal(AnyWakeUpInput::of::<W>()) };
+        wui.enable(mode);
+        // Dropping the driver would disable the channel again.
+        core::mem::forget(wui);
+    }
+
+    /// Disable waking up from a low power state on this pin, and clear any pending wake-up.
+    ///
+    /// Does nothing if the pin has no WakeUpInput channel associated with it.
+    pub fn disable_wake(&mut self) {
+        if let Some(channel) = pin_channel(self.any_pin().port(), self.any_pin().pin()) {
+            // Safety: the channel is only driven through the pin, which this input owns.
+            let mut wui = unsafe { WakeUp::steal(channel) };
+            wui.disable();
+            wui.clear_pending();
+        }
+    }
+
+    /// Returns true if this pin has triggered a wake-up since it was enabled with [Self::enable_wake]
+    #[must_use]
+    pub fn is_wake_pending(&self) -> bool {
+        pin_channel(self.any_pin().port(), self.any_pin().pin()).is_some_and(|channel| {
+            // Safety: the channel is only read.
+            let wui = unsafe { WakeUp::steal(channel) };
+            let pending = wui.is_pending();
+            core::mem::forget(wui);
+            pending
+        })
+    }
+}
+
 impl<'d, T> Deref for AwaitableInput<'d, T> {
     type Target = Input<'d, T>;
 
@@ -76,177 +371,406 @@ impl<T> DerefMut for AwaitableInput<'_, T> {
     }
 }
 
+/// Driver for GPIO input pins that counts edges on their WakeUpInput channel.
+///
+/// The edges are counted in the interrupt handler, so no task needs to be awaiting the pin.
+/// Useful for flow sensors or simple rotary encoders.
+/// Dereferences to the underlying [Input] driver instance.
+pub struct EdgeCounter<'d, T> {
+    pin: Input<'d, T>,
+    wui: WakeUp<'d>,
+}
+
+impl<'d> EdgeCounter<'d, CanPullUp> {
+    /// Create a new input that counts every [Edge]
+    pub fn new<PIN, WUI>(
+        pin: impl Peripheral<P = PIN> + 'd,
+        wui: impl Peripheral<P = WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<WUI::Interrupt, InterruptHandler<WUI>>,
+        edge: Edge,
+    ) -> Self
+    where
+        PIN: InputPin + 'd,
+        WUI: WakeUpInput + 'd,
+        (PIN, WUI): AwaitableInputPin,
+    {
+        let mut wui = WakeUp::new(wui, irqs);
+        wui.reset_count();
+        wui.enable_counting(edge);
+
+        EdgeCounter {
+            pin: Input::new(pin),
+            wui,
+        }
+    }
+}
+
+impl<'d> EdgeCounter<'d, PullDownOnly> {
+    /// Create a new input that counts every [Edge]
+    pub fn new_lowvoltage<PIN, WUI>(
+        pin: impl Peripheral<P = PIN> + 'd,
+        wui: impl Peripheral<P = WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<WUI::Interrupt, InterruptHandler<WUI>>,
+        edge: Edge,
+    ) -> Self
+    where
+        PIN: LowVoltagePin + 'd,
+        WUI: WakeUpInput + 'd,
+        (PIN, WUI): AwaitableInputPin,
+    {
+        let mut wui = WakeUp::new(wui, irqs);
+        wui.reset_count();
+        wui.enable_counting(edge);
+
+        EdgeCounter {
+            pin: Input::new_lowvoltage(pin),
+            wui,
+        }
+    }
+}
+
+impl<T> EdgeCounter<'_, T> {
+    /// The number of edges counted since construction or the last reset
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.wui.count()
+    }
+
+    /// Reset the number of counted edges to zero
+    pub fn reset(&mut self) {
+        self.wui.reset_count();
+    }
+
+    /// Returns the number of counted edges and resets it to zero, without losing any edges in between
+    pub fn take(&mut self) -> u32 {
+        self.wui.take_count()
+    }
+}
+
+impl<'d, T> Deref for EdgeCounter<'d, T> {
+    type Target = Input<'d, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pin
+    }
+}
+
+impl<T> DerefMut for EdgeCounter<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pin
+    }
+}
+
 impl<T> embedded_hal::digital::ErrorType for AwaitableInput<'_, T> {
     type Error = Infallible;
 }
 
+impl<T> embedded_hal::digital::InputPin for AwaitableInput<'_, T> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.pin.is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.pin.is_low())
+    }
+}
+
 impl<T> embedded_hal_async::digital::Wait for AwaitableInput<'_, T> {
     async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
-        if self.is_high() {
-            return Ok(());
-        }
-        self.wui.wait_for(Level::High).await;
+        self.wait_for_level(Level::High).await;
         Ok(())
     }
 
     async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
-        if self.is_low() {
-            return Ok(());
-        }
-        self.wui.wait_for(Level::Low).await;
+        self.wait_for_level(Level::Low).await;
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(Edge::Rising).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(Edge::Falling).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(Edge::Any).await;
+        Ok(())
+    }
+}
+
+/// Debounced wrapper around an [AwaitableInput].
+///
+/// Every level or edge reported by the MIWU is only accepted after the input has settled for the debounce duration,
+/// after which it is sampled again. Bounces in between are ignored.
+///
+/// Dereferences to the underlying [Input] driver instance, which reports the raw (not debounced) input level.
+#[cfg(feature = "time")]
+pub struct DebouncedInput<'d, T> {
+    input: AwaitableInput<'d, T>,
+    debounce: embassy_time::Duration,
+}
+
+#[cfg(feature = "time")]
+impl<'d, T> DebouncedInput<'d, T> {
+    /// Wrap an [AwaitableInput], only accepting levels that are stable for `debounce`
+    pub fn new(input: AwaitableInput<'d, T>, debounce: embassy_time::Duration) -> Self {
+        Self { input, debounce }
+    }
+
+    /// Change the debounce duration
+    pub fn set_debounce(&mut self, debounce: embassy_time::Duration) {
+        self.debounce = debounce;
+    }
+
+    /// Returns the debounce duration
+    #[must_use]
+    pub fn debounce(&self) -> embassy_time::Duration {
+        self.debounce
+    }
+
+    /// Release the underlying [AwaitableInput]
+    pub fn into_inner(self) -> AwaitableInput<'d, T> {
+        self.input
+    }
+
+    /// Wait for the input to be stable high
+    pub async fn wait_for_high(&mut self) {
+        self.input.wait_for_stable(true, self.debounce).await;
+    }
+
+    /// Wait for the input to be stable low
+    pub async fn wait_for_low(&mut self) {
+        self.input.wait_for_stable(false, self.debounce).await;
+    }
+
+    /// Wait for the input to be stable low, followed by it being stable high
+    pub async fn wait_for_rising_edge(&mut self) {
+        self.input.wait_for_stable_edge(Edge::Rising, self.debounce).await;
+    }
+
+    /// Wait for the input to be stable high, followed by it being stable low
+    pub async fn wait_for_falling_edge(&mut self) {
+        self.input.wait_for_stable_edge(Edge::Falling, self.debounce).await;
+    }
+
+    /// Wait for the input to be stable at the opposite of its current level
+    pub async fn wait_for_any_edge(&mut self) {
+        self.input.wait_for_stable_edge(Edge::Any, self.debounce).await;
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'d, T> Deref for DebouncedInput<'d, T> {
+    type Target = Input<'d, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.input
+    }
+}
+
+#[cfg(feature = "time")]
+impl<T> DerefMut for DebouncedInput<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.input
+    }
+}
+
+#[cfg(feature = "time")]
+impl<T> embedded_hal::digital::ErrorType for DebouncedInput<'_, T> {
+    type Error = Infallible;
+}
+
+#[cfg(feature = "time")]
+impl<T> embedded_hal_async::digital::Wait for DebouncedInput<'_, T> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        DebouncedInput::wait_for_high(self).await;
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        DebouncedInput::wait_for_low(self).await;
         Ok(())
     }
 
     async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
-        self.wui.wait_for(Edge::Rising).await;
+        DebouncedInput::wait_for_rising_edge(self).await;
         Ok(())
     }
 
     async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
-        self.wui.wait_for(Edge::Falling).await;
+        DebouncedInput::wait_for_falling_edge(self).await;
         Ok(())
     }
 
     async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
-        self.wui.wait_for(Edge::Any).await;
+        DebouncedInput::wait_for_any_edge(self).await;
         Ok(())
     }
 }
 
-macro_rules! impl_pin_channel {
-    ($pin:ident, $channel:ident) => {
-        impl sealed::SealedAwaitableInputPin for (crate::gpio::$pin, crate::peripherals::$channel) {}
-        impl AwaitableInputPin for (crate::gpio::$pin, crate::peripherals::$channel) {}
+macro_rules! impl_pin_channels {
+    ($(($pin:ident, $channel:ident)),* $(,)?) => {
+        $(
+            impl sealed::SealedAwaitableInputPin for (crate::gpio::$pin, crate::peripherals::$channel) {}
+            impl AwaitableInputPin for (crate::gpio::$pin, crate::peripherals::$channel) {}
+
+            impl sealed::SealedPinWakeUpInput for crate::peripherals::$channel {
+                fn pin_location() -> (&'static crate::pac::gpio0::RegisterBlock, u8) {
+                    // Safety: the pin is only used to look up its location, not to access it.
+                    let pin = unsafe { crate::gpio::$pin::steal() };
+                    (pin.port(), pin.pin())
+                }
+            }
+            impl PinWakeUpInput for crate::peripherals::$channel {}
+        )*
+
+        /// The WakeUpInput channel associated with the pin at `port` and `pin`, if any.
+        fn pin_channel(port: &crate::pac::gpio0::RegisterBlock, pin: u8) -> Option<AnyWakeUpInput> {
+            $(
+                let (channel_port, channel_pin) =
+                    <crate::peripherals::$channel as sealed::SealedPinWakeUpInput>::pin_location();
+                if core::ptr::eq(channel_port, port) && channel_pin == pin {
+                    return Some(AnyWakeUpInput::of::<crate::peripherals::$channel>());
+                }
+            )*
+            None
+        }
     };
 }
 
-impl_pin_channel!(Gpio80, MIWU0_10);
-impl_pin_channel!(Gpio81, MIWU0_11);
-impl_pin_channel!(Gpio82, MIWU0_12);
-impl_pin_channel!(Gpio83, MIWU0_13);
-impl_pin_channel!(Gpio87, MIWU0_17);
-
-impl_pin_channel!(Gpio90, MIWU0_20);
-impl_pin_channel!(Gpio91, MIWU0_21);
-impl_pin_channel!(Gpio92, MIWU0_22);
-impl_pin_channel!(Gpio93, MIWU0_23);
-impl_pin_channel!(Gpio94, MIWU0_24);
-impl_pin_channel!(Gpio95, MIWU0_25);
-
-impl_pin_channel!(Gpio96, MIWU0_30);
-impl_pin_channel!(Gpio97, MIWU0_31);
-impl_pin_channel!(Gpioa0, MIWU0_32);
-impl_pin_channel!(Gpioa1, MIWU0_33);
-impl_pin_channel!(Gpioa2, MIWU0_34);
-impl_pin_channel!(Gpioa3, MIWU0_35);
-impl_pin_channel!(Gpioa4, MIWU0_36);
-impl_pin_channel!(Gpioa5, MIWU0_37);
-
-impl_pin_channel!(Gpioa6, MIWU0_40);
-impl_pin_channel!(Gpioa7, MIWU0_41);
-impl_pin_channel!(Gpiob0, MIWU0_42);
-impl_pin_channel!(Gpiob1, MIWU0_45);
-impl_pin_channel!(Gpiob2, MIWU0_46);
-
-impl_pin_channel!(Gpiob3, MIWU0_50);
-impl_pin_channel!(Gpiob4, MIWU0_51);
-impl_pin_channel!(Gpiob5, MIWU0_52);
-impl_pin_channel!(Gpiob7, MIWU0_54);
-
-impl_pin_channel!(Gpioc0, MIWU0_60);
-impl_pin_channel!(Gpioc1, MIWU0_61);
-impl_pin_channel!(Gpioc2, MIWU0_62);
-impl_pin_channel!(Gpioc3, MIWU0_63);
-impl_pin_channel!(Gpioc4, MIWU0_64);
-impl_pin_channel!(Gpioc5, MIWU0_65);
-impl_pin_channel!(Gpioc6, MIWU0_66);
-impl_pin_channel!(Gpioc7, MIWU0_67);
-
-impl_pin_channel!(Gpiod0, MIWU0_70);
-impl_pin_channel!(Gpiod1, MIWU0_71);
-impl_pin_channel!(Gpiod2, MIWU0_72);
-impl_pin_channel!(Gpiod3, MIWU0_73);
-impl_pin_channel!(Gpiod4, MIWU0_74);
-impl_pin_channel!(Gpiod5, MIWU0_75);
-impl_pin_channel!(Gpioe0, MIWU0_77);
-
-impl_pin_channel!(Gpioe1, MIWU0_80);
-impl_pin_channel!(Gpioe2, MIWU0_81);
-impl_pin_channel!(Gpioe3, MIWU0_82);
-impl_pin_channel!(Gpioe4, MIWU0_83);
-impl_pin_channel!(Gpioe5, MIWU0_84);
-impl_pin_channel!(Gpiof0, MIWU0_85);
-impl_pin_channel!(Gpiof3, MIWU0_86);
-impl_pin_channel!(Gpioe7, MIWU0_87);
-
-impl_pin_channel!(Gpio00, MIWU1_10);
-impl_pin_channel!(Gpio01, MIWU1_11);
-impl_pin_channel!(Gpio02, MIWU1_12);
-impl_pin_channel!(Gpio03, MIWU1_13);
-impl_pin_channel!(Gpio04, MIWU1_14);
-impl_pin_channel!(Gpio05, MIWU1_15);
-impl_pin_channel!(Gpio06, MIWU1_16);
-impl_pin_channel!(Gpio07, MIWU1_17);
-
-impl_pin_channel!(Gpio10, MIWU1_20);
-impl_pin_channel!(Gpio11, MIWU1_21);
-impl_pin_channel!(Gpiof4, MIWU1_22);
-impl_pin_channel!(Gpio13, MIWU1_23);
-impl_pin_channel!(Gpio14, MIWU1_24);
-impl_pin_channel!(Gpio15, MIWU1_25);
-impl_pin_channel!(Gpio16, MIWU1_26);
-impl_pin_channel!(Gpio17, MIWU1_27);
-
-impl_pin_channel!(Gpio31, MIWU1_30);
-impl_pin_channel!(Gpio30, MIWU1_31);
-impl_pin_channel!(Gpio27, MIWU1_32);
-impl_pin_channel!(Gpio26, MIWU1_33);
-impl_pin_channel!(Gpio25, MIWU1_34);
-impl_pin_channel!(Gpio24, MIWU1_35);
-impl_pin_channel!(Gpio23, MIWU1_36);
-impl_pin_channel!(Gpio22, MIWU1_37);
-
-impl_pin_channel!(Gpio20, MIWU1_40);
-impl_pin_channel!(Gpio21, MIWU1_41);
-impl_pin_channel!(Gpiof5, MIWU1_42);
-impl_pin_channel!(Gpio33, MIWU1_43);
-impl_pin_channel!(Gpio34, MIWU1_44);
-impl_pin_channel!(Gpio36, MIWU1_46);
-impl_pin_channel!(Gpio37, MIWU1_47);
-
-impl_pin_channel!(Gpio40, MIWU1_50);
-impl_pin_channel!(Gpio41, MIWU1_51);
-impl_pin_channel!(Gpio42, MIWU1_52);
-impl_pin_channel!(Gpio43, MIWU1_53);
-impl_pin_channel!(Gpio44, MIWU1_54);
-impl_pin_channel!(Gpio45, MIWU1_55);
-impl_pin_channel!(Gpio46, MIWU1_56);
-impl_pin_channel!(Gpio47, MIWU1_57);
-
-impl_pin_channel!(Gpio50, MIWU1_60);
-impl_pin_channel!(Gpio51, MIWU1_61);
-impl_pin_channel!(Gpio52, MIWU1_62);
-impl_pin_channel!(Gpio53, MIWU1_63);
-impl_pin_channel!(Gpio54, MIWU1_64);
-impl_pin_channel!(Gpio55, MIWU1_65);
-impl_pin_channel!(Gpio56, MIWU1_66);
-impl_pin_channel!(Gpio57, MIWU1_67);
-
-impl_pin_channel!(Gpio60, MIWU1_70);
-impl_pin_channel!(Gpio61, MIWU1_71);
-impl_pin_channel!(Gpio62, MIWU1_72);
-impl_pin_channel!(Gpio63, MIWU1_73);
-impl_pin_channel!(Gpio64, MIWU1_74);
-
-impl_pin_channel!(Gpio70, MIWU1_80);
-impl_pin_channel!(Gpio67, MIWU1_81);
-impl_pin_channel!(Gpio72, MIWU1_82);
-impl_pin_channel!(Gpio73, MIWU1_83);
-impl_pin_channel!(Gpio74, MIWU1_84);
-impl_pin_channel!(Gpio75, MIWU1_85);
-impl_pin_channel!(Gpio76, MIWU1_86);
-
-impl_pin_channel!(Gpio12, MIWU2_60);
-impl_pin_channel!(Gpiof1, MIWU2_61);
-impl_pin_channel!(Gpiof2, MIWU2_62);
-impl_pin_channel!(Gpiod6, MIWU2_65);
+impl_pin_channels! {
+    (Gpio80, MIWU0_10),
+    (Gpio81, MIWU0_11),
+    (Gpio82, MIWU0_12),
+    (Gpio83, MIWU0_13),
+    (Gpio87, MIWU0_17),
+
+    (Gpio90, MIWU0_20),
+    (Gpio91, MIWU0_21),
+    (Gpio92, MIWU0_22),
+    (Gpio93, MIWU0_23),
+    (Gpio94, MIWU0_24),
+    (Gpio95, MIWU0_25),
+
+    (Gpio96, MIWU0_30),
+    (Gpio97, MIWU0_31),
+    (Gpioa0, MIWU0_32),
+    (Gpioa1, MIWU0_33),
+    (Gpioa2, MIWU0_34),
+    (Gpioa3, MIWU0_35),
+    (Gpioa4, MIWU0_36),
+    (Gpioa5, MIWU0_37),
+
+    (Gpioa6, MIWU0_40),
+    (Gpioa7, MIWU0_41),
+    (Gpiob0, MIWU0_42),
+    (Gpiob1, MIWU0_45),
+    (Gpiob2, MIWU0_46),
+
+    (Gpiob3, MIWU0_50),
+    (Gpiob4, MIWU0_51),
+    (Gpiob5, MIWU0_52),
+    (Gpiob7, MIWU0_54),
+
+    (Gpioc0, MIWU0_60),
+    (Gpioc1, MIWU0_61),
+    (Gpioc2, MIWU0_62),
+    (Gpioc3, MIWU0_63),
+    (Gpioc4, MIWU0_64),
+    (Gpioc5, MIWU0_65),
+    (Gpioc6, MIWU0_66),
+    (Gpioc7, MIWU0_67),
+
+    (Gpiod0, MIWU0_70),
+    (Gpiod1, MIWU0_71),
+    (Gpiod2, MIWU0_72),
+    (Gpiod3, MIWU0_73),
+    (Gpiod4, MIWU0_74),
+    (Gpiod5, MIWU0_75),
+    (Gpioe0, MIWU0_77),
+
+    (Gpioe1, MIWU0_80),
+    (Gpioe2, MIWU0_81),
+    (Gpioe3, MIWU0_82),
+    (Gpioe4, MIWU0_83),
+    (Gpioe5, MIWU0_84),
+    (Gpiof0, MIWU0_85),
+    (Gpiof3, MIWU0_86),
+    (Gpioe7, MIWU0_87),
+
+    (Gpio00, MIWU1_10),
+    (Gpio01, MIWU1_11),
+    (Gpio02, MIWU1_12),
+    (Gpio03, MIWU1_13),
+    (Gpio04, MIWU1_14),
+    (Gpio05, MIWU1_15),
+    (Gpio06, MIWU1_16),
+    (Gpio07, MIWU1_17),
+
+    (Gpio10, MIWU1_20),
+    (Gpio11, MIWU1_21),
+    (Gpiof4, MIWU1_22),
+    (Gpio13, MIWU1_23),
+    (Gpio14, MIWU1_24),
+    (Gpio15, MIWU1_25),
+    (Gpio16, MIWU1_26),
+    (Gpio17, MIWU1_27),
+
+    (Gpio31, MIWU1_30),
+    (Gpio30, MIWU1_31),
+    (Gpio27, MIWU1_32),
+    (Gpio26, MIWU1_33),
+    (Gpio25, MIWU1_34),
+    (Gpio24, MIWU1_35),
+    (Gpio23, MIWU1_36),
+    (Gpio22, MIWU1_37),
+
+    (Gpio20, MIWU1_40),
+    (Gpio21, MIWU1_41),
+    (Gpiof5, MIWU1_42),
+    (Gpio33, MIWU1_43),
+    (Gpio34, MIWU1_44),
+    (Gpio36, MIWU1_46),
+    (Gpio37, MIWU1_47),
+
+    (Gpio40, MIWU1_50),
+    (Gpio41, MIWU1_51),
+    (Gpio42, MIWU1_52),
+    (Gpio43, MIWU1_53),
+    (Gpio44, MIWU1_54),
+    (Gpio45, MIWU1_55),
+    (Gpio46, MIWU1_56),
+    (Gpio47, MIWU1_57),
+
+    (Gpio50, MIWU1_60),
+    (Gpio51, MIWU1_61),
+    (Gpio52, MIWU1_62),
+    (Gpio53, MIWU1_63),
+    (Gpio54, MIWU1_64),
+    (Gpio55, MIWU1_65),
+    (Gpio56, MIWU1_66),
+    (Gpio57, MIWU1_67),
+
+    (Gpio60, MIWU1_70),
+    (Gpio61, MIWU1_71),
+    (Gpio62, MIWU1_72),
+    (Gpio63, MIWU1_73),
+    (Gpio64, MIWU1_74),
+
+    (Gpio70, MIWU1_80),
+    (Gpio67, MIWU1_81),
+    (Gpio72, MIWU1_82),
+    (Gpio73, MIWU1_83),
+    (Gpio74, MIWU1_84),
+    (Gpio75, MIWU1_85),
+    (Gpio76, MIWU1_86),
+
+    (Gpio12, MIWU2_60),
+    (Gpiof1, MIWU2_61),
+    (Gpiof2, MIWU2_62),
+    (Gpiod6, MIWU2_65),
+}
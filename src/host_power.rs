@@ -0,0 +1,163 @@
+//! Host power state tracking
+//!
+//! The host signals its sleep states with the `SLP_S3#`, `SLP_S5#` and `SUS_WARN#` wires, as eSPI virtual wires or as
+//! GPIO pins in `LPC` designs. A [HostPowerTracker] is fed the levels of these wires by whichever driver receives
+//! them, and turns them into a single [HostPowerState] that tasks can await.
+//!
+//! ```rust,ignore
+//! static HOST_POWER: HostPowerTracker = HostPowerTracker::new();
+//!
+//! // In the task receiving the wires:
+//! HOST_POWER.set_wire(HostWire::SlpS3, slp_s3.is_low());
+//!
+//! // In the task managing EC power:
+//! HOST_POWER.wait_for(HostPowerState::S0).await;
+//! ```
+
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use critical_section::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+
+/// The sleep state of the host.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostPowerState {
+    /// Working, neither `SLP_S3#` nor `SLP_S5#` is asserted
+    S0,
+    /// Suspended to RAM, `SLP_S3#` is asserted
+    S3,
+    /// Soft off, `SLP_S5#` is asserted
+    S5,
+}
+
+/// The wires that make up the [HostPowerState].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostWire {
+    /// `SLP_S3#`
+    SlpS3,
+    /// `SLP_S5#`
+    SlpS5,
+    /// `SUS_WARN#`, the host is about to remove the suspend well power
+    SusWarn,
+}
+
+impl HostWire {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Wires {
+    /// The asserted wires, by [HostWire::bit]
+    asserted: u8,
+    /// Incremented on every change of the [HostPowerState] or the `SUS_WARN#` wire
+    generation: u32,
+}
+
+/// Tracks the [HostPowerState] from the host sleep wires.
+///
+/// Starts out in [HostPowerState::S0] until the wires are set. Only a single task can await changes at a time.
+pub struct HostPowerTracker {
+    wires: Mutex<Cell<Wires>>,
+    waker: AtomicWaker,
+}
+
+impl HostPowerTracker {
+    /// Create a new tracker, with none of the wires asserted.
+    pub const fn new() -> Self {
+        Self {
+            wires: Mutex::new(Cell::new(Wires {
+                asserted: 0,
+                generation: 0,
+            })),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    fn wires(&self) -> Wires {
+        critical_section::with(|cs| self.wires.borrow(cs).get())
+    }
+
+    /// Record the level of a wire, where asserted means the (active low) wire is low.
+    pub fn set_wire(&self, wire: HostWire, asserted: bool) {
+        let changed = critical_section::with(|cs| {
+            let cell = self.wires.borrow(cs);
+            let mut wires = cell.get();
+            let old = wires.asserted;
+
+            match asserted {
+                true => wires.asserted |= wire.bit(),
+                false => wires.asserted &= !wire.bit(),
+            }
+
+            let changed = Self::state_of(old) != Self::state_of(wires.asserted)
+                || (old ^ wires.asserted) & HostWire::SusWarn.bit() != 0;
+            if changed {
+                wires.generation = wires.generation.wrapping_add(1);
+            }
+
+            cell.set(wires);
+            changed
+        });
+
+        if changed {
+            self.waker.wake();
+        }
+    }
+
+    fn state_of(asserted: u8) -> HostPowerState {
+        if asserted & HostWire::SlpS5.bit() != 0 {
+            HostPowerState::S5
+        } else if asserted & HostWire::SlpS3.bit() != 0 {
+            HostPowerState::S3
+        } else {
+            HostPowerState::S0
+        }
+    }
+
+    /// The current host power state.
+    #[must_use]
+    pub fn state(&self) -> HostPowerState {
+        Self::state_of(self.wires().asserted)
+    }
+
+    /// Returns true while `SUS_WARN#` is asserted.
+    #[must_use]
+    pub fn suspend_warning(&self) -> bool {
+        self.wires().asserted & HostWire::SusWarn.bit() != 0
+    }
+
+    /// Wait for the next change of the host power state or of `SUS_WARN#`, and return the new state.
+    pub async fn wait_for_change(&self) -> HostPowerState {
+        let start = self.wires().generation;
+
+        poll_fn(|cx| {
+            self.waker.register(cx.waker());
+
+            let wires = self.wires();
+            match wires.generation == start {
+                true => Poll::Pending,
+                false => Poll::Ready(Self::state_of(wires.asserted)),
+            }
+        })
+        .await
+    }
+
+    /// Wait until the host is in `state`, returning immediately if it already is.
+    pub async fn wait_for(&self, state: HostPowerState) {
+        while self.state() != state {
+            self.wait_for_change().await;
+        }
+    }
+}
+
+impl Default for HostPowerTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,6 @@
 //! Implementation of the I2C peripheral
 
+use core::cell::Cell;
 use core::future::Future;
 use core::marker::PhantomData;
 
@@ -9,15 +10,25 @@ use embassy_sync::waitqueue::AtomicWaker;
 pub use embedded_hal_async::i2c::{I2c, Operation};
 
 use crate::cancellation::CancellationToken;
-use crate::cdcg::get_clocks;
+use crate::cdcg::{get_clocks, ClockChangeHook, ClockChangeRegistration};
 use crate::gpio::Pin;
 use crate::interrupt::typelevel::Interrupt;
+use crate::pmc::{AnyPowerDomain, ClockGuard, SuspendHook, SuspendRegistration};
+use crate::timer::itim::{IntervalTimer, Timeout, TimeoutGuard};
+use crate::timer::IntervalInstance;
 
 // Size of the peripherals fifo
 const FIFO_SIZE: u8 = 32;
 // Number of bytes between refilling/emptying of the fifo
 const GROUP_SIZE: u8 = 24;
 
+// Half period of SCL while recovering the bus, in microseconds (100kHz)
+const RECOVERY_HALF_PERIOD_US: u32 = 5;
+// Longest time a target may hold SCL low while recovering the bus, in microseconds (SMBus tTIMEOUT,MIN)
+const RECOVERY_SCL_TIMEOUT_US: u32 = 35_000;
+// Number of clocks after which any target has shifted out the rest of its byte and released SDA
+const RECOVERY_CLOCKS: usize = 9;
+
 /// SMBUS Broadcast address
 pub const GENERALCALL_ADDRESS: u8 = 0;
 /// SMBUS ARP address
@@ -98,6 +109,9 @@ pub struct Config {
     pub speed: Speed,
     /// If true, the internal pullups are enabled
     pub pullup: bool,
+    /// If true, the pins use 1.8V low voltage input detection.
+    /// Can't be combined with the internal pullups, as those pull up to the 3.3V supply.
+    pub low_voltage: bool,
 }
 
 /// Error type for the I2C operations
@@ -137,6 +151,16 @@ pub enum ListenError {
     InvalidAddressList,
 }
 
+/// Error type for [I2CController::recover_bus]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecoveryError {
+    /// A target kept SCL low for longer than the SMBus timeout
+    SclStuckLow,
+    /// SDA was still low after clocking out a full byte
+    SdaStuckLow,
+}
+
 /// Commands the user has to handle in the listen code
 #[derive(Debug, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -180,6 +204,11 @@ impl Peripheral for AnySMB {
 /// An instance of the I2C driver
 pub struct I2CController<'a> {
     _dev: PeripheralRef<'a, AnySMB>,
+    _clock: ClockGuard,
+    retune: ClockChangeRegistration,
+    _suspend: SuspendRegistration,
+    /// The APB bus of the controller, kept running while a transaction is in progress
+    domain: AnyPowerDomain,
     regs: &'static crate::pac::smb0::RegisterBlock,
     waker: &'static AtomicWaker,
 }
@@ -229,7 +258,7 @@ impl<'p> I2CController<'p> {
     /// Initialize speed settings
     ///
     /// Should be called only with bank 0 selected and peripheral disabled
-    fn speed_init(&mut self, speed: Speed, clk: u32) {
+    fn speed_init(regs: &crate::pac::smb0::RegisterBlock, speed: Speed, clk: u32) {
         struct StandardMode {
             sclfrq: u16,
             hldt: u8,
@@ -384,17 +413,15 @@ impl<'p> I2CController<'p> {
                     i += 1;
                 }
 
-                self.regs.smbn_ctl3().modify(|_, w| unsafe {
+                regs.smbn_ctl3().modify(|_, w| unsafe {
                     w._400k_mode()
                         .clear_bit()
                         .sclfrq8_7()
                         .bits(((STANDARDMODE[i].1.sclfrq & 0x180) >> 7) as u8)
                 });
-                self.regs
-                    .smbn_ctl4()
+                regs.smbn_ctl4()
                     .modify(|_, w| unsafe { w.hldt().bits(STANDARDMODE[i].1.hldt) });
-                self.regs
-                    .smbn_ctl2()
+                regs.smbn_ctl2()
                     .modify(|_, w| unsafe { w.sclfrq6_0().bits((STANDARDMODE[i].1.sclfrq & 0x7F) as u8) });
             }
             Speed::Fast => {
@@ -403,15 +430,13 @@ impl<'p> I2CController<'p> {
                     i += 1;
                 }
 
-                self.regs
-                    .smbn_ctl3()
+                regs.smbn_ctl3()
                     .modify(|_, w| unsafe { w._400k_mode().set_bit().sclfrq8_7().bits(0) });
-                self.regs
-                    .smbn_ctl4()
+                regs.smbn_ctl4()
                     .modify(|_, w| unsafe { w.hldt().bits(FASTMODE[i].1.hldt) });
-                self.regs.smbn_ctl2().modify(|_, w| unsafe { w.sclfrq6_0().bits(0) });
-                self.regs.smbn_scllt().write(|w| unsafe { w.bits(FASTMODE[i].1.scllt) });
-                self.regs.smbn_sclht().write(|w| unsafe { w.bits(FASTMODE[i].1.sclht) });
+                regs.smbn_ctl2().modify(|_, w| unsafe { w.sclfrq6_0().bits(0) });
+                regs.smbn_scllt().write(|w| unsafe { w.bits(FASTMODE[i].1.scllt) });
+                regs.smbn_sclht().write(|w| unsafe { w.bits(FASTMODE[i].1.sclht) });
             }
             Speed::FastPlus => {
                 let mut i = 0;
@@ -419,19 +444,13 @@ impl<'p> I2CController<'p> {
                     i += 1;
                 }
 
-                self.regs
-                    .smbn_ctl3()
+                regs.smbn_ctl3()
                     .modify(|_, w| unsafe { w._400k_mode().set_bit().sclfrq8_7().bits(0) });
-                self.regs
-                    .smbn_ctl4()
+                regs.smbn_ctl4()
                     .modify(|_, w| unsafe { w.hldt().bits(FASTMODEPLUS[i].1.hldt) });
-                self.regs.smbn_ctl2().modify(|_, w| unsafe { w.sclfrq6_0().bits(0) });
-                self.regs
-                    .smbn_scllt()
-                    .write(|w| unsafe { w.bits(FASTMODEPLUS[i].1.scllt) });
-                self.regs
-                    .smbn_sclht()
-                    .write(|w| unsafe { w.bits(FASTMODEPLUS[i].1.sclht) });
+                regs.smbn_ctl2().modify(|_, w| unsafe { w.sclfrq6_0().bits(0) });
+                regs.smbn_scllt().write(|w| unsafe { w.bits(FASTMODEPLUS[i].1.scllt) });
+                regs.smbn_sclht().write(|w| unsafe { w.bits(FASTMODEPLUS[i].1.sclht) });
             }
         }
     }
@@ -448,6 +467,11 @@ impl<'p> I2CController<'p> {
     where
         (T, Scl, Sda, Mode): ValidI2CConfig,
     {
+        assert!(
+            !(config.pullup && config.low_voltage),
+            "The internal pullups can't be used on a low voltage bus"
+        );
+
         // Safety: _irqs ensures an interrupt handler is bound
         unsafe {
             T::Interrupt::enable();
@@ -468,10 +492,24 @@ impl<'p> I2CController<'p> {
             // Safety: We have exclusive ownership over the peripherals.
             unsafe { <(T, Scl, Sda, Mode) as sealed::SealedValidI2CConfig>::setup_pins(cs) };
             unsafe { <(T, Scl, Sda, Mode) as sealed::SealedValidI2CConfig>::setup_pullup(cs, config.pullup) };
+
+            if config.low_voltage {
+                // Safety: The pins are now driven open-drain by the SMB module and no pullups are enabled
+                unsafe {
+                    scl.set_low_voltage(cs, true);
+                }
+                unsafe {
+                    sda.set_low_voltage(cs, true);
+                }
+            }
         });
 
         let mut dev = Self {
             _dev: peri.map_into(),
+            _clock: ClockGuard::new::<T>(),
+            retune: ClockChangeRegistration::new::<Suspend<T>>(T::regs() as *const _ as usize, config.speed as u32),
+            _suspend: SuspendRegistration::new::<Suspend<T>>(T::regs() as *const _ as usize, config.speed as u32),
+            domain: AnyPowerDomain::of::<T::Domain>(),
             regs: T::regs(),
             waker: T::waker(),
         };
@@ -490,7 +528,7 @@ impl<'p> I2CController<'p> {
         });
         dev.regs.smbn_ctl4().modify(|_, w| w.lvl_we().clear_bit());
         // Safety: We have the peripheral, so init was called.
-        dev.speed_init(config.speed, unsafe { T::clockfreq() });
+        Self::speed_init(dev.regs, config.speed, unsafe { T::clockfreq() });
         dev.regs.smbn_fif_ctl().modify(|_, w| w.fifo_en().set_bit());
         dev.regs.smbn_ctl2().modify(|_, w| w.enable().set_bit());
         dev.bank_sel(true);
@@ -703,6 +741,8 @@ impl<'p> I2CController<'p> {
             Write,
         }
 
+        let _busy = self.retune.busy();
+        let _domain = self.domain.token();
         let mut prevop = PrevOpType::None;
 
         for (op, last) in operations.iter_mut().mark_last() {
@@ -863,6 +903,83 @@ impl<'p> I2CController<'p> {
             .await
     }
 
+    /// Free a bus on which a target holds SDA low, for example because it was reset in the middle of a read.
+    ///
+    /// Clocks SCL until the target releases SDA, up to a full byte, and then generates a STOP condition. Waiting for a
+    /// target to release SCL is bounded by a [TimeoutGuard] on `timer`, and `timer` also times the clock pulses.
+    /// Blocks for about 100us on a healthy bus, and for up to the SMBus timeout of 35ms per clock pulse otherwise.
+    pub fn recover_bus<T: IntervalInstance>(&mut self, timer: &mut IntervalTimer<'_, T>) -> Result<(), RecoveryError> {
+        self.regs.smbn_ctl2().modify(|_, w| w.enable().clear_bit());
+        self.bank_sel(false);
+        self.regs.smbn_ctl4().modify(|_, w| w.lvl_we().set_bit());
+
+        let result = self.clock_out_bus(timer);
+
+        self.regs
+            .smbn_ctl3()
+            .modify(|_, w| w.scl_lvl().set_bit().sda_lvl().set_bit());
+        self.regs.smbn_ctl4().modify(|_, w| w.lvl_we().clear_bit());
+        self.regs.smbn_fif_ctl().modify(|_, w| w.fifo_en().set_bit());
+        self.regs.smbn_ctl2().modify(|_, w| w.enable().set_bit());
+        self.bank_sel(true);
+
+        result
+    }
+
+    /// Drive the bus for [I2CController::recover_bus], with the level control enabled.
+    fn clock_out_bus<T: IntervalInstance>(&mut self, timer: &mut IntervalTimer<'_, T>) -> Result<(), RecoveryError> {
+        let regs = self.regs;
+        let half_period = (u64::from(RECOVERY_HALF_PERIOD_US) * u64::from(timer.frequency())).div_ceil(1_000_000);
+        let half_period = half_period.clamp(1, u64::from(u32::MAX)) as u32;
+
+        // Reading the level bits returns the level on the pins, not the level that is driven.
+        let sda_high = || regs.smbn_ctl3().read().sda_lvl().bit_is_set();
+        let drive = |scl: bool, sda: bool| {
+            regs.smbn_ctl3().modify(|_, w| w.scl_lvl().bit(scl).sda_lvl().bit(sda));
+        };
+        let delay = |timer: &mut IntervalTimer<'_, T>| {
+            timer.start_oneshot(half_period);
+            while !timer.take_expired() {}
+        };
+        // A target may stretch the clock, so SCL is only high once it also released it.
+        let release_scl = |timer: &mut IntervalTimer<'_, T>| {
+            TimeoutGuard::new(timer, RECOVERY_SCL_TIMEOUT_US)
+                .wait_until(|| regs.smbn_ctl3().read().scl_lvl().bit_is_set().then_some(()))
+                .map_err(|Timeout| RecoveryError::SclStuckLow)
+        };
+
+        release_scl(timer)?;
+        for _ in 0..RECOVERY_CLOCKS {
+            if sda_high() {
+                break;
+            }
+            drive(false, true);
+            delay(timer);
+            drive(true, true);
+            release_scl(timer)?;
+            delay(timer);
+        }
+        if !sda_high() {
+            return Err(RecoveryError::SdaStuckLow);
+        }
+
+        // STOP condition: SDA rises while SCL is high
+        drive(false, true);
+        delay(timer);
+        drive(false, false);
+        delay(timer);
+        drive(true, false);
+        release_scl(timer)?;
+        delay(timer);
+        drive(true, true);
+        delay(timer);
+
+        match sda_high() {
+            true => Ok(()),
+            false => Err(RecoveryError::SdaStuckLow),
+        }
+    }
+
     fn configure_addresses(&mut self, addresses: &[u8]) -> Result<(), ListenError> {
         let mut cnt = 0;
         for (i, addr) in addresses.iter().copied().enumerate() {
@@ -1001,6 +1118,8 @@ impl<'p> I2CController<'p> {
     }
 
     async fn handle_listen_transaction(&mut self, mut handler: impl FnMut(u8, ListenCommand)) {
+        let _busy = self.retune.busy();
+        let _domain = self.domain.token();
         let Some(addr) = self.decode_addr() else {
             // Spurious nmatch, clear it and return
             self.regs.smbn_st().write(|w| w.nmatch().set_bit());
@@ -1213,6 +1332,103 @@ impl<'p> I2CController<'p> {
     }
 }
 
+/// Re-tune the bus speed of SMB `T` after the clocks changed.
+fn retune<T: Instance>(speed: u32) {
+    let speed = match speed {
+        0 => Speed::Standard,
+        1 => Speed::Fast,
+        _ => Speed::FastPlus,
+    };
+
+    let regs = T::regs();
+    regs.smbn_ctl2().modify(|_, w| w.enable().clear_bit());
+    regs.smbn_ctl3()
+        .modify(|_, w| w.scl_lvl().set_bit().sda_lvl().set_bit().bnk_sel().clear_bit());
+    // Safety: the clocks changed, so they have been initialized.
+    I2CController::speed_init(regs, speed, unsafe { T::clockfreq() });
+    regs.smbn_fif_ctl().modify(|_, w| w.fifo_en().set_bit());
+    regs.smbn_ctl2().modify(|_, w| w.enable().set_bit());
+    regs.smbn_ctl3()
+        .modify(|_, w| w.scl_lvl().set_bit().sda_lvl().set_bit().bnk_sel().set_bit());
+}
+
+/// The target configuration of an SMB, saved while suspended
+#[derive(Copy, Clone)]
+struct Suspended {
+    /// The `SMBnADDR1..8` registers
+    addresses: [u8; 8],
+    /// `SMBnCTL1.GCMEN`: respond to the general call address
+    general_call: bool,
+    /// `SMBnCTL3.ARPMEN`: respond to the ARP address
+    arp: bool,
+    /// `SMBnCTL1.INTEN`: a driver is waiting for the interrupt
+    interrupt: bool,
+}
+
+/// Restores the configuration of SMB `T` after deep sleep, and re-tunes it after the clocks changed.
+///
+/// The speed is recomputed by [retune], the own addresses and the interrupt enable of a listening controller are saved
+/// before deep sleep and written back after it.
+struct Suspend<T>(PhantomData<T>);
+
+impl<T: Instance> SuspendHook for Suspend<T> {
+    fn suspend(_speed: u32) {
+        let regs = T::regs();
+
+        regs.smbn_ctl3().modify(|_, w| w.bnk_sel().clear_bit());
+        let saved = Suspended {
+            addresses: [
+                regs.smbn_addr1().read().bits(),
+                regs.smbn_addr2().read().bits(),
+                regs.smbn_addr3().read().bits(),
+                regs.smbn_addr4().read().bits(),
+                regs.smbn_addr5().read().bits(),
+                regs.smbn_addr6().read().bits(),
+                regs.smbn_addr7().read().bits(),
+                regs.smbn_addr8().read().bits(),
+            ],
+            general_call: regs.smbn_ctl1().read().gcmen().bit_is_set(),
+            arp: regs.smbn_ctl3().read().arpmen().bit_is_set(),
+            interrupt: regs.smbn_ctl1().read().inten().bit_is_set(),
+        };
+        regs.smbn_ctl3().modify(|_, w| w.bnk_sel().set_bit());
+
+        critical_section::with(|cs| T::suspended().borrow(cs).set(saved));
+    }
+
+    fn resume(speed: u32) {
+        retune::<T>(speed);
+
+        let regs = T::regs();
+        let saved = critical_section::with(|cs| T::suspended().borrow(cs).get());
+        let [addr1, addr2, addr3, addr4, addr5, addr6, addr7, addr8] = saved.addresses;
+
+        regs.smbn_ctl3().modify(|_, w| w.bnk_sel().clear_bit());
+        regs.smbn_addr1().write(|w| unsafe { w.bits(addr1) });
+        regs.smbn_addr2().write(|w| unsafe { w.bits(addr2) });
+        regs.smbn_addr3().write(|w| unsafe { w.bits(addr3) });
+        regs.smbn_addr4().write(|w| unsafe { w.bits(addr4) });
+        regs.smbn_addr5().write(|w| unsafe { w.bits(addr5) });
+        regs.smbn_addr6().write(|w| unsafe { w.bits(addr6) });
+        regs.smbn_addr7().write(|w| unsafe { w.bits(addr7) });
+        regs.smbn_addr8().write(|w| unsafe { w.bits(addr8) });
+        regs.smbn_ctl1().modify(|_, w| w.gcmen().bit(saved.general_call));
+        regs.smbn_ctl3()
+            .modify(|_, w| w.arpmen().bit(saved.arp).bnk_sel().set_bit());
+
+        // Note(cs): interrupt handler changes this register as well.
+        critical_section::with(|_| {
+            regs.smbn_ctl1().modify(|_, w| w.inten().bit(saved.interrupt));
+        });
+    }
+}
+
+impl<T: Instance> ClockChangeHook for Suspend<T> {
+    fn retune(speed: u32) {
+        retune::<T>(speed);
+    }
+}
+
 impl embedded_hal_async::i2c::ErrorType for I2CController<'_> {
     type Error = Error;
 }
@@ -1231,9 +1447,14 @@ mod sealed {
         unsafe fn setup_pullup(_cs: critical_section::CriticalSection, enable: bool);
     }
 
-    pub trait SealedInstance {
+    #[allow(private_bounds)]
+    pub trait SealedInstance: crate::pmc::ClockGated {
+        /// The APB bus clocking the controller
+        type Domain: crate::pmc::PowerDomain;
+
         fn waker() -> &'static AtomicWaker;
         fn regs() -> &'static crate::pac::smb0::RegisterBlock;
+        fn suspended() -> &'static critical_section::Mutex<core::cell::Cell<crate::i2c::Suspended>>;
         /// Safety: should only be called after clock init
         unsafe fn clockfreq() -> u32;
     }
@@ -1249,8 +1470,10 @@ pub trait Instance: sealed::SealedInstance + embassy_hal_internal::Peripheral<P
 }
 
 macro_rules! impl_instance {
-    ($instance:ident, $pac:ident, $clock:ident) => {
+    ($instance:ident, $pac:ident, $clock:ident, $domain:ident) => {
         impl sealed::SealedInstance for crate::peripherals::$instance {
+            type Domain = crate::pmc::$domain;
+
             fn waker() -> &'static AtomicWaker {
                 static WAKER: AtomicWaker = AtomicWaker::new();
                 &WAKER
@@ -1261,6 +1484,17 @@ macro_rules! impl_instance {
                 unsafe { &*crate::pac::$pac::PTR }
             }
 
+            fn suspended() -> &'static critical_section::Mutex<Cell<Suspended>> {
+                static SUSPENDED: critical_section::Mutex<Cell<Suspended>> =
+                    critical_section::Mutex::new(Cell::new(Suspended {
+                        addresses: [0; 8],
+                        general_call: false,
+                        arp: false,
+                        interrupt: false,
+                    }));
+                &SUSPENDED
+            }
+
             unsafe fn clockfreq() -> u32 {
                 // Safety: We require clock init to be called before this is called
                 unsafe { get_clocks() }.$clock
@@ -1351,14 +1585,14 @@ macro_rules! impl_config_lpc {
     };
 }
 
-impl_instance!(SMB0, Smb0, apb3_clk);
-impl_instance!(SMB1, Smb1, apb3_clk);
-impl_instance!(SMB2, Smb2, apb2_clk);
-impl_instance!(SMB3, Smb3, apb2_clk);
-impl_instance!(SMB4, Smb4, apb3_clk);
-impl_instance!(SMB5, Smb5, apb3_clk);
-impl_instance!(SMB6, Smb6, apb3_clk);
-impl_instance!(SMB7, Smb7, apb3_clk);
+impl_instance!(SMB0, Smb0, apb3_clk, Apb3);
+impl_instance!(SMB1, Smb1, apb3_clk, Apb3);
+impl_instance!(SMB2, Smb2, apb2_clk, Apb2);
+impl_instance!(SMB3, Smb3, apb2_clk, Apb2);
+impl_instance!(SMB4, Smb4, apb3_clk, Apb3);
+impl_instance!(SMB5, Smb5, apb3_clk, Apb3);
+impl_instance!(SMB6, Smb6, apb3_clk, Apb3);
+impl_instance!(SMB7, Smb7, apb3_clk, Apb3);
 
 impl_config!(
     SMB0,
@@ -0,0 +1,443 @@
+//! Low-power Clock Timer (LCT)
+//!
+//! The LCT counts down seconds, minutes, hours, days and weeks from the `LFCLK`, in the `VSBY` power well. It keeps
+//! counting in deep sleep, and signals its [LCT](crate::miwu::internal::LCT) wake-up input when it reaches zero.
+//! [Lct] builds a seconds clock on it, for a real-time clock without an external RTC chip: the time is the seconds
+//! counted down since the counters were last loaded, so the counters are loaded with the time until the next alarm,
+//! or with their full range of 255 weeks when there is none.
+//!
+//! The counters stop at zero. Keep awaiting [Lct::wait_for_alarm] while an alarm is set, so the counters are reloaded
+//! right after they stopped.
+//!
+//! The counters only count whole seconds, and loading them restarts the current second. Every load drops the fraction
+//! of the second that had passed, so the clock falls behind by up to a second each time an alarm is set or cancelled.
+//! The reload by [Lct::wait_for_alarm] happens at the end of a second, and only loses the latency until the task runs.
+//! Set the time again from the host now and then when alarms are changed often.
+//!
+//! For wall-clock time, [Lct::set_datetime] sets the time in seconds since the Unix epoch, which [Lct::now] converts
+//! back into a [DateTime]. The host typically provides the time after boot.
+
+use core::cell::Cell;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::miwu::internal::LCT as LCT_WUI;
+use crate::miwu::{InterruptHandler, Level, WakeUp, WakeUpInput};
+use crate::peripherals::LCT;
+
+/// `LCTCONT`: run the counters
+const LCTCONT_EN: u8 = 1 << 0;
+/// `LCTCONT`: signal the wake-up input when the counters reach zero
+const LCTCONT_EVEN: u8 = 1 << 1;
+/// `LCTSTAT`: the counters reached zero, cleared by writing 1
+const LCTSTAT_EVST: u8 = 1 << 0;
+
+const SECONDS_PER_MINUTE: u32 = 60;
+const SECONDS_PER_HOUR: u32 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: u32 = 24 * SECONDS_PER_HOUR;
+const SECONDS_PER_WEEK: u32 = 7 * SECONDS_PER_DAY;
+
+/// The value of the LCT counters.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LctTime {
+    /// Weeks, 0 through 255
+    pub weeks: u8,
+    /// Days, 0 through 6
+    pub days: u8,
+    /// Hours, 0 through 23
+    pub hours: u8,
+    /// Minutes, 0 through 59
+    pub minutes: u8,
+    /// Seconds, 0 through 59
+    pub seconds: u8,
+}
+
+impl LctTime {
+    /// The largest value of the counters, almost 5 years.
+    pub const MAX: Self = Self {
+        weeks: u8::MAX,
+        days: 6,
+        hours: 23,
+        minutes: 59,
+        seconds: 59,
+    };
+
+    /// Split a number of seconds over the counters.
+    ///
+    /// Returns `None` if `seconds` is larger than [LctTime::MAX].
+    pub fn from_seconds(seconds: u32) -> Option<Self> {
+        if seconds > Self::MAX.as_seconds() {
+            return None;
+        }
+
+        Some(Self {
+            weeks: (seconds / SECONDS_PER_WEEK) as u8,
+            days: (seconds % SECONDS_PER_WEEK / SECONDS_PER_DAY) as u8,
+            hours: (seconds % SECONDS_PER_DAY / SECONDS_PER_HOUR) as u8,
+            minutes: (seconds % SECONDS_PER_HOUR / SECONDS_PER_MINUTE) as u8,
+            seconds: (seconds % SECONDS_PER_MINUTE) as u8,
+        })
+    }
+
+    /// The total number of seconds.
+    #[must_use]
+    pub const fn as_seconds(&self) -> u32 {
+        self.weeks as u32 * SECONDS_PER_WEEK
+            + self.days as u32 * SECONDS_PER_DAY
+            + self.hours as u32 * SECONDS_PER_HOUR
+            + self.minutes as u32 * SECONDS_PER_MINUTE
+            + self.seconds as u32
+    }
+}
+
+/// The day of the week.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// A civil date and time in UTC, from 1970 up to the 7th of February 2106.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+/// Returns true if `year` has a 29th of February.
+const fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    /// The first representable time, the Unix epoch.
+    pub const MIN: Self = Self {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+
+    /// Create a date and time from its parts, with `month` and `day` counting from 1.
+    ///
+    /// Returns `None` if a part is out of range, or the time is after 2106-02-07 06:28:15, the last second that fits
+    /// the seconds counter.
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        if year < 1970 || year > 2106 || month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        let datetime = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+
+        // The seconds counter runs out early in 2106.
+        if datetime.days_since_epoch() as u64 * SECONDS_PER_DAY as u64 + datetime.seconds_of_day() as u64
+            > u32::MAX as u64
+        {
+            return None;
+        }
+
+        Some(datetime)
+    }
+
+    /// The date and time `seconds` after the Unix epoch.
+    pub const fn from_unix(seconds: u32) -> Self {
+        let days = seconds / SECONDS_PER_DAY;
+        let seconds_of_day = seconds % SECONDS_PER_DAY;
+
+        // Count from the 1st of March 0000, so the leap day is at the end of the year.
+        // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / SECONDS_PER_HOUR) as u8,
+            minute: (seconds_of_day % SECONDS_PER_HOUR / SECONDS_PER_MINUTE) as u8,
+            second: (seconds_of_day % SECONDS_PER_MINUTE) as u8,
+        }
+    }
+
+    /// The number of days since the Unix epoch.
+    const fn days_since_epoch(&self) -> u32 {
+        // See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = self.year as u32 - if self.month <= 2 { 1 } else { 0 };
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let shifted_month = (self.month as u32 + 9) % 12;
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day as u32 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    const fn seconds_of_day(&self) -> u32 {
+        self.hour as u32 * SECONDS_PER_HOUR + self.minute as u32 * SECONDS_PER_MINUTE + self.second as u32
+    }
+
+    /// The number of seconds since the Unix epoch.
+    #[must_use]
+    pub const fn to_unix(&self) -> u32 {
+        self.days_since_epoch() * SECONDS_PER_DAY + self.seconds_of_day()
+    }
+
+    /// The year.
+    #[must_use]
+    pub const fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, 1 through 12.
+    #[must_use]
+    pub const fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, counting from 1.
+    #[must_use]
+    pub const fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, 0 through 23.
+    #[must_use]
+    pub const fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, 0 through 59.
+    #[must_use]
+    pub const fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, 0 through 59.
+    #[must_use]
+    pub const fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// The day of the week.
+    #[must_use]
+    pub const fn weekday(&self) -> Weekday {
+        // The Unix epoch was a Thursday.
+        match (self.days_since_epoch() + 3) % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+}
+
+/// Real-time clock and alarm on the LCT.
+pub struct Lct<'d> {
+    _lct: PeripheralRef<'d, LCT>,
+    alarm: WakeUp<'d>,
+    /// The time in seconds at which the counters were last loaded
+    base: Cell<u32>,
+    /// The number of seconds the counters were last loaded with
+    load: Cell<u32>,
+}
+
+impl<'d> Lct<'d> {
+    /// Start the LCT, with the time at 0 and no alarm.
+    pub fn new(
+        lct: impl Peripheral<P = LCT> + 'd,
+        wui: impl Peripheral<P = LCT_WUI> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<<LCT_WUI as WakeUpInput>::Interrupt, InterruptHandler<LCT_WUI>>,
+    ) -> Self {
+        into_ref!(lct);
+        let this = Self {
+            _lct: lct,
+            alarm: crate::miwu::claim_for_peripheral(wui, irqs),
+            base: Cell::new(0),
+            load: Cell::new(0),
+        };
+        this.load(0, LctTime::MAX);
+        this
+    }
+
+    fn regs() -> &'static crate::pac::lct::RegisterBlock {
+        // Safety: the LCT is owned by this driver.
+        unsafe { &*crate::pac::Lct::PTR }
+    }
+
+    /// Stop the counters and load them with `value`, at time `base`.
+    fn load(&self, base: u32, value: LctTime) {
+        let r = Self::regs();
+
+        r.lctcont().write(|w| unsafe { w.bits(0) });
+        while r.lctcont().read().bits() & LCTCONT_EN != 0 {}
+        r.lctstat().write(|w| unsafe { w.bits(LCTSTAT_EVST) });
+
+        r.lctweek().write(|w| unsafe { w.bits(value.weeks) });
+        r.lctday().write(|w| unsafe { w.bits(value.days) });
+        r.lcthour().write(|w| unsafe { w.bits(value.hours) });
+        r.lctminute().write(|w| unsafe { w.bits(value.minutes) });
+        r.lctsecond().write(|w| unsafe { w.bits(value.seconds) });
+
+        self.base.set(base);
+        self.load.set(value.as_seconds());
+
+        r.lctcont().write(|w| unsafe { w.bits(LCTCONT_EN | LCTCONT_EVEN) });
+    }
+
+    /// The current value of the counters.
+    #[must_use]
+    pub fn counters(&self) -> LctTime {
+        let r = Self::regs();
+        let read = || LctTime {
+            weeks: r.lctweek().read().bits(),
+            days: r.lctday().read().bits(),
+            hours: r.lcthour().read().bits(),
+            minutes: r.lctminute().read().bits(),
+            seconds: r.lctsecond().read().bits(),
+        };
+
+        // The counters can change between reading them, retry until two reads agree.
+        let mut value = read();
+        loop {
+            let again = read();
+            if again == value {
+                return value;
+            }
+            value = again;
+        }
+    }
+
+    /// Returns true if the counters reached zero.
+    fn expired(&self) -> bool {
+        Self::regs().lctstat().read().bits() & LCTSTAT_EVST != 0
+    }
+
+    /// The current time in seconds.
+    #[must_use]
+    pub fn seconds(&self) -> u32 {
+        let remaining = if self.expired() {
+            0
+        } else {
+            self.counters().as_seconds()
+        };
+        self.base.get().wrapping_add(self.load.get() - remaining)
+    }
+
+    /// Set the current time in seconds, cancelling the alarm.
+    pub fn set_seconds(&mut self, seconds: u32) {
+        self.load(seconds, LctTime::MAX);
+    }
+
+    /// The current date and time, as set with [Lct::set_datetime].
+    #[must_use]
+    pub fn now(&self) -> DateTime {
+        DateTime::from_unix(self.seconds())
+    }
+
+    /// Set the current date and time, cancelling the alarm.
+    pub fn set_datetime(&mut self, datetime: DateTime) {
+        self.set_seconds(datetime.to_unix());
+    }
+
+    /// Set the alarm to fire at `at`, replacing an earlier alarm.
+    ///
+    /// See [Lct::set_alarm] for the range of the alarm.
+    pub fn set_alarm_at(&mut self, at: DateTime) -> bool {
+        self.set_alarm(at.to_unix())
+    }
+
+    /// Set the alarm to fire at `at` seconds, replacing an earlier alarm.
+    ///
+    /// An alarm further away than [LctTime::MAX] cannot be set and returns `false`. An alarm that is not in the future
+    /// fires immediately. The counters are reloaded, so the clock drops the fraction of the current second.
+    pub fn set_alarm(&mut self, at: u32) -> bool {
+        let now = self.seconds();
+        let Some(value) = LctTime::from_seconds(at.saturating_sub(now)) else {
+            return false;
+        };
+
+        self.alarm.clear_pending();
+        self.load(now, value);
+        true
+    }
+
+    /// Set the alarm to fire after `seconds`, replacing an earlier alarm.
+    pub fn set_alarm_in(&mut self, seconds: LctTime) {
+        let now = self.seconds();
+        self.alarm.clear_pending();
+        self.load(now, seconds);
+    }
+
+    /// Cancel the alarm.
+    ///
+    /// Like setting an alarm, this reloads the counters and drops the fraction of the current second.
+    pub fn cancel_alarm(&mut self) {
+        let now = self.seconds();
+        self.load(now, LctTime::MAX);
+    }
+
+    /// Let the alarm wake the chip from a low power state.
+    pub fn enable_wake(&mut self) {
+        self.alarm.enable(Level::High);
+    }
+
+    /// Wait for the alarm, and keep the clock running after it fired.
+    ///
+    /// Returns immediately if the alarm already fired.
+    pub async fn wait_for_alarm(&mut self) {
+        // The event stays asserted until its status is cleared, so awaiting the level cannot miss it.
+        if !self.expired() {
+            self.alarm.wait_for(Level::High).await;
+        }
+
+        let now = self.seconds();
+        self.load(now, LctTime::MAX);
+    }
+}
@@ -4,29 +4,66 @@
 
 pub mod cancellation;
 pub mod cdcg;
+pub mod delay;
 pub mod gpio;
 pub mod gpio_miwu;
+pub mod host_power;
 pub mod i2c;
+pub mod lct;
+#[cfg(feature = "low-power")]
+pub mod low_power;
 pub mod miwu;
+#[cfg(feature = "panic-report")]
+pub mod panic_report;
+pub mod pmc;
+#[cfg(feature = "debug")]
+pub mod profiling;
+pub mod psl;
+pub mod pwm;
 pub mod spip;
+pub mod supply;
+pub mod tach;
 pub mod timer;
+pub mod timestamp;
 pub mod uart;
+pub mod watchdog;
 
-#[cfg(any(
-    feature = "time-driver-mft16-1",
-    feature = "time-driver-mft16-2",
-    feature = "time-driver-mft16-3"
-))]
+#[cfg(feature = "_time-driver")]
 mod time_driver;
 
 pub use npcx490m_pac as pac;
 
+/// `JEN_CTL1.JEN_EN`: disable the JTAG/SWD debug interface, `0110b`. The interface is enabled by `1001b`.
+const JEN_EN_DISABLED: u8 = 0b0110;
+
 /// Configuration for the HAL
+///
+/// Options that belong to a pin are not part of the config, as the pins are only handed out after init. The voltage
+/// level of a pin is chosen by the driver it is given to, like [gpio::Input::new_lowvoltage], and the `LFCLK` is
+/// routed to its output pin by [cdcg::LfClockOut].
 #[non_exhaustive]
 #[derive(Debug, Clone, Default)]
 pub struct Config {
     /// Clock configuration
     pub cdcg: cdcg::Config,
+    /// Whether the JTAG/SWD debug interface stays enabled
+    pub debug_interface: DebugInterface,
+}
+
+/// Availability of the JTAG/SWD debug interface after init.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DebugInterface {
+    /// Keep the debug interface as configured by the straps and the booter
+    #[default]
+    Enabled,
+    /// Keep the debug interface enabled, and never let the chip sleep deeper than [pmc::SleepMode::Idle].
+    ///
+    /// In sleep and deep sleep the core clock stops, which makes an attached probe lose its connection to the core.
+    /// Use this while debugging low-power firmware, at the cost of a much higher sleep current.
+    EnabledInSleep,
+    /// Disable the debug interface, for example on production boards, or to use its pins as GPIO
+    Disabled,
 }
 
 embassy_hal_internal::peripherals!(
@@ -194,6 +231,7 @@ embassy_hal_internal::peripherals!(
     MIWU0_40,
     MIWU0_41,
     MIWU0_42,
+    #[cfg(not(feature = "time-driver-itim"))]
     MIWU0_43,
     MIWU0_44,
     MIWU0_45,
@@ -372,12 +410,29 @@ embassy_hal_internal::peripherals!(
     CR_UART3,
     CR_UART4,
     SPIP,
+    TWD,
+    LCT,
+    PWM0,
+    PWM1,
+    PWM2,
+    PWM3,
+    PWM4,
+    PWM5,
+    PWM6,
+    PWM7,
     #[cfg(not(feature = "time-driver-mft16-1"))]
     MFT16_1,
     #[cfg(not(feature = "time-driver-mft16-2"))]
     MFT16_2,
     #[cfg(not(feature = "time-driver-mft16-3"))]
-    MFT16_3
+    MFT16_3,
+    #[cfg(not(feature = "time-driver-itim"))]
+    ITIM32_1,
+    ITIM32_2,
+    ITIM32_3,
+    ITIM32_4,
+    ITIM32_5,
+    ITIM32_6
 );
 
 /// Macro to bind interrupts to handlers.
@@ -449,33 +504,82 @@ macro_rules! bind_interrupts {
 // marked non-exhaustive to ensure the user can't create one from nothing
 #[non_exhaustive]
 #[derive(Debug, Copy, Clone)]
-pub struct Lpc {}
+pub struct Lpc {
+    clock_report: cdcg::ClockReport,
+}
+
+impl Lpc {
+    /// The state of the clocks right after they were brought up by the init function.
+    #[must_use]
+    pub fn clock_report(&self) -> cdcg::ClockReport {
+        self.clock_report
+    }
+}
 
 /// Marker struct for ESpi mode
 // marked non-exhaustive to ensure the user can't create one from nothing
 #[non_exhaustive]
 #[derive(Debug, Copy, Clone)]
-pub struct ESpi {}
+pub struct ESpi {
+    clock_report: cdcg::ClockReport,
+}
+
+impl ESpi {
+    /// The state of the clocks right after they were brought up by the init function.
+    #[must_use]
+    pub fn clock_report(&self) -> cdcg::ClockReport {
+        self.clock_report
+    }
+}
 
-fn init(config: Config) -> Peripherals {
+/// Marker struct for standalone mode, without a host interface
+// marked non-exhaustive to ensure the user can't create one from nothing
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone)]
+pub struct Standalone {
+    clock_report: cdcg::ClockReport,
+}
+
+impl Standalone {
+    /// The state of the clocks right after they were brought up by the init function.
+    #[must_use]
+    pub fn clock_report(&self) -> cdcg::ClockReport {
+        self.clock_report
+    }
+}
+
+fn init(config: Config) -> (Peripherals, cdcg::ClockReport) {
     cdcg::init_clocks(config.cdcg);
+    timestamp::init();
+    delay::calibrate();
+    let clock_report = cdcg::clock_report();
+    #[cfg(feature = "defmt")]
+    defmt::info!("{}", clock_report);
+
+    if config.debug_interface == DebugInterface::Disabled {
+        // We still have control over all peripherals, so this is safe to do outside a critical section
+        unsafe { crate::pac::Sysconfig::steal() }
+            .jen_ctl1()
+            .modify(|_, w| w.jen_en().bits(JEN_EN_DISABLED));
+    }
+
+    if config.debug_interface == DebugInterface::EnabledInSleep {
+        // Keep the core clock running for the lifetime of the program.
+        core::mem::forget(pmc::PowerDomainToken::<pmc::Core>::new());
+    }
 
-    #[cfg(any(
-        feature = "time-driver-mft16-1",
-        feature = "time-driver-mft16-2",
-        feature = "time-driver-mft16-3"
-    ))]
+    #[cfg(feature = "_time-driver")]
     critical_section::with(|cs| {
         time_driver::init(cs);
     });
 
-    Peripherals::take()
+    (Peripherals::take(), clock_report)
 }
 
 /// Inititalize the chip and HAL in `LPC` mode.
 /// After this the chip will need a full power-cycle to initialize into the `eSPI` mode.
 pub fn init_lpc(config: Config) -> (Peripherals, Lpc) {
-    let per = init(config);
+    let (per, clock_report) = init(config);
 
     // We still have control over all peripherals, so this is safe to do outside a critical section
     unsafe { crate::pac::Sysconfig::steal() }.devcnt().modify(|r, w| {
@@ -483,13 +587,13 @@ pub fn init_lpc(config: Config) -> (Peripherals, Lpc) {
         unsafe { w.hif_typ_sel().bits(1) }
     });
 
-    (per, Lpc {})
+    (per, Lpc { clock_report })
 }
 
 /// Inititalize the chip and HAL in `eSPI` mode.
 /// After this the chip will need a full power-cycle to initialize into the `LPC` mode.
 pub fn init_espi(config: Config) -> (Peripherals, ESpi) {
-    let per = init(config);
+    let (per, clock_report) = init(config);
 
     // We still have control over all peripherals, so this is safe to do outside a critical section
     unsafe { crate::pac::Sysconfig::steal() }.devcnt().modify(|r, w| {
@@ -497,7 +601,32 @@ pub fn init_espi(config: Config) -> (Peripherals, ESpi) {
         unsafe { w.hif_typ_sel().bits(2) }
     });
 
-    (per, ESpi {})
+    (per, ESpi { clock_report })
+}
+
+/// Error returned by [init_standalone] when a host interface was already selected, for example by a bootloader.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HostInterfaceSelected;
+
+/// Inititalize the chip and HAL without a host interface, for hostless designs.
+/// The host interface is left unselected. As with the other init functions, the chip and HAL can only be initialized
+/// once, so it can not be initialized into `LPC` or `eSPI` mode afterwards.
+///
+/// Returns [HostInterfaceSelected], without initializing anything, if a host interface was already selected.
+pub fn init_standalone(config: Config) -> Result<(Peripherals, Standalone), HostInterfaceSelected> {
+    // We still have control over all peripherals, so this is safe to do outside a critical section
+    let hif_typ_sel = unsafe { crate::pac::Sysconfig::steal() }
+        .devcnt()
+        .read()
+        .hif_typ_sel()
+        .bits();
+    if hif_typ_sel != 0 {
+        return Err(HostInterfaceSelected);
+    }
+
+    let (per, clock_report) = init(config);
+    Ok((per, Standalone { clock_report }))
 }
 
 pub use interrupt_mod::*;
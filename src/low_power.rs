@@ -0,0 +1,74 @@
+//! Low-power executor
+//!
+//! An executor that puts the chip in the deepest PMC sleep mode that does not stop a clock still in use, each time
+//! it runs out of work. Use it in place of the default thread-mode executor:
+//!
+//! ```rust,ignore
+//! #[embassy_executor::main(executor = "embassy_npcx::low_power::Executor")]
+//! async fn main(spawner: embassy_executor::Spawner) {
+//!     let p = embassy_npcx::init_espi(Default::default());
+//!     // ...
+//! }
+//! ```
+//!
+//! In sleep and deep sleep only the inputs enabled in the MIWU wake the chip, see [crate::pmc]. The UART, I2C, SPIP
+//! and MFT16 capture drivers keep their bus running while an operation is pending, so awaiting them limits the sleep
+//! mode like a [PowerDomainToken](crate::pmc::PowerDomainToken) does.
+
+use core::marker::PhantomData;
+
+use embassy_executor::{raw, Spawner};
+
+use crate::pmc;
+
+/// The context the `arch-cortex-m` pender of embassy-executor recognizes as the thread-mode executor.
+const THREAD_PENDER: usize = usize::MAX;
+
+/// Thread-mode executor that sleeps in the deepest allowed PMC sleep mode when idle.
+pub struct Executor {
+    inner: raw::Executor,
+    not_send: PhantomData<*mut ()>,
+}
+
+impl Executor {
+    /// Create a new executor.
+    pub fn new() -> Self {
+        Self {
+            inner: raw::Executor::new(THREAD_PENDER as *mut ()),
+            not_send: PhantomData,
+        }
+    }
+
+    /// Run the executor.
+    ///
+    /// The `init` closure is called with a [Spawner] that spawns tasks on this executor. Use it to spawn the initial
+    /// task(s). After `init` returns, the executor starts running the tasks.
+    ///
+    /// This function never returns.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        // Let interrupts that become pending while they are masked wake the WFE.
+        // Safety: only the SEVONPEND bit is changed, which nothing else in the HAL uses.
+        unsafe { cortex_m::Peripherals::steal().SCB.set_sevonpend() };
+
+        init(self.inner.spawner());
+
+        loop {
+            // Safety: the executor is only polled from this thread.
+            unsafe { self.inner.poll() };
+
+            // Note(cs): like `pmc::enter`, the interrupt that wakes the chip is only taken after the PMC is restored
+            // and the wake reason is recorded, before its handler clears the pending MIWU input.
+            critical_section::with(|_cs| {
+                // The pender signals a woken task with a SEV, which makes the WFE return immediately instead of
+                // sleeping. The PMC enters its sleep mode for WFE like it does for WFI.
+                pmc::sleep_with(pmc::deepest_allowed_mode(), cortex_m::asm::wfe);
+            });
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -6,16 +6,40 @@
 //!
 //! This means that if the interrupt is run, all pending WakeUpInputs are disabled, and need to be re-enabled if used for
 //! exiting a low power state.
+//! [crate::pmc::set_rearm_wake_inputs] re-enables them automatically before every sleep.
+//!
+//! This can be changed per input with [WakeUp::enable_with] and [OnTrigger]. For example inputs in sticky mode, enabled
+//! with [WakeUp::enable_counting] or [WakeUp::edges], for which the interrupt counts the edge, clears the `pending` bit
+//! and leaves the input enabled.
+//!
+//! ## Wakers
+//! By default every WakeUpInput has its own waker. With the `miwu-group-waiters` feature the WakeUpInputs of a group
+//! instead share an intrusive wait queue, in which every awaiting task links its own waiter. This costs one wait queue
+//! per group instead of one waker per WakeUpInput, at the cost of waking all tasks awaiting the same group on every
+//! interrupt of that group.
 //!
 //! # Use cases
 //! * View [AwaitableInput](crate::gpio_miwu::AwaitableInput) to configure an pin interrupt.
-//! * These WakeUpInputs can be consumed by the HAL implementation for specific peripherals unrelated to GPIO pins.
+//! * These WakeUpInputs can be consumed by the HAL implementation for specific peripherals unrelated to GPIO pins,
+//!   see [claim_for_peripheral].
+
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+#[cfg(not(feature = "miwu-group-waiters"))]
 use embassy_sync::waitqueue::AtomicWaker;
+#[cfg(feature = "miwu-group-waiters")]
+use maitake_sync::WaitQueue;
 use paste::paste;
 
+use crate::pmc::SleepMode;
+
+/// `ITCTS` of the ITIM32 timers: clock the timer from the `LFCLK` instead of the `APB2_CLK`
+const ITCTS_CKSEL: u8 = 1 << 4;
+
 /// Signal level used as signalling condition.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Level {
     /// A low signal
     Low,
@@ -24,16 +48,20 @@ pub enum Level {
 }
 
 /// Signal edge used as signalling condition.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Edge {
     /// Both falling and rising edges
     Any,
-    /// The transition from low to high
-    Falling,
     /// The transition from high to low
+    Falling,
+    /// The transition from low to high
     Rising,
 }
 
 /// Signalling condition on which the [WakeUp] input is triggered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Mode {
     /// Trigger when the signal is at this level
     Level(Level),
@@ -53,13 +81,169 @@ impl From<Edge> for Mode {
     }
 }
 
-mod sealed {
-    use embassy_sync::waitqueue::AtomicWaker;
+/// What the interrupt handler does with a [WakeUpInput] once its signalling condition has been triggered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OnTrigger {
+    /// Disable the input, leaving the `pending` bit set for the awaiting task to observe. Used by [WakeUp::wait_for].
+    #[default]
+    Disable,
+    /// Count the event, acknowledge it and leave the input enabled (sticky mode). Used by [WakeUp::edges].
+    ///
+    /// Note that in level mode the input triggers again immediately for as long as the level is present.
+    KeepEnabled,
+    /// Count the event, acknowledge it and mask the input until it is unmasked with [WakeUp::unmask].
+    ///
+    /// This mirrors a typical GPIO interrupt controller, where an active-low interrupt line is masked until the
+    /// device has been serviced.
+    Mask,
+}
+
+impl OnTrigger {
+    const fn to_bits(self) -> u8 {
+        match self {
+            OnTrigger::Disable => 0,
+            OnTrigger::KeepEnabled => 1,
+            OnTrigger::Mask => 2,
+        }
+    }
+
+    const fn from_bits(bits: u8) -> Self {
+        match bits {
+            1 => OnTrigger::KeepEnabled,
+            2 => OnTrigger::Mask,
+            _ => OnTrigger::Disable,
+        }
+    }
+}
+
+/// State of a single [WakeUpInput], shared with its interrupt handler.
+pub(crate) struct ChannelState {
+    on_trigger: AtomicU8,
+    count: AtomicU32,
+    /// Time of the last counted edge, in embassy-time ticks
+    #[cfg(feature = "time")]
+    last_event: critical_section::Mutex<core::cell::Cell<Option<u64>>>,
+    /// Times of the counted edges not yet consumed by an [EdgeStream], only recorded while one exists
+    #[cfg(feature = "miwu-edge-stream")]
+    timestamps: critical_section::Mutex<core::cell::RefCell<Option<TimestampRing>>>,
+}
+
+impl ChannelState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            on_trigger: AtomicU8::new(OnTrigger::Disable.to_bits()),
+            count: AtomicU32::new(0),
+            #[cfg(feature = "time")]
+            last_event: critical_section::Mutex::new(core::cell::Cell::new(None)),
+            #[cfg(feature = "miwu-edge-stream")]
+            timestamps: critical_section::Mutex::new(core::cell::RefCell::new(None)),
+        }
+    }
+
+    fn record_event(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "time")]
+        critical_section::with(|cs| {
+            let now = embassy_time::Instant::now().as_ticks();
+            self.last_event.borrow(cs).set(Some(now));
+            #[cfg(feature = "miwu-edge-stream")]
+            if let Some(timestamps) = self.timestamps.borrow_ref_mut(cs).as_mut() {
+                timestamps.push(now);
+            }
+        });
+    }
+}
+
+/// The number of edge timestamps buffered per WakeUpInput for an [EdgeStream]
+#[cfg(feature = "miwu-edge-stream")]
+pub const EDGE_STREAM_CAPACITY: usize = 8;
+
+/// Ring buffer of edge timestamps, in embassy-time ticks.
+#[cfg(feature = "miwu-edge-stream")]
+struct TimestampRing {
+    ticks: [u64; EDGE_STREAM_CAPACITY],
+    start: usize,
+    len: usize,
+    dropped: u32,
+}
+
+#[cfg(feature = "miwu-edge-stream")]
+impl TimestampRing {
+    const fn new() -> Self {
+        Self {
+            ticks: [0; EDGE_STREAM_CAPACITY],
+            start: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, ticks: u64) {
+        if self.len == EDGE_STREAM_CAPACITY {
+            self.dropped = self.dropped.saturating_add(1);
+        } else {
+            self.ticks[(self.start + self.len) % EDGE_STREAM_CAPACITY] = ticks;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let ticks = self.ticks[self.start];
+        self.start = (self.start + 1) % EDGE_STREAM_CAPACITY;
+        self.len -= 1;
+        Some(ticks)
+    }
+}
+
+/// The waker of a single WakeUpInput.
+#[cfg(not(feature = "miwu-group-waiters"))]
+type ChannelWaker = AtomicWaker;
+
+/// The wait queue shared by all WakeUpInputs of a group.
+#[cfg(feature = "miwu-group-waiters")]
+type ChannelWaker = WaitQueue;
+
+fn wake(waker: &ChannelWaker) {
+    #[cfg(not(feature = "miwu-group-waiters"))]
+    waker.wake();
+    #[cfg(feature = "miwu-group-waiters")]
+    waker.wake_all();
+}
+
+/// Wait until `f` returns a value, re-evaluating it every time `waker` is woken.
+#[cfg(not(feature = "miwu-group-waiters"))]
+async fn wait_until<R>(waker: &ChannelWaker, mut f: impl FnMut() -> Option<R>) -> R {
+    core::future::poll_fn(|cx| {
+        waker.register(cx.waker());
 
+        match f() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// Wait until `f` returns a value, re-evaluating it every time `waker` is woken.
+#[cfg(feature = "miwu-group-waiters")]
+async fn wait_until<R>(waker: &ChannelWaker, f: impl FnMut() -> Option<R>) -> R {
+    // The queue is never closed, so waiting can not fail.
+    waker.wait_for_value(f).await.unwrap()
+}
+
+mod sealed {
     pub(crate) trait SealedWakeUpInput {
-        fn waker() -> &'static AtomicWaker;
+        fn waker() -> &'static super::ChannelWaker;
+        fn state() -> &'static super::ChannelState;
 
         fn port() -> &'static crate::pac::miwu0::RegisterBlock;
+        fn miwu() -> u8;
         fn group() -> u8;
         fn subgroup() -> u8;
     }
@@ -93,8 +277,95 @@ impl<'d> WakeUp<'d> {
         Self { wui: wui.map_into() }
     }
 
+    /// Drive a channel without claiming its peripheral, for drivers that own the GPIO pin wired to it.
+    ///
+    /// # Safety
+    /// The interrupt handler of the channel must be bound before it is enabled, and no other [WakeUp] may be driving
+    /// the same channel while this one is in use.
+    pub(crate) unsafe fn steal(wui: AnyWakeUpInput) -> WakeUp<'static> {
+        WakeUp {
+            wui: PeripheralRef::new(wui),
+        }
+    }
+
     /// Enable the [WakeUpInput] with a specific signalling condition [Mode], enabling triggering the WakeUp signal and/or interrupt.
     pub fn enable(&mut self, mode: impl Into<Mode>) {
+        self.configure(mode.into(), OnTrigger::Disable);
+    }
+
+    /// Enable the [WakeUpInput] with a specific signalling condition [Mode], and choose what the interrupt handler
+    /// does with the input once it has been triggered.
+    ///
+    /// With [OnTrigger::KeepEnabled] and [OnTrigger::Mask] the events are counted, and can be awaited with
+    /// [WakeUp::wait_for_event].
+    pub fn enable_with(&mut self, mode: impl Into<Mode>, on_trigger: OnTrigger) {
+        self.configure(mode.into(), on_trigger);
+    }
+
+    /// Re-enable an input that was masked by the interrupt handler, see [OnTrigger::Mask].
+    ///
+    /// The signalling condition is kept. Any trigger that happened while masked is discarded.
+    pub fn unmask(&mut self) {
+        let wui = self.wui.reborrow();
+        // Note(cs): WakeUpInputs can share MIWU and group, which use the same registers.
+        critical_section::with(|_cs| {
+            wui.port
+                .wkpcln(wui.group as usize)
+                .write(|w| w.input(wui.subgroup).clear());
+            wui.port
+                .wkenn(wui.group as usize)
+                .modify(|_, w| w.input(wui.subgroup).enabled());
+        });
+    }
+
+    /// Await events counted by the interrupt handler, returning the number of events since the previous call.
+    ///
+    /// Only useful after enabling the input with [OnTrigger::KeepEnabled] or [OnTrigger::Mask].
+    /// Returns immediately if events were counted since the previous call.
+    pub async fn wait_for_event(&mut self) -> u32 {
+        let state = self.wui.state;
+        wait_until(self.wui.waker, || {
+            let count = state.count.swap(0, Ordering::Relaxed);
+            (count > 0).then_some(count)
+        })
+        .await
+    }
+
+    /// Enable the [WakeUpInput] in sticky mode, counting every [Edge] in the interrupt handler.
+    ///
+    /// Unlike [WakeUp::enable], the input stays enabled after it has been triggered: the interrupt handler only
+    /// counts the edge, acknowledges it and wakes the waiting task. See [WakeUp::edges] to await the edges.
+    /// The count is not reset, use [WakeUp::reset_count] for that.
+    pub fn enable_counting(&mut self, edge: Edge) {
+        self.configure(Mode::Edge(edge), OnTrigger::KeepEnabled);
+    }
+
+    /// The number of edges counted since the last reset
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.wui.state.count.load(Ordering::Relaxed)
+    }
+
+    /// Reset the number of counted edges to zero
+    pub fn reset_count(&mut self) {
+        self.wui.state.count.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the number of counted edges and resets it to zero, without losing any edges in between
+    pub fn take_count(&mut self) -> u32 {
+        self.wui.state.count.swap(0, Ordering::Relaxed)
+    }
+
+    /// The time at which the interrupt handler counted the most recent edge, if any edge has been counted yet.
+    ///
+    /// The timestamp is taken in the interrupt handler, so it does not include the latency of the awaiting task.
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub fn last_event_time(&self) -> Option<embassy_time::Instant> {
+        critical_section::with(|cs| self.wui.state.last_event.borrow(cs).get()).map(embassy_time::Instant::from_ticks)
+    }
+
+    fn configure(&mut self, mode: Mode, on_trigger: OnTrigger) {
         let wui = self.wui.reborrow();
 
         let port = wui.port;
@@ -102,7 +373,7 @@ impl<'d> WakeUp<'d> {
 
         use crate::pac::miwu0::*;
         let (wkmod, wkaedgn, wkedgn);
-        match mode.into() {
+        match mode {
             Mode::Level(level) => {
                 wkmod = wkmodn::InputMode::Level;
                 wkaedgn = None;
@@ -137,8 +408,12 @@ impl<'d> WakeUp<'d> {
 
             port.wkinenn(group).modify(|_, w| w.input(wui.subgroup).enabled());
             port.wkpcln(group).write(|w| w.input(wui.subgroup).clear());
+
+            wui.state.on_trigger.store(on_trigger.to_bits(), Ordering::Relaxed);
             port.wkenn(group).modify(|_, w| w.input(wui.subgroup).enabled());
         });
+
+        ARMED[miwu_index(port)][group].fetch_or(1 << wui.subgroup, Ordering::Relaxed);
     }
 
     /// Disable the [WakeUpInput], forbidding the WakeUp signal and/or interrupt.
@@ -149,7 +424,27 @@ impl<'d> WakeUp<'d> {
             wui.port
                 .wkenn(wui.group as usize)
                 .modify(|_, w| w.input(wui.subgroup).disabled());
+            wui.state
+                .on_trigger
+                .store(OnTrigger::Disable.to_bits(), Ordering::Relaxed);
         });
+
+        ARMED[miwu_index(wui.port)][wui.group as usize].fetch_and(!(1 << wui.subgroup), Ordering::Relaxed);
+    }
+
+    /// Set the NVIC priority of the `WKINTx_n` interrupt backing this channel.
+    ///
+    /// The interrupt is shared by all WakeUpInputs of the same group, so this affects all of them.
+    pub fn set_priority(&mut self, priority: crate::interrupt::Priority) {
+        use crate::interrupt::InterruptExt;
+        self.wui.irq.set_priority(priority);
+    }
+
+    /// Returns the NVIC priority of the `WKINTx_n` interrupt backing this channel
+    #[must_use]
+    pub fn priority(&self) -> crate::interrupt::Priority {
+        use crate::interrupt::InterruptExt;
+        self.wui.irq.get_priority()
     }
 
     /// Make the signal no longer pending due to a previous trigger
@@ -161,6 +456,28 @@ impl<'d> WakeUp<'d> {
             .write(|w| w.input(wui.subgroup).clear());
     }
 
+    /// Indicates whether the input is enabled, and thus able to trigger the WakeUp signal and/or interrupt.
+    ///
+    /// Inputs are disabled by the interrupt handler once triggered, depending on [OnTrigger].
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        let wui = &self.wui;
+        wui.port
+            .wkenn(wui.group as usize)
+            .read()
+            .input(wui.subgroup)
+            .is_enabled()
+    }
+
+    /// Returns whether the signal was pending due to a previous trigger, and makes it no longer pending.
+    pub fn take_pending(&mut self) -> bool {
+        let pending = self.is_pending();
+        if pending {
+            self.clear_pending();
+        }
+        pending
+    }
+
     /// Indicates whether the input signal, regardless of signalling condition, is high or not.
     pub fn is_high(&self) -> bool {
         let wui = &self.wui;
@@ -178,6 +495,324 @@ impl<'d> WakeUp<'d> {
     }
 }
 
+/// Returns the pending bits of all 8 WakeUpInputs of a group, without clearing them.
+///
+/// Bit `n` corresponds to WakeUpInput `MIWU{miwu_n}_{group}{n}`. The group is 1-indexed, like the peripheral names.
+/// Useful for polling firmware to find out which inputs to drain with [WakeUp::take_pending].
+///
+/// Panics if `miwu_n` is not 0, 1 or 2, or `group` is not 1 through 8.
+#[must_use]
+pub fn group_pending(miwu_n: u8, group: u8) -> u8 {
+    assert!((1..=8).contains(&group), "MIWU groups are numbered 1 through 8");
+
+    miwu_regs(miwu_n).wkpndn(group as usize - 1).read().bits()
+}
+
+/// The inputs enabled by their [WakeUp] and not disabled by it since, indexed by MIWU and 0-indexed group.
+///
+/// The interrupt handler can disable inputs without clearing these bits, see [rearm].
+static ARMED: [[AtomicU8; 8]; 3] = [const { [const { AtomicU8::new(0) }; 8] }; 3];
+
+fn miwu_index(port: &'static crate::pac::miwu0::RegisterBlock) -> usize {
+    (0..3)
+        .find(|n| core::ptr::eq(miwu_regs(*n as u8), port))
+        .expect("There are only 3 MIWUs")
+}
+
+/// Re-enable the inputs that were disabled by the interrupt handler, but not by their [WakeUp].
+///
+/// Inputs with their pending bit still set are left disabled, as their event has not been handled yet.
+pub(crate) fn rearm() {
+    for (miwu, groups) in ARMED.iter().enumerate() {
+        let port = miwu_regs(miwu as u8);
+        for (group, armed) in groups.iter().enumerate() {
+            // Note(cs): WakeUpInputs can share MIWU and group, which use the same registers.
+            critical_section::with(|_cs| {
+                let rearm = armed.load(Ordering::Relaxed) & !port.wkpndn(group).read().bits();
+                if rearm != 0 {
+                    port.wkenn(group).modify(|r, w| unsafe { w.bits(r.bits() | rearm) });
+                }
+            });
+        }
+    }
+}
+
+/// The pending bits of all groups of all MIWUs, indexed by MIWU and 0-indexed group.
+pub(crate) fn pending_snapshot() -> [[u8; 8]; 3] {
+    core::array::from_fn(|miwu| {
+        let port = miwu_regs(miwu as u8);
+        core::array::from_fn(|group| port.wkpndn(group).read().bits())
+    })
+}
+
+#[cfg(not(test))]
+fn miwu_regs(miwu_n: u8) -> &'static crate::pac::miwu0::RegisterBlock {
+    let ptr = match miwu_n {
+        0 => crate::pac::Miwu0::ptr(),
+        1 => crate::pac::Miwu1::ptr(),
+        2 => crate::pac::Miwu2::ptr(),
+        _ => panic!("There are only 3 MIWUs"),
+    };
+
+    // Safety:
+    // the pac ptr functions return pointers to memory that is used for registers for the 'static lifetime
+    // and the created reference is shared.
+    unsafe { &*ptr }
+}
+
+#[cfg(test)]
+use tests::miwu_regs;
+
+#[cfg(feature = "debug")]
+struct GroupCounters {
+    delivered: AtomicU32,
+    spurious: AtomicU32,
+}
+
+#[cfg(feature = "debug")]
+impl GroupCounters {
+    const fn new() -> Self {
+        Self {
+            delivered: AtomicU32::new(0),
+            spurious: AtomicU32::new(0),
+        }
+    }
+}
+
+#[cfg(feature = "debug")]
+static IRQ_STATS: [[GroupCounters; 8]; 3] = [const { [const { GroupCounters::new() }; 8] }; 3];
+
+/// Interrupt counters of a group, see [irq_stats].
+#[cfg(feature = "debug")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrqStats {
+    /// The number of times a bound [InterruptHandler] found its WakeUpInput pending
+    pub delivered: u32,
+    /// The number of times a bound [InterruptHandler] ran while no WakeUpInput of the group was pending
+    ///
+    /// Every handler bound to the group counts such an interrupt.
+    pub spurious: u32,
+}
+
+/// Returns the interrupt counters of a group since boot or the last [reset_irq_stats].
+///
+/// A `delivered` count that keeps climbing quickly points to an interrupt storm, for example a channel in level mode
+/// with [OnTrigger::KeepEnabled] whose signal stays at that level. The group is 1-indexed, like the peripheral names.
+///
+/// Panics if `miwu_n` is not 0, 1 or 2, or `group` is not 1 through 8.
+#[cfg(feature = "debug")]
+#[must_use]
+pub fn irq_stats(miwu_n: u8, group: u8) -> IrqStats {
+    assert!((1..=8).contains(&group), "MIWU groups are numbered 1 through 8");
+    assert!(miwu_n < 3, "There are only 3 MIWUs");
+
+    let counters = &IRQ_STATS[miwu_n as usize][group as usize - 1];
+    IrqStats {
+        delivered: counters.delivered.load(Ordering::Relaxed),
+        spurious: counters.spurious.load(Ordering::Relaxed),
+    }
+}
+
+/// Resets the interrupt counters of all groups to zero.
+#[cfg(feature = "debug")]
+pub fn reset_irq_stats() {
+    for counters in IRQ_STATS.iter().flatten() {
+        counters.delivered.store(0, Ordering::Relaxed);
+        counters.spurious.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of the state of a single WakeUpInput, see [dump_state].
+#[cfg(feature = "debug")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelInfo {
+    /// The MIWU the input belongs to
+    pub miwu: u8,
+    /// The group of the input, 1-indexed like the peripheral names
+    pub group: u8,
+    /// The index of the input within its group
+    pub input: u8,
+    /// Whether the input is enabled to trigger the WakeUp signal and/or interrupt
+    pub enabled: bool,
+    /// The configured signalling condition
+    pub mode: Mode,
+    /// Whether the signalling condition has been triggered
+    pub pending: bool,
+    /// Whether the input signal is currently high
+    pub high: bool,
+}
+
+/// Iterate over the state of all WakeUpInputs, for diagnosing spurious wake-ups.
+///
+/// The registers of each input are read when the iterator reaches it, and are not modified.
+/// The WakeUpInput `MIWU{miwu}_{group}{input}` corresponds to a record.
+#[cfg(feature = "debug")]
+pub fn dump_state() -> impl Iterator<Item = ChannelInfo> {
+    use crate::pac::miwu0::*;
+
+    (0..3u8)
+        .flat_map(|miwu| (1..=8u8).map(move |group| (miwu, group)))
+        .flat_map(|(miwu, group)| (0..8u8).map(move |input| (miwu, group, input)))
+        .map(|(miwu, group, input)| {
+            let port = miwu_regs(miwu);
+            let n = group as usize - 1;
+
+            let edge = port.wkedgn(n).read().input(input).variant();
+            let mode = match port.wkmodn(n).read().input(input).variant() {
+                wkmodn::InputMode::Level => Mode::Level(match edge {
+                    wkedgn::Edge::LowFalling => Level::Low,
+                    wkedgn::Edge::HighRising => Level::High,
+                }),
+                wkmodn::InputMode::Edge => Mode::Edge(match port.wkaedgn(n).read().input(input).variant() {
+                    wkaedgn::AnyEdge::Any => Edge::Any,
+                    wkaedgn::AnyEdge::Edge => match edge {
+                        wkedgn::Edge::LowFalling => Edge::Falling,
+                        wkedgn::Edge::HighRising => Edge::Rising,
+                    },
+                }),
+            };
+
+            ChannelInfo {
+                miwu,
+                group,
+                input,
+                enabled: port.wkenn(n).read().input(input).is_enabled(),
+                mode,
+                pending: port.wkpndn(n).read().input(input).is_pending(),
+                high: port.wkstn(n).read().input(input).is_high(),
+            }
+        })
+}
+
+/// Claim the [WakeUpInput] dedicated to a peripheral, for use by the driver of that peripheral.
+///
+/// Some WakeUpInputs are not tied to a GPIO pin, but to an internal signal of a peripheral, like the UART receive line,
+/// an SMBus address match or an eSPI event. Drivers for these peripherals take the channel and its interrupt
+/// binding from their user in their constructor, next to the peripheral itself, and keep the returned [WakeUp] for
+/// as long as the driver lives:
+///
+/// ```rust,ignore
+/// pub fn new<W: WakeUpInput + 'd>(
+///     peri: impl Peripheral<P = T> + 'd,
+///     wui: impl Peripheral<P = W> + 'd,
+///     irqs: impl Binding<T::Interrupt, InterruptHandler<T>> + Binding<W::Interrupt, miwu::InterruptHandler<W>>,
+/// ) -> Self {
+///     let wake = miwu::claim_for_peripheral(wui, irqs);
+///     // ...
+/// }
+/// ```
+///
+/// The driver can then await the channel with [WakeUp::wait_for], or arm it with [WakeUp::enable] before a low power
+/// state. The channel starts out disabled.
+pub fn claim_for_peripheral<'d, P: WakeUpInput + 'd>(
+    wui: impl Peripheral<P = P> + 'd,
+    irqs: impl crate::interrupt::typelevel::Binding<P::Interrupt, InterruptHandler<P>>,
+) -> WakeUp<'d> {
+    let mut wake = WakeUp::new(wui, irqs);
+    wake.disable();
+    wake.clear_pending();
+    wake
+}
+
+/// WakeUpInputs signalled by internal events instead of a GPIO pin.
+///
+/// The channels are aliases of the MIWU peripherals they are wired to, so they can only be claimed once.
+/// [InternalWakeUp] awaits them with the signalling condition of the event.
+///
+/// The ITIM32_1 timeout on `MIWU0_43` is not listed: with the `time-driver-itim` feature the time driver uses it to
+/// wake up from deep sleep, and the peripheral is not available.
+#[allow(non_camel_case_types)]
+pub mod internal {
+    use embassy_hal_internal::Peripheral;
+
+    use super::{Edge, InterruptHandler, Mode, WakeUp, WakeUpInput};
+
+    /// Low-power Clock Timer (LCT) alarm
+    pub type LCT = crate::peripherals::MIWU0_47;
+    /// VCC1_RST# power event
+    pub type VCC1_RST = crate::peripherals::MIWU0_76;
+    /// TWD timer `T0` expired, the early warning of the [Watchdog](crate::watchdog::Watchdog)
+    pub type T0OUT = crate::peripherals::MIWU0_44;
+    /// Power Switch Logic input 1 (on GPIOD2)
+    pub type PSL_IN1 = crate::peripherals::MIWU0_72;
+    /// Power Switch Logic input 2 (on GPIO00)
+    pub type PSL_IN2 = crate::peripherals::MIWU1_10;
+    /// Power Switch Logic input 3 (on GPIO01)
+    pub type PSL_IN3 = crate::peripherals::MIWU1_11;
+    /// Power Switch Logic input 4 (on GPIO02)
+    pub type PSL_IN4 = crate::peripherals::MIWU1_12;
+
+    /// A [WakeUpInput] signalled by an internal event.
+    pub trait InternalWakeUpInput: WakeUpInput {
+        /// The signalling condition of the event
+        const MODE: Mode;
+    }
+
+    impl InternalWakeUpInput for LCT {
+        const MODE: Mode = Mode::Edge(Edge::Rising);
+    }
+    impl InternalWakeUpInput for VCC1_RST {
+        const MODE: Mode = Mode::Edge(Edge::Any);
+    }
+    impl InternalWakeUpInput for T0OUT {
+        const MODE: Mode = Mode::Edge(Edge::Rising);
+    }
+    impl InternalWakeUpInput for PSL_IN1 {
+        const MODE: Mode = Mode::Edge(Edge::Any);
+    }
+    impl InternalWakeUpInput for PSL_IN2 {
+        const MODE: Mode = Mode::Edge(Edge::Any);
+    }
+    impl InternalWakeUpInput for PSL_IN3 {
+        const MODE: Mode = Mode::Edge(Edge::Any);
+    }
+    impl InternalWakeUpInput for PSL_IN4 {
+        const MODE: Mode = Mode::Edge(Edge::Any);
+    }
+
+    /// Awaitable internal event, like "LCT alarm fired" or "power switch event".
+    pub struct InternalWakeUp<'d> {
+        wui: WakeUp<'d>,
+        mode: Mode,
+    }
+
+    impl<'d> InternalWakeUp<'d> {
+        /// Claim the [InternalWakeUpInput], leaving it disabled.
+        pub fn new<P: InternalWakeUpInput + 'd>(
+            wui: impl Peripheral<P = P> + 'd,
+            irqs: impl crate::interrupt::typelevel::Binding<P::Interrupt, InterruptHandler<P>>,
+        ) -> Self {
+            Self {
+                wui: super::claim_for_peripheral(wui, irqs),
+                mode: P::MODE,
+            }
+        }
+
+        /// Wait for the event to occur.
+        pub async fn wait(&mut self) {
+            self.wui.wait_for(self.mode).await;
+        }
+
+        /// Arm the event for waking up from a low power state.
+        pub fn enable_wake(&mut self) {
+            self.wui.enable(self.mode);
+        }
+
+        /// Returns true if the event occurred since it was armed.
+        #[must_use]
+        pub fn is_pending(&self) -> bool {
+            self.wui.is_pending()
+        }
+
+        /// Release the underlying [WakeUp] channel.
+        pub fn into_inner(self) -> WakeUp<'d> {
+            self.wui
+        }
+    }
+}
+
 /// Disables the [WakeUpInput] signalling condition when dropped.
 impl Drop for WakeUp<'_> {
     fn drop(&mut self) {
@@ -185,8 +820,10 @@ impl Drop for WakeUp<'_> {
     }
 }
 
-struct AnyWakeUpInput {
-    waker: &'static AtomicWaker,
+pub(crate) struct AnyWakeUpInput {
+    irq: crate::interrupt::Interrupt,
+    waker: &'static ChannelWaker,
+    state: &'static ChannelState,
     port: &'static crate::pac::miwu0::RegisterBlock,
     group: u8,
     subgroup: u8,
@@ -198,7 +835,9 @@ impl Peripheral for AnyWakeUpInput {
 
     unsafe fn clone_unchecked(&self) -> Self::P {
         AnyWakeUpInput {
+            irq: self.irq,
             waker: self.waker,
+            state: self.state,
             port: self.port,
             group: self.group,
             subgroup: self.subgroup,
@@ -206,25 +845,59 @@ impl Peripheral for AnyWakeUpInput {
     }
 }
 
-impl<T: WakeUpInput> From<T> for AnyWakeUpInput {
-    fn from(_value: T) -> Self {
+impl AnyWakeUpInput {
+    /// The channel `T`, without needing an instance of its peripheral.
+    pub(crate) fn of<T: WakeUpInput>() -> Self {
         Self {
+            irq: <T::Interrupt as crate::interrupt::typelevel::Interrupt>::IRQ,
             waker: T::waker(),
+            state: T::state(),
             port: T::port(),
             group: T::group(),
             subgroup: T::subgroup(),
         }
     }
+
+    /// The deepest [SleepMode] in which the signal on this input is still generated.
+    fn deepest_mode(&self) -> SleepMode {
+        // MIWU0_43 is the ITIM32_1 timeout, the counter is frozen in deep sleep unless it runs from the LFCLK.
+        if miwu_index(self.port) == 0 && self.group == 3 && self.subgroup == 3 {
+            // Safety: only reads the clock select of the ITIM32_1, which is owned by whoever uses its timeout.
+            let itim = unsafe { &*crate::pac::Itim32_1::PTR };
+            if itim.itcts32().read().bits() & ITCTS_CKSEL == 0 {
+                return SleepMode::Sleep;
+            }
+        }
+
+        SleepMode::DeepSleep
+    }
+}
+
+impl<T: WakeUpInput> From<T> for AnyWakeUpInput {
+    fn from(_value: T) -> Self {
+        Self::of::<T>()
+    }
 }
 
 macro_rules! impl_wake_up_input {
-    ($peripheral:ty, $miwu_n:expr, $group:expr, $subgroup:expr, $interrupt:ident) => {
+    ($peripheral:ty, $miwu_n:expr, $group:expr, $subgroup:expr, $interrupt:ident, $group_waiters:ident) => {
         impl sealed::SealedWakeUpInput for $peripheral {
-            fn waker() -> &'static AtomicWaker {
+            #[cfg(not(feature = "miwu-group-waiters"))]
+            fn waker() -> &'static ChannelWaker {
                 static WAKER: AtomicWaker = AtomicWaker::new();
                 &WAKER
             }
 
+            #[cfg(feature = "miwu-group-waiters")]
+            fn waker() -> &'static ChannelWaker {
+                &$group_waiters
+            }
+
+            fn state() -> &'static ChannelState {
+                static STATE: ChannelState = ChannelState::new();
+                &STATE
+            }
+
             fn port() -> &'static crate::pac::miwu0::RegisterBlock {
                 let ptr = paste! { crate::pac::[<Miwu $miwu_n>]::ptr() };
 
@@ -234,6 +907,9 @@ macro_rules! impl_wake_up_input {
                 unsafe { &*ptr }
             }
 
+            fn miwu() -> u8 {
+                $miwu_n
+            }
             fn group() -> u8 {
                 $group
             }
@@ -247,15 +923,45 @@ macro_rules! impl_wake_up_input {
     };
 }
 
-use core::future::Future;
 use core::marker::PhantomData;
-use core::task::{Context, Poll};
+#[cfg(not(feature = "miwu-group-waiters"))]
+use core::task::Poll;
 
 impl<'d> WakeUp<'d> {
     /// Configures a specific signalling condition [Mode] and awaits for it to be signalled.
     pub async fn wait_for(&mut self, mode: impl Into<Mode>) {
         self.enable(mode);
-        WakeUpInputFuture::<'_, 'd> { channel: self }.await
+        WaitGuard { channel: self }.wait_for_pending().await
+    }
+
+    /// Configures a specific signalling condition [Mode] and awaits for it to be signalled, for at most `timeout`.
+    ///
+    /// Whether it times out or not, the input is disabled and its pending bit is cleared when this returns.
+    #[cfg(feature = "time")]
+    pub async fn wait_for_timeout(
+        &mut self,
+        mode: impl Into<Mode>,
+        timeout: embassy_time::Duration,
+    ) -> Result<(), embassy_time::TimeoutError> {
+        embassy_time::with_timeout(timeout, self.wait_for(mode)).await
+    }
+
+    /// Configures a specific signalling condition [Mode] and awaits for it to be signalled, returning an
+    /// [EventToken] for the observed event.
+    ///
+    /// Unlike [WakeUp::wait_for], the pending bit is not cleared when this returns, but exactly once when the token is
+    /// acknowledged or dropped. Until then, the event stays visible to for example [group_pending] and [WakeSources].
+    /// When cancelled before the event is observed, the input is disabled and its pending bit cleared.
+    pub async fn wait_for_ack(&mut self, mode: impl Into<Mode>) -> EventToken<'_, 'd> {
+        self.enable(mode);
+
+        let guard = WaitGuard { channel: &mut *self };
+        guard.wait_for_pending().await;
+        // The event has been observed, from now on the token is responsible for clearing it.
+        core::mem::forget(guard);
+
+        self.disable();
+        EventToken { channel: self }
     }
 
     /// Configures the [Level::High] signalling condition and awaits for it to be signalled.
@@ -267,35 +973,442 @@ impl<'d> WakeUp<'d> {
     pub async fn wait_for_low(&mut self) {
         self.wait_for(Level::Low).await
     }
+
+    /// Await `count` signalled [Edge]s, counted in the interrupt handler so no edges are missed in between.
+    ///
+    /// The count is reset when called. The input is disabled again when done.
+    pub async fn wait_for_count(&mut self, edge: Edge, count: u32) {
+        let mut edges = self.edges(edge);
+        let mut seen = 0;
+        while seen < count {
+            seen += edges.next().await;
+        }
+    }
+
+    /// Enable the [WakeUpInput] in sticky mode, and return a stream of the signalled [Edge]s.
+    ///
+    /// As the input stays enabled in between awaits, no edges are lost. The input is disabled when the
+    /// returned [Edges] is dropped.
+    pub fn edges(&mut self, edge: Edge) -> Edges<'_, 'd> {
+        self.reset_count();
+        #[cfg(feature = "time")]
+        critical_section::with(|cs| self.wui.state.last_event.borrow(cs).set(None));
+        self.enable_counting(edge);
+        Edges { channel: self }
+    }
 }
 
-struct WakeUpInputFuture<'a, 'd> {
+#[cfg(feature = "miwu-edge-stream")]
+impl<'d> WakeUp<'d> {
+    /// Enable the [WakeUpInput] in sticky mode, and return a stream of the times of the signalled [Edge]s.
+    ///
+    /// The times are taken in the interrupt handler and buffered, so bursts of up to [EDGE_STREAM_CAPACITY] edges
+    /// are not lost between polls. The input is disabled when the returned [EdgeStream] is dropped, and the interrupt
+    /// handler only records the times while it exists. Needs the `miwu-edge-stream` feature.
+    pub fn edge_stream(&mut self, edge: Edge) -> EdgeStream<'_, 'd> {
+        self.reset_count();
+        critical_section::with(|cs| *self.wui.state.timestamps.borrow_ref_mut(cs) = Some(TimestampRing::new()));
+        self.enable_counting(edge);
+        EdgeStream { channel: self }
+    }
+}
+
+/// Stream of the times of the edges signalled by a [WakeUp], created with [WakeUp::edge_stream].
+///
+/// Implements [futures_core::Stream], unless the `miwu-group-waiters` feature is enabled.
+#[cfg(feature = "miwu-edge-stream")]
+pub struct EdgeStream<'a, 'd> {
     channel: &'a mut WakeUp<'d>,
 }
 
-impl Drop for WakeUpInputFuture<'_, '_> {
+#[cfg(feature = "miwu-edge-stream")]
+impl EdgeStream<'_, '_> {
+    fn pop(&self) -> Option<embassy_time::Instant> {
+        critical_section::with(|cs| self.channel.wui.state.timestamps.borrow_ref_mut(cs).as_mut()?.pop())
+            .map(embassy_time::Instant::from_ticks)
+    }
+
+    /// Wait for the next edge, returning the time at which it was signalled
+    pub async fn next(&mut self) -> embassy_time::Instant {
+        let this = &*self;
+        wait_until(this.channel.wui.waker, || this.pop()).await
+    }
+
+    /// The number of edges that were dropped because the buffer was full
+    #[must_use]
+    pub fn dropped(&self) -> u32 {
+        critical_section::with(|cs| {
+            let timestamps = self.channel.wui.state.timestamps.borrow_ref(cs);
+            timestamps.as_ref().map_or(0, |timestamps| timestamps.dropped)
+        })
+    }
+}
+
+#[cfg(all(feature = "miwu-edge-stream", not(feature = "miwu-group-waiters")))]
+impl futures_core::Stream for EdgeStream<'_, '_> {
+    type Item = embassy_time::Instant;
+
+    fn poll_next(self: core::pin::Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.channel.wui.waker.register(cx.waker());
+
+        match self.pop() {
+            Some(instant) => Poll::Ready(Some(instant)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "miwu-edge-stream")]
+impl Drop for EdgeStream<'_, '_> {
     fn drop(&mut self) {
-        // Clean up, and do not assume that the interrupt has run.
         self.channel.disable();
         self.channel.clear_pending();
+        critical_section::with(|cs| *self.channel.wui.state.timestamps.borrow_ref_mut(cs) = None);
     }
 }
 
-impl Future for WakeUpInputFuture<'_, '_> {
-    type Output = ();
+/// An event observed by [WakeUp::wait_for_ack], which is acknowledged by clearing the pending bit when dropped.
+#[must_use = "dropping the token immediately acknowledges the event"]
+pub struct EventToken<'a, 'd> {
+    channel: &'a mut WakeUp<'d>,
+}
 
-    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.channel.wui.waker.register(cx.waker());
+impl EventToken<'_, '_> {
+    /// Acknowledge the event, making the signal no longer pending
+    pub fn ack(self) {
+        // Clearing is done in Drop
+    }
+}
+
+impl Drop for EventToken<'_, '_> {
+    fn drop(&mut self) {
+        self.channel.clear_pending();
+    }
+}
+
+/// Stream of the edges signalled by a [WakeUp] in sticky mode, created with [WakeUp::edges].
+pub struct Edges<'a, 'd> {
+    channel: &'a mut WakeUp<'d>,
+}
+
+impl Edges<'_, '_> {
+    /// Wait for the next edges, returning the number of edges signalled since the previous call.
+    ///
+    /// Returns immediately if edges were signalled since the previous call.
+    pub async fn next(&mut self) -> u32 {
+        let channel = &*self.channel;
+        wait_until(channel.wui.waker, || {
+            let count = channel.wui.state.count.swap(0, Ordering::Relaxed);
+            (count > 0).then_some(count)
+        })
+        .await
+    }
+
+    /// The time at which the interrupt handler counted the most recent edge, see [WakeUp::last_event_time].
+    #[cfg(feature = "time")]
+    #[must_use]
+    pub fn last_event_time(&self) -> Option<embassy_time::Instant> {
+        self.channel.last_event_time()
+    }
+}
+
+impl Drop for Edges<'_, '_> {
+    fn drop(&mut self) {
+        self.channel.disable();
+        self.channel.clear_pending();
+    }
+}
+
+/// Error returned by [WakeSources]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeSourceError {
+    /// The registry has no room for more wake sources
+    Full,
+    /// No wake sources have been registered, so the system would never wake up
+    NoWakeSources,
+    /// The wake source with this index is not enabled
+    Disabled(usize),
+    /// The wake source with this index is already pending, so the system would wake up immediately
+    Pending(usize),
+    /// The wake source with this index is signalled by a peripheral that stops in the sleep mode, so it cannot wake
+    /// the system
+    Unavailable(usize),
+}
+
+/// Registry of the [WakeUp] channels that are armed as wake sources for a low power state.
+///
+/// Sleep entry code registers its wake sources once, and then uses [WakeSources::arm] and [WakeSources::validate]
+/// before every low power state, and [WakeSources::rearm] on resume. In [SleepMode::Idle] every enabled interrupt
+/// wakes the core, so the registry may be empty; in the deeper modes only the registered wake sources do.
+pub struct WakeSources<'a, 'd, const N: usize> {
+    sources: [Option<(&'a mut WakeUp<'d>, Mode)>; N],
+}
+
+impl<'a, 'd, const N: usize> WakeSources<'a, 'd, N> {
+    /// Create a registry for up to `N` wake sources. At most 32 wake sources are supported.
+    #[must_use]
+    pub fn new() -> Self {
+        assert!(N <= 32, "At most 32 wake sources are supported");
+
+        Self {
+            sources: [const { None }; N],
+        }
+    }
+
+    /// Register a channel as wake source with its signalling condition [Mode], returning its index.
+    ///
+    /// The channel is not armed until [WakeSources::arm] is called.
+    pub fn register(&mut self, wake: &'a mut WakeUp<'d>, mode: impl Into<Mode>) -> Result<usize, WakeSourceError> {
+        let index = self
+            .sources
+            .iter()
+            .position(Option::is_none)
+            .ok_or(WakeSourceError::Full)?;
+        self.sources[index] = Some((wake, mode.into()));
+        Ok(index)
+    }
+
+    /// Enable all registered wake sources with their signalling condition, clearing any previous trigger
+    pub fn arm(&mut self) {
+        for (wake, mode) in self.sources.iter_mut().flatten() {
+            wake.enable(*mode);
+        }
+    }
 
-        if self.channel.is_pending() {
-            Poll::Ready(())
+    /// Check that all registered wake sources are armed, not yet triggered and able to wake the system from `mode`,
+    /// right before entering that low power state
+    pub fn validate(&self, mode: SleepMode) -> Result<(), WakeSourceError> {
+        let mut any = false;
+        for (index, (wake, _)) in self
+            .sources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, source)| source.as_ref().map(|s| (i, s)))
+        {
+            any = true;
+            if wake.is_pending() {
+                return Err(WakeSourceError::Pending(index));
+            }
+            if !wake.is_enabled() {
+                return Err(WakeSourceError::Disabled(index));
+            }
+            if wake.wui.deepest_mode() < mode {
+                return Err(WakeSourceError::Unavailable(index));
+            }
+        }
+
+        if any || mode == SleepMode::Idle {
+            Ok(())
         } else {
-            Poll::Pending
+            Err(WakeSourceError::NoWakeSources)
+        }
+    }
+
+    /// Returns a bitmask of the wake sources that have been triggered, bit `n` being the source with index `n`
+    #[must_use]
+    pub fn triggered(&self) -> u32 {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| source.as_ref().is_some_and(|(wake, _)| wake.is_pending()))
+            .fold(0, |mask, (index, _)| mask | 1 << index)
+    }
+
+    /// Re-enable all registered wake sources after resuming, returning the bitmask of [WakeSources::triggered]
+    pub fn rearm(&mut self) -> u32 {
+        let triggered = self.triggered();
+        self.arm();
+        triggered
+    }
+
+    /// Disable all registered wake sources
+    pub fn disarm(&mut self) {
+        for (wake, _) in self.sources.iter_mut().flatten() {
+            wake.disable();
+            wake.clear_pending();
         }
     }
 }
 
+impl<const N: usize> Default for WakeSources<'_, '_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [WakeUp] channel that is triggered from software, for signalling between tasks or from code without interrupts.
+///
+/// Pending bits can be set by writing them, which is handled just like a signalling condition on the input.
+/// Use a channel of which the input is unused, as signals on the input also trigger the channel.
+///
+/// The channel runs in sticky mode, so triggers that happen while no task is waiting are counted and not lost.
+pub struct SoftWake<'d> {
+    wake: WakeUp<'d>,
+}
+
+impl<'d> SoftWake<'d> {
+    /// Use the channel for software triggers
+    pub fn new(mut wake: WakeUp<'d>) -> Self {
+        wake.reset_count();
+        wake.enable_with(Edge::Rising, OnTrigger::KeepEnabled);
+        Self { wake }
+    }
+
+    /// Returns a handle with which the channel can be triggered from anywhere, including other tasks and interrupts
+    #[must_use]
+    pub fn trigger(&self) -> SoftWakeTrigger {
+        let wui = &self.wake.wui;
+        SoftWakeTrigger {
+            miwu: miwu_index(wui.port) as u8,
+            group: wui.group,
+            subgroup: wui.subgroup,
+        }
+    }
+
+    /// Await the next triggers, returning the number of triggers since the previous call
+    pub async fn wait(&mut self) -> u32 {
+        self.wake.wait_for_event().await
+    }
+
+    /// Stop using the channel for software triggers
+    pub fn into_inner(mut self) -> WakeUp<'d> {
+        self.wake.disable();
+        self.wake.clear_pending();
+        self.wake
+    }
+}
+
+/// Handle to trigger a [SoftWake]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SoftWakeTrigger {
+    miwu: u8,
+    group: u8,
+    subgroup: u8,
+}
+
+impl SoftWakeTrigger {
+    /// Trigger the channel, by setting its pending bit
+    pub fn pend(&self) {
+        // Note(no-cs): writing zeroes has no effect, so this atomically sets a single bit.
+        miwu_regs(self.miwu)
+            .wkpndn(self.group as usize)
+            .write(|w| w.input(self.subgroup).set_bit());
+    }
+}
+
+/// A set of [WakeUp] channels that can be awaited together.
+///
+/// Awaiting any of several channels is done with a single future, instead of building `select` trees.
+pub struct MultiWakeUp<'d, const N: usize> {
+    channels: [WakeUp<'d>; N],
+}
+
+impl<'d, const N: usize> MultiWakeUp<'d, N> {
+    /// Combine several channels, which are referred to by their index in `channels`.
+    pub fn new(channels: [WakeUp<'d>; N]) -> Self {
+        Self { channels }
+    }
+
+    /// Configures the same signalling condition [Mode] on every channel, and awaits any of them to be signalled.
+    ///
+    /// Returns the index of the signalled channel.
+    pub async fn wait_for_any(&mut self, mode: impl Into<Mode>) -> usize {
+        let mode = mode.into();
+        self.wait_for_any_of([mode; N]).await
+    }
+
+    /// Configures a signalling condition [Mode] per channel, and awaits any of them to be signalled.
+    ///
+    /// Returns the index of the signalled channel. All channels are disabled afterwards, but only the pending bit of
+    /// the returned channel is cleared: other channels that were signalled as well can be checked with
+    /// [WakeUp::is_pending] through [Self::channel]. Waiting again clears them.
+    pub async fn wait_for_any_of(&mut self, modes: [Mode; N]) -> usize {
+        for (channel, mode) in self.channels.iter_mut().zip(modes) {
+            channel.enable(mode);
+        }
+
+        let mut guard = MultiWaitGuard {
+            channels: &mut self.channels,
+            reported: None,
+        };
+        let channels = &*guard.channels;
+
+        let futures = core::array::from_fn(|i| {
+            let channel = &channels[i];
+            wait_until(channel.wui.waker, move || channel.is_pending().then_some(()))
+        });
+
+        let ((), index) = embassy_futures::select::select_array(futures).await;
+        guard.reported = Some(index);
+        index
+    }
+
+    /// Access a single channel
+    pub fn channel(&mut self, index: usize) -> &mut WakeUp<'d> {
+        &mut self.channels[index]
+    }
+
+    /// Split into the individual channels
+    pub fn into_inner(self) -> [WakeUp<'d>; N] {
+        self.channels
+    }
+}
+
+/// Disables all channels when the wait completes or is cancelled, and clears the pending bit of the reported channel.
+struct MultiWaitGuard<'a, 'd, const N: usize> {
+    channels: &'a mut [WakeUp<'d>; N],
+    /// The index of the channel returned by the wait, if it completed
+    reported: Option<usize>,
+}
+
+impl<const N: usize> Drop for MultiWaitGuard<'_, '_, N> {
+    fn drop(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.disable();
+        }
+
+        if let Some(index) = self.reported {
+            self.channels[index].clear_pending();
+        }
+    }
+}
+
+/// Disables the channel and clears its pending bit when the wait completes or is cancelled.
+struct WaitGuard<'a, 'd> {
+    channel: &'a mut WakeUp<'d>,
+}
+
+impl WaitGuard<'_, '_> {
+    async fn wait_for_pending(&self) {
+        wait_until(self.channel.wui.waker, || self.channel.is_pending().then_some(())).await
+    }
+}
+
+impl Drop for WaitGuard<'_, '_> {
+    fn drop(&mut self) {
+        // Clean up, and do not assume that the interrupt has run.
+        self.channel.disable();
+        self.channel.clear_pending();
+    }
+}
+
 /// Interrupt handler for the [WakeUp] driver
+///
+/// All WakeUpInputs of a group share the `WKINTx_n` interrupt of that group, so the handler of every channel in use
+/// needs to be bound to that interrupt. Other handlers, like your own, can be bound to the same interrupt as well:
+///
+/// ```rust,ignore
+/// use embassy_npcx::{bind_interrupts, miwu, peripherals};
+///
+/// bind_interrupts!(struct Irqs {
+///     WKINTG_1 => miwu::InterruptHandler<peripherals::MIWU1_73>, miwu::InterruptHandler<peripherals::MIWU1_74>;
+/// });
+/// ```
+///
+/// The interrupt is enabled when a [WakeUp] is constructed, with the priority that was set on it before,
+/// see [InterruptExt::set_priority](crate::interrupt::InterruptExt::set_priority).
 pub struct InterruptHandler<T> {
     _phantom: PhantomData<T>,
 }
@@ -306,13 +1419,48 @@ impl<T: WakeUpInput> crate::interrupt::typelevel::Handler<T::Interrupt> for Inte
         let group = T::group() as usize;
 
         let pending = port.wkpndn(group).read();
-        if pending.input(T::subgroup()).bit_is_set() {
-            T::waker().wake();
+        if pending.input(T::subgroup()).bit_is_clear() {
+            #[cfg(feature = "debug")]
+            if pending.bits() == 0 {
+                IRQ_STATS[T::miwu() as usize][group]
+                    .spurious
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
 
-            // Note(cs): other tasks can be modifying the same register.
-            critical_section::with(|_cs| {
-                port.wkenn(group).modify(|_, w| w.input(T::subgroup()).clear_bit());
-            });
+        #[cfg(feature = "debug")]
+        IRQ_STATS[T::miwu() as usize][group]
+            .delivered
+            .fetch_add(1, Ordering::Relaxed);
+
+        let state = T::state();
+        match OnTrigger::from_bits(state.on_trigger.load(Ordering::Relaxed)) {
+            OnTrigger::Disable => {
+                wake(T::waker());
+
+                // Note(cs): other tasks can be modifying the same register.
+                critical_section::with(|_cs| {
+                    port.wkenn(group).modify(|_, w| w.input(T::subgroup()).clear_bit());
+                });
+            }
+            OnTrigger::KeepEnabled => {
+                state.record_event();
+
+                // Note(no-cs): atomic write to clear a single bit, safe.
+                port.wkpcln(group).write(|w| w.input(T::subgroup()).clear());
+                wake(T::waker());
+            }
+            OnTrigger::Mask => {
+                state.record_event();
+
+                // Note(cs): other tasks can be modifying the same register.
+                critical_section::with(|_cs| {
+                    port.wkenn(group).modify(|_, w| w.input(T::subgroup()).clear_bit());
+                });
+                port.wkpcln(group).write(|w| w.input(T::subgroup()).clear());
+                wake(T::waker());
+            }
         }
     }
 }
@@ -324,7 +1472,8 @@ macro_rules! impl_wake_up_input_n {
                 $miwu_n,
                 ($group - 1), // The groups are 1-indexed
                 $subgroup,
-                $interrupt
+                $interrupt,
+                [<GROUP_WAITERS_ $miwu_n _ $group>]
             );
         }
     };
@@ -332,6 +1481,11 @@ macro_rules! impl_wake_up_input_n {
 
 macro_rules! impl_wake_up_input_nm {
     ($miwu_n:literal, $group:literal, $interrupt:ident) => {
+        paste! {
+            #[cfg(feature = "miwu-group-waiters")]
+            static [<GROUP_WAITERS_ $miwu_n _ $group>]: WaitQueue = WaitQueue::new();
+        }
+
         impl_wake_up_input_n!($miwu_n, $group, 0, $interrupt);
         impl_wake_up_input_n!($miwu_n, $group, 1, $interrupt);
         impl_wake_up_input_n!($miwu_n, $group, 2, $interrupt);
@@ -346,7 +1500,18 @@ macro_rules! impl_wake_up_input_nm {
 impl_wake_up_input_nm!(0, 1, WKINTA_0);
 impl_wake_up_input_nm!(0, 2, WKINTB_0);
 impl_wake_up_input_nm!(0, 3, WKINTC_0);
-impl_wake_up_input_nm!(0, 4, WKINTD_0);
+// MIWU0_43 is the ITIM32_1 timeout, which the time driver takes with `time-driver-itim`.
+#[cfg(feature = "miwu-group-waiters")]
+static GROUP_WAITERS_0_4: WaitQueue = WaitQueue::new();
+impl_wake_up_input_n!(0, 4, 0, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 1, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 2, WKINTD_0);
+#[cfg(not(feature = "time-driver-itim"))]
+impl_wake_up_input_n!(0, 4, 3, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 4, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 5, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 6, WKINTD_0);
+impl_wake_up_input_n!(0, 4, 7, WKINTD_0);
 impl_wake_up_input_nm!(0, 5, WKINTE_0);
 impl_wake_up_input_nm!(0, 6, WKINTF_0);
 impl_wake_up_input_nm!(0, 7, WKINTG_0);
@@ -367,3 +1532,188 @@ impl_wake_up_input_nm!(2, 5, WKINTE_2);
 impl_wake_up_input_nm!(2, 6, WKINTF_2);
 impl_wake_up_input_nm!(2, 7, WKINTG_2);
 impl_wake_up_input_nm!(2, 8, WKINTH_2);
+
+#[cfg(test)]
+mod tests {
+    //! Host-side tests of the drop paths, against register blocks in RAM.
+    //!
+    //! Run with `cargo test --lib --no-default-features --target x86_64-unknown-linux-gnu`.
+
+    extern crate std;
+
+    use core::cell::UnsafeCell;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use std::boxed::Box;
+    use std::sync::{Mutex, MutexGuard};
+
+    use embassy_hal_internal::PeripheralRef;
+
+    use super::{AnyWakeUpInput, ChannelState, ChannelWaker, Edge, WakeUp};
+    use crate::pac::miwu0::RegisterBlock;
+
+    #[repr(C, align(4))]
+    struct MockRegisters(UnsafeCell<[u8; core::mem::size_of::<RegisterBlock>()]>);
+
+    // Safety: only accessed by the test holding LOCK.
+    unsafe impl Sync for MockRegisters {}
+
+    static REGISTERS: [MockRegisters; 3] =
+        [const { MockRegisters(UnsafeCell::new([0; core::mem::size_of::<RegisterBlock>()])) }; 3];
+
+    /// Serializes the tests, as they share the register blocks
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    pub(super) fn miwu_regs(miwu_n: u8) -> &'static RegisterBlock {
+        // Safety: the mock is large and aligned enough for the register block.
+        unsafe { &*REGISTERS[usize::from(miwu_n)].0.get().cast::<RegisterBlock>() }
+    }
+
+    /// Take the registers for a test, all zero.
+    fn reset() -> MutexGuard<'static, ()> {
+        let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for registers in &REGISTERS {
+            // Safety: LOCK is held.
+            unsafe { *registers.0.get() = [0; core::mem::size_of::<RegisterBlock>()] };
+        }
+        guard
+    }
+
+    fn wake_up(group: u8, subgroup: u8) -> WakeUp<'static> {
+        WakeUp {
+            wui: PeripheralRef::new(AnyWakeUpInput {
+                irq: crate::interrupt::Interrupt::WKINTA_0,
+                waker: Box::leak(Box::new(ChannelWaker::new())),
+                state: Box::leak(Box::new(ChannelState::new())),
+                port: miwu_regs(0),
+                group,
+                subgroup,
+            }),
+        }
+    }
+
+    fn read(register: *const u8) -> u8 {
+        // Safety: points into the mock registers.
+        unsafe { register.read_volatile() }
+    }
+
+    fn write(register: *const u8, value: u8) {
+        // Safety: points into the mock registers.
+        unsafe { register.cast_mut().write_volatile(value) }
+    }
+
+    /// The bits written to `WKPCL`, which the mock keeps instead of clearing `WKPND`
+    fn cleared(group: u8) -> u8 {
+        read(miwu_regs(0).wkpcln(usize::from(group)) as *const _ as *const u8)
+    }
+
+    fn forget_cleared(group: u8) {
+        write(miwu_regs(0).wkpcln(usize::from(group)) as *const _ as *const u8, 0);
+    }
+
+    fn enabled(group: u8) -> u8 {
+        read(miwu_regs(0).wkenn(usize::from(group)) as *const _ as *const u8)
+    }
+
+    fn set_pending(group: u8, subgroup: u8) {
+        write(
+            miwu_regs(0).wkpndn(usize::from(group)) as *const _ as *const u8,
+            1 << subgroup,
+        );
+    }
+
+    fn poll<F: Future>(future: core::pin::Pin<&mut F>) -> Poll<F::Output> {
+        const VTABLE: RawWakerVTable =
+            RawWakerVTable::new(|_| RawWaker::new(core::ptr::null(), &VTABLE), |_| {}, |_| {}, |_| {});
+        // Safety: the vtable does nothing.
+        let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn cancelled_wait_disables_and_clears() {
+        let _lock = reset();
+        let mut wake = wake_up(1, 2);
+
+        {
+            let mut future = pin!(wake.wait_for(Edge::Rising));
+            assert!(poll(future.as_mut()).is_pending());
+            assert_eq!(enabled(1), 1 << 2);
+            forget_cleared(1);
+        }
+
+        assert_eq!(enabled(1), 0);
+        assert_eq!(cleared(1), 1 << 2);
+    }
+
+    #[test]
+    fn completed_wait_disables_and_clears() {
+        let _lock = reset();
+        let mut wake = wake_up(1, 2);
+
+        let mut future = pin!(wake.wait_for(Edge::Rising));
+        assert!(poll(future.as_mut()).is_pending());
+        forget_cleared(1);
+
+        set_pending(1, 2);
+        assert!(poll(future.as_mut()).is_ready());
+        assert_eq!(enabled(1), 0);
+        assert_eq!(cleared(1), 1 << 2);
+    }
+
+    #[test]
+    fn event_token_clears_once_when_dropped() {
+        let _lock = reset();
+        let mut wake = wake_up(3, 5);
+
+        let mut future = pin!(wake.wait_for_ack(Edge::Falling));
+        assert!(poll(future.as_mut()).is_pending());
+        forget_cleared(3);
+
+        set_pending(3, 5);
+        let Poll::Ready(token) = poll(future.as_mut()) else {
+            panic!("the pending event was not observed");
+        };
+        // The future that observed the event leaves it pending for the token.
+        assert_eq!(enabled(3), 0);
+        assert_eq!(cleared(3), 0);
+
+        drop(token);
+        assert_eq!(cleared(3), 1 << 5);
+    }
+
+    #[test]
+    fn event_token_clears_when_acknowledged() {
+        let _lock = reset();
+        let mut wake = wake_up(3, 5);
+
+        let mut future = pin!(wake.wait_for_ack(Edge::Any));
+        assert!(poll(future.as_mut()).is_pending());
+        forget_cleared(3);
+
+        set_pending(3, 5);
+        let Poll::Ready(token) = poll(future.as_mut()) else {
+            panic!("the pending event was not observed");
+        };
+        assert_eq!(cleared(3), 0);
+
+        token.ack();
+        assert_eq!(cleared(3), 1 << 5);
+    }
+
+    #[test]
+    fn cancelled_wait_for_ack_disables_and_clears() {
+        let _lock = reset();
+        let mut wake = wake_up(6, 0);
+
+        {
+            let mut future = pin!(wake.wait_for_ack(Edge::Rising));
+            assert!(poll(future.as_mut()).is_pending());
+            forget_cleared(6);
+        }
+
+        assert_eq!(enabled(6), 0);
+        assert_eq!(cleared(6), 1 << 0);
+    }
+}
@@ -0,0 +1,159 @@
+//! Crash reports across a watchdog reset
+//!
+//! With the `panic-report` feature, the HAL provides the `#[panic_handler]`, and a `HardFault` handler with the `rt`
+//! feature, so the application must not define its own. Both write a [PanicReport] into the `.uninit` section of RAM,
+//! which keeps its contents across a reset, and then let the TWD watchdog reset the chip with [pmc::system_reset]. On
+//! the next boot [take_panic_report] returns the report once, so the firmware can log or forward it to the host.
+//!
+//! The report is lost when RAM loses power, like on a `VCC1` power cycle.
+
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+use crate::pmc::{self, ResetCause, ResetType};
+
+/// The maximum length of a recorded panic message in bytes, longer messages are truncated.
+pub const MAX_MESSAGE_LEN: usize = 192;
+
+/// Marks a valid report in retained RAM
+const MAGIC: u32 = 0x5041_4e43;
+
+/// The report as stored in retained RAM.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Retained {
+    magic: u32,
+    len: u32,
+    message: [u8; MAX_MESSAGE_LEN],
+    /// Guards against a report that was only partly written, or clobbered after the reset
+    checksum: u32,
+}
+
+impl Retained {
+    fn checksum(&self) -> u32 {
+        self.message[..self.len as usize]
+            .iter()
+            .fold(self.len ^ MAGIC, |sum, byte| sum.rotate_left(5) ^ u32::from(*byte))
+    }
+}
+
+#[link_section = ".uninit.panic_report"]
+static mut RETAINED: MaybeUninit<Retained> = MaybeUninit::uninit();
+
+/// A panic recorded before the last reset.
+#[derive(Copy, Clone)]
+pub struct PanicReport {
+    len: usize,
+    message: [u8; MAX_MESSAGE_LEN],
+    reset_cause: ResetCause,
+}
+
+impl PanicReport {
+    /// The panic message, possibly truncated to [MAX_MESSAGE_LEN] bytes.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        let message = &self.message[..self.len];
+        match core::str::from_utf8(message) {
+            Ok(message) => message,
+            // Truncation can split a character.
+            Err(e) => core::str::from_utf8(&message[..e.valid_up_to()]).unwrap_or_default(),
+        }
+    }
+
+    /// How the chip was reset after the panic, normally [ResetCause::Watchdog].
+    #[must_use]
+    pub fn reset_cause(&self) -> ResetCause {
+        self.reset_cause
+    }
+}
+
+impl core::fmt::Debug for PanicReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PanicReport")
+            .field("message", &self.message())
+            .field("reset_cause", &self.reset_cause)
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for PanicReport {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "PanicReport {{ message: {=str}, reset_cause: {} }}",
+            self.message(),
+            self.reset_cause
+        )
+    }
+}
+
+/// Returns the panic recorded before the last reset, once.
+pub fn take_panic_report() -> Option<PanicReport> {
+    critical_section::with(|_| {
+        // Safety: the report is only accessed in a critical section, or by the panic handler that never returns.
+        let retained = unsafe { &mut *addr_of_mut!(RETAINED) };
+
+        // Safety: every bit pattern is a valid `Retained`, the magic and checksum tell whether it holds a report. The
+        // read is volatile, as the contents were written before the reset.
+        let report = unsafe { core::ptr::read_volatile(retained.as_ptr()) };
+        if report.magic != MAGIC || report.len as usize > MAX_MESSAGE_LEN || report.checksum() != report.checksum {
+            return None;
+        }
+
+        // Safety: see above.
+        unsafe { addr_of_mut!((*retained.as_mut_ptr()).magic).write_volatile(0) };
+
+        Some(PanicReport {
+            len: report.len as usize,
+            message: report.message,
+            reset_cause: pmc::reset_cause(),
+        })
+    })
+}
+
+/// Formats into the retained report, truncating what does not fit.
+struct MessageWriter<'a> {
+    report: &'a mut Retained,
+}
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let len = self.report.len as usize;
+        let n = s.len().min(MAX_MESSAGE_LEN - len);
+        self.report.message[len..len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.report.len += n as u32;
+        Ok(())
+    }
+}
+
+/// Record `args` as the panic report, and reset the chip with the watchdog.
+fn record_and_reset(args: core::fmt::Arguments) -> ! {
+    cortex_m::interrupt::disable();
+
+    // Safety: interrupts are disabled and this never returns, so nothing else accesses the report.
+    let report = unsafe { (*addr_of_mut!(RETAINED)).assume_init_mut() };
+    report.magic = 0;
+    report.len = 0;
+    let _ = MessageWriter { report: &mut *report }.write_fmt(args);
+    report.checksum = report.checksum();
+    report.magic = MAGIC;
+
+    pmc::system_reset(ResetType::Watchdog)
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    record_and_reset(format_args!("{}", info))
+}
+
+#[cfg(feature = "rt")]
+#[cortex_m_rt::exception]
+unsafe fn HardFault(frame: &cortex_m_rt::ExceptionFrame) -> ! {
+    record_and_reset(format_args!(
+        "HardFault at {:#010x}, lr {:#010x}",
+        frame.pc(),
+        frame.lr()
+    ))
+}
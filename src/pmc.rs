@@ -0,0 +1,903 @@
+//! Power Management Controller (PMC)
+//!
+//! Every peripheral has a bit in one of the `PWDWN_CTLn` registers that stops its clock. Drivers hold a [ClockGuard]
+//! for the lifetime of the driver, which ungates the clock of their peripheral on construction and gates it again
+//! when dropped.
+//!
+//! The PMC also controls the power modes of the chip, see [enter_idle], [enter_sleep] and [enter_deep_sleep].
+//! In sleep and deep sleep the peripheral interrupts are not delivered to the core directly, only inputs enabled in
+//! the MIWU (see [crate::miwu]) wake the chip. Once awake, the pending interrupt is handled as usual.
+
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+#[cfg(feature = "debug")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+/// The `PWDWN_CTLn` register and bit that gates the clock of a peripheral.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct PwdwnBit {
+    /// The `PWDWN_CTLn` register, 1-indexed like the register names
+    ctl: u8,
+    /// The bit within that register
+    bit: u8,
+}
+
+impl PwdwnBit {
+    pub(crate) const fn new(ctl: u8, bit: u8) -> Self {
+        assert!(matches!(ctl, 1..=7), "There are only 7 PWDWN_CTL registers");
+        assert!(bit < 8, "The PWDWN_CTL registers are 8 bits wide");
+
+        Self { ctl, bit }
+    }
+}
+
+/// A peripheral whose clock can be gated in the PMC.
+pub(crate) trait ClockGated {
+    /// The bit that gates the clock of this peripheral
+    const PWDWN: PwdwnBit;
+    /// The power well the peripheral is in, all clock gated peripherals are in the core well
+    const POWER_WELL: PowerWell = PowerWell::Vcc1;
+}
+
+/// The power wells of the chip.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerWell {
+    /// The standby supply, which stays on in the PSL state to power the PSL itself
+    Vsby,
+    /// The supply of the core and its peripherals, switched off by the PSL
+    Vcc1,
+}
+
+impl PowerWell {
+    const ALL: [PowerWell; 2] = [PowerWell::Vsby, PowerWell::Vcc1];
+}
+
+/// Errors when entering a power state
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerWellError {
+    /// A driver still depends on this power well, which the state would switch off
+    InUse(PowerWell),
+}
+
+/// The number of drivers depending on each power well
+static POWER_WELL_USERS: [AtomicU16; PowerWell::ALL.len()] = [const { AtomicU16::new(0) }; PowerWell::ALL.len()];
+
+/// Mark a driver as depending on `well`.
+pub(crate) fn acquire_power_well(well: PowerWell) {
+    POWER_WELL_USERS[well as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Undo [acquire_power_well].
+pub(crate) fn release_power_well(well: PowerWell) {
+    POWER_WELL_USERS[well as usize].fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Check that no driver depends on any of the `wells` a power state would switch off.
+///
+/// Idle, sleep and deep sleep stop clocks but keep every well powered, the clocks are covered by [PowerDomainToken]s
+/// in [enter]. The PSL state is the only one that switches a well off, so [crate::psl::Psl::enter] checks `VCC1`.
+pub fn check_power_down(wells: &[PowerWell]) -> Result<(), PowerWellError> {
+    match wells
+        .iter()
+        .find(|well| POWER_WELL_USERS[**well as usize].load(Ordering::Relaxed) != 0)
+    {
+        Some(well) => Err(PowerWellError::InUse(*well)),
+        None => Ok(()),
+    }
+}
+
+fn modify_pwdwn(ctl: u8, f: impl FnOnce(u8) -> u8) {
+    // Safety:
+    // the pac ptr functions return pointers to memory that is used for registers for the 'static lifetime
+    // and the created reference is shared.
+    let pmc = unsafe { &*crate::pac::Pmc::ptr() };
+
+    // Note(cs): other drivers can be modifying the same register.
+    critical_section::with(|_cs| match ctl {
+        1 => pmc.pwdwn_ctl1().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        2 => pmc.pwdwn_ctl2().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        3 => pmc.pwdwn_ctl3().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        4 => pmc.pwdwn_ctl4().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        5 => pmc.pwdwn_ctl5().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        6 => pmc.pwdwn_ctl6().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        7 => pmc.pwdwn_ctl7().modify(|r, w| unsafe { w.bits(f(r.bits())) }),
+        _ => unreachable!(),
+    });
+}
+
+/// Let the clock of a peripheral run.
+pub(crate) fn ungate(pwdwn: PwdwnBit) {
+    modify_pwdwn(pwdwn.ctl, |bits| bits & !(1 << pwdwn.bit));
+}
+
+/// Stop the clock of a peripheral.
+pub(crate) fn gate(pwdwn: PwdwnBit) {
+    modify_pwdwn(pwdwn.ctl, |bits| bits | (1 << pwdwn.bit));
+}
+
+/// Let the clock of peripheral `T` run, and mark its power well as in use.
+pub(crate) fn enable_peripheral_clock<T: ClockGated>() {
+    ungate(T::PWDWN);
+    acquire_power_well(T::POWER_WELL);
+}
+
+/// Stop the clock of peripheral `T`, and release its power well.
+#[allow(unused)]
+pub(crate) fn disable_peripheral_clock<T: ClockGated>() {
+    gate(T::PWDWN);
+    release_power_well(T::POWER_WELL);
+}
+
+/// Keeps a power well marked as in use until dropped, for drivers without a clock to gate.
+pub(crate) struct PowerWellGuard(PowerWell);
+
+impl PowerWellGuard {
+    pub(crate) fn new(well: PowerWell) -> Self {
+        acquire_power_well(well);
+        Self(well)
+    }
+}
+
+impl Drop for PowerWellGuard {
+    fn drop(&mut self) {
+        release_power_well(self.0);
+    }
+}
+
+/// Keeps the clock of a peripheral running, and its power well marked as in use, until dropped.
+pub(crate) struct ClockGuard {
+    pwdwn: PwdwnBit,
+    power_well: PowerWell,
+}
+
+impl ClockGuard {
+    /// Ungate the clock of peripheral `T`.
+    pub(crate) fn new<T: ClockGated>() -> Self {
+        enable_peripheral_clock::<T>();
+        Self {
+            pwdwn: T::PWDWN,
+            power_well: T::POWER_WELL,
+        }
+    }
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        gate(self.pwdwn);
+        release_power_well(self.power_well);
+    }
+}
+
+macro_rules! impl_clock_gated {
+    ($($(#[$attr:meta])* $peripheral:ident => $pwdwn:expr,)*) => {
+        $(
+            $(#[$attr])*
+            impl ClockGated for crate::peripherals::$peripheral {
+                const PWDWN: PwdwnBit = $pwdwn;
+            }
+        )*
+    };
+}
+
+/// The `PWDWN_CTLn` bits of the MFT16 timers, also used by the time driver.
+pub(crate) const MFT16_PWDWN: [PwdwnBit; 3] = [PwdwnBit::new(1, 0), PwdwnBit::new(1, 1), PwdwnBit::new(1, 2)];
+/// The `PWDWN_CTLn` bits of the ITIM32 timers, also used by the time driver.
+pub(crate) const ITIM32_PWDWN: [PwdwnBit; 6] = [
+    PwdwnBit::new(4, 0),
+    PwdwnBit::new(4, 1),
+    PwdwnBit::new(4, 2),
+    PwdwnBit::new(4, 3),
+    PwdwnBit::new(4, 4),
+    PwdwnBit::new(4, 5),
+];
+/// The `PWDWN_CTLn` bit of the ITIM64 timer, also used by the time driver.
+#[allow(unused)]
+pub(crate) const ITIM64_PWDWN: PwdwnBit = PwdwnBit::new(4, 6);
+
+impl_clock_gated!(
+    #[cfg(not(feature = "time-driver-mft16-1"))]
+    MFT16_1 => MFT16_PWDWN[0],
+    #[cfg(not(feature = "time-driver-mft16-2"))]
+    MFT16_2 => MFT16_PWDWN[1],
+    #[cfg(not(feature = "time-driver-mft16-3"))]
+    MFT16_3 => MFT16_PWDWN[2],
+    #[cfg(not(feature = "time-driver-itim"))]
+    ITIM32_1 => ITIM32_PWDWN[0],
+    ITIM32_2 => ITIM32_PWDWN[1],
+    ITIM32_3 => ITIM32_PWDWN[2],
+    ITIM32_4 => ITIM32_PWDWN[3],
+    ITIM32_5 => ITIM32_PWDWN[4],
+    ITIM32_6 => ITIM32_PWDWN[5],
+    CR_UART1 => PwdwnBit::new(1, 4),
+    PWM0 => PwdwnBit::new(2, 0),
+    PWM1 => PwdwnBit::new(2, 1),
+    PWM2 => PwdwnBit::new(2, 2),
+    PWM3 => PwdwnBit::new(2, 3),
+    PWM4 => PwdwnBit::new(2, 4),
+    PWM5 => PwdwnBit::new(2, 5),
+    PWM6 => PwdwnBit::new(2, 6),
+    PWM7 => PwdwnBit::new(2, 7),
+    SMB0 => PwdwnBit::new(3, 0),
+    SMB1 => PwdwnBit::new(3, 1),
+    SMB2 => PwdwnBit::new(3, 2),
+    SMB3 => PwdwnBit::new(3, 3),
+    SMB4 => PwdwnBit::new(3, 4),
+    SPIP => PwdwnBit::new(4, 7),
+    SMB5 => PwdwnBit::new(7, 0),
+    SMB6 => PwdwnBit::new(7, 1),
+    SMB7 => PwdwnBit::new(7, 2),
+    CR_UART4 => PwdwnBit::new(7, 4),
+    CR_UART3 => PwdwnBit::new(7, 5),
+    CR_UART2 => PwdwnBit::new(7, 6),
+);
+
+/// `PMCSR`: enable instant wake-up from deep sleep
+const PMCSR_DI_INSTW: u8 = 1 << 0;
+/// `PMCSR`: disable the high frequency clock on the next `WFI`
+const PMCSR_DHF: u8 = 1 << 1;
+/// `PMCSR`: enter sleep on the next `WFI`
+const PMCSR_IDLE: u8 = 1 << 2;
+
+/// Power mode of the chip while it waits for an interrupt, from shallow to deep.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepMode {
+    /// Only the core is halted, all clocks keep running.
+    Idle,
+    /// The core clock is stopped, the high frequency clock generator keeps running.
+    Sleep,
+    /// Like [SleepMode::DeepSleep], but the high frequency clock is kept ready for a faster wake-up at a higher
+    /// sleep current.
+    DeepSleepInstantWake,
+    /// The high frequency clock generator is stopped as well, only the `LFCLK` domain keeps running.
+    DeepSleep,
+}
+
+impl SleepMode {
+    const fn pmcsr(self) -> u8 {
+        match self {
+            SleepMode::Idle => 0,
+            SleepMode::Sleep => PMCSR_IDLE,
+            SleepMode::DeepSleep => PMCSR_IDLE | PMCSR_DHF,
+            SleepMode::DeepSleepInstantWake => PMCSR_IDLE | PMCSR_DHF | PMCSR_DI_INSTW,
+        }
+    }
+}
+
+mod sealed {
+    pub trait SealedPowerDomain {
+        const INDEX: usize;
+    }
+}
+
+/// A clock domain that can be kept running while the chip sleeps, with a [PowerDomainToken].
+#[allow(private_bounds)]
+pub trait PowerDomain: sealed::SealedPowerDomain {
+    /// The deepest mode in which this domain keeps running
+    const DEEPEST: SleepMode;
+}
+
+macro_rules! power_domain {
+    ($($(#[$attr:meta])* $name:ident => $index:expr, $deepest:expr;)*) => {
+        $(
+            $(#[$attr])*
+            #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+            #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+            pub struct $name;
+
+            impl sealed::SealedPowerDomain for $name {
+                const INDEX: usize = $index;
+            }
+
+            impl PowerDomain for $name {
+                const DEEPEST: SleepMode = $deepest;
+            }
+        )*
+
+        const POWER_DOMAIN_COUNT: usize = [$($index),*].len();
+        const POWER_DOMAIN_DEEPEST: [SleepMode; POWER_DOMAIN_COUNT] = [$($deepest),*];
+    };
+}
+
+power_domain!(
+    /// The core clock, for example to keep the debugger attached.
+    Core => 0, SleepMode::Idle;
+    /// The `APB1_CLK`, used by the MFT16 timers.
+    Apb1 => 1, SleepMode::Sleep;
+    /// The `APB2_CLK`, used by the SPIP, some of the SMBus controllers, the ITIM timers and the PWM channels.
+    Apb2 => 2, SleepMode::Sleep;
+    /// The `APB3_CLK`, used by the other SMBus controllers.
+    Apb3 => 3, SleepMode::Sleep;
+    /// The `APB4_CLK`, used by the UARTs.
+    Apb4 => 4, SleepMode::Sleep;
+);
+
+/// The number of outstanding [PowerDomainToken]s per domain
+static POWER_DOMAIN_TOKENS: [AtomicU16; POWER_DOMAIN_COUNT] = [const { AtomicU16::new(0) }; POWER_DOMAIN_COUNT];
+
+/// Keeps a clock domain running while the chip sleeps, until dropped.
+///
+/// [enter] and the low-power executor never sleep deeper than all outstanding tokens allow.
+#[must_use = "The domain may stop in sleep as soon as the token is dropped"]
+pub struct PowerDomainToken<D: PowerDomain> {
+    _domain: PhantomData<D>,
+}
+
+impl<D: PowerDomain> PowerDomainToken<D> {
+    /// Keep domain `D` running while the chip sleeps.
+    pub fn new() -> Self {
+        POWER_DOMAIN_TOKENS[D::INDEX].fetch_add(1, Ordering::Relaxed);
+        Self { _domain: PhantomData }
+    }
+}
+
+impl<D: PowerDomain> Default for PowerDomainToken<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: PowerDomain> Drop for PowerDomainToken<D> {
+    fn drop(&mut self) {
+        POWER_DOMAIN_TOKENS[D::INDEX].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A [PowerDomain] picked at runtime, for drivers that are not generic over their instance.
+#[derive(Copy, Clone)]
+pub(crate) struct AnyPowerDomain {
+    index: usize,
+}
+
+impl AnyPowerDomain {
+    pub(crate) fn of<D: PowerDomain>() -> Self {
+        Self { index: D::INDEX }
+    }
+
+    /// Keep the domain running while the chip sleeps, like [PowerDomainToken::new].
+    pub(crate) fn token(self) -> AnyPowerDomainToken {
+        POWER_DOMAIN_TOKENS[self.index].fetch_add(1, Ordering::Relaxed);
+        AnyPowerDomainToken { index: self.index }
+    }
+}
+
+/// Keeps an [AnyPowerDomain] running while the chip sleeps, until dropped.
+pub(crate) struct AnyPowerDomainToken {
+    index: usize,
+}
+
+impl Drop for AnyPowerDomainToken {
+    fn drop(&mut self) {
+        POWER_DOMAIN_TOKENS[self.index].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The deepest mode the chip can sleep in without stopping a clock that is still in use.
+#[must_use]
+pub fn deepest_allowed_mode() -> SleepMode {
+    let mut deepest = SleepMode::DeepSleep;
+    for (tokens, domain_deepest) in POWER_DOMAIN_TOKENS.iter().zip(POWER_DOMAIN_DEEPEST) {
+        if tokens.load(Ordering::Relaxed) != 0 {
+            deepest = deepest.min(domain_deepest);
+        }
+    }
+
+    // A time driver clocked from an APB clock would freeze in deep sleep, and lose track of time.
+    #[cfg(feature = "_time-driver")]
+    if crate::time_driver::uses_apb() {
+        deepest = deepest.min(SleepMode::Sleep);
+    }
+
+    deepest
+}
+
+/// What woke the chip from its last sleep.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeReason {
+    /// The Low-power Clock Timer alarm, see [crate::miwu::internal::LCT]
+    LctAlarm,
+    /// A `VCC1_RST#` power event, see [crate::miwu::internal::VCC1_RST]
+    Vcc1Reset,
+    /// A Power Switch Logic input, numbered 1 through 4, see [crate::miwu::internal::PSL_IN1]
+    PowerSwitch(u8),
+    /// Another MIWU input, like a GPIO pin or a host interface event.
+    ///
+    /// The group is 1-indexed, like the peripheral names, so `MIWU1_57` is `{ miwu: 1, group: 5, input: 7 }`.
+    WakeUpInput {
+        /// The MIWU, 0 through 2
+        miwu: u8,
+        /// The group, 1 through 8
+        group: u8,
+        /// The input within the group, 0 through 7
+        input: u8,
+    },
+    /// No MIWU input became pending, for example a peripheral interrupt while idle.
+    Other,
+}
+
+impl WakeReason {
+    fn from_wake_up_input(miwu: u8, group: u8, input: u8) -> Self {
+        match (miwu, group, input) {
+            (0, 4, 7) => WakeReason::LctAlarm,
+            (0, 7, 6) => WakeReason::Vcc1Reset,
+            (0, 7, 2) => WakeReason::PowerSwitch(1),
+            (1, 1, 0..=2) => WakeReason::PowerSwitch(input + 2),
+            _ => WakeReason::WakeUpInput { miwu, group, input },
+        }
+    }
+
+    /// The first MIWU input that became pending between two snapshots of the pending bits.
+    fn from_pending(before: [[u8; 8]; 3], after: [[u8; 8]; 3]) -> Self {
+        for (miwu, (before, after)) in before.iter().zip(after.iter()).enumerate() {
+            for (group, (before, after)) in before.iter().zip(after.iter()).enumerate() {
+                let new = after & !before;
+                if new != 0 {
+                    return Self::from_wake_up_input(miwu as u8, group as u8 + 1, new.trailing_zeros() as u8);
+                }
+            }
+        }
+
+        WakeReason::Other
+    }
+}
+
+/// The reason the chip woke from its last sleep
+static LAST_WAKE_REASON: critical_section::Mutex<Cell<Option<WakeReason>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// What woke the chip from its last sleep through [enter] or the low-power executor, or `None` if it has not slept.
+///
+/// Only inputs that became pending during the sleep are considered, and when several did, the lowest numbered one is
+/// reported.
+#[must_use]
+pub fn last_wake_reason() -> Option<WakeReason> {
+    critical_section::with(|cs| LAST_WAKE_REASON.borrow(cs).get())
+}
+
+/// A driver whose peripheral loses volatile configuration in deep sleep.
+///
+/// Register the hook with [register_suspend_hook] for as long as the driver lives. Deep sleep entry through [enter] or
+/// the low-power executor then calls [SuspendHook::suspend] of every registered hook before sleeping, and
+/// [SuspendHook::resume] in reverse order after waking.
+pub trait SuspendHook {
+    /// Save the volatile configuration and quiesce the peripheral, called with the `context` of the registration.
+    fn suspend(_context: u32) {}
+
+    /// Restore the configuration after waking, called with the `context` of the registration.
+    fn resume(context: u32);
+}
+
+/// The maximum number of [SuspendHook]s registered at the same time
+const MAX_SUSPEND_HOOKS: usize = 16;
+
+#[derive(Copy, Clone)]
+struct RegisteredSuspendHook {
+    key: usize,
+    suspend: fn(u32),
+    resume: fn(u32),
+    context: u32,
+}
+
+/// Drivers to suspend and resume around deep sleep
+static SUSPEND_HOOKS: critical_section::Mutex<RefCell<[Option<RegisteredSuspendHook>; MAX_SUSPEND_HOOKS]>> =
+    critical_section::Mutex::new(RefCell::new([None; MAX_SUSPEND_HOOKS]));
+
+/// Register `H` to be called with `context` around deep sleep.
+///
+/// The `key` identifies the driver instance, registering the same key again replaces its hook.
+/// Panics if too many hooks are registered.
+pub fn register_suspend_hook<H: SuspendHook>(key: usize, context: u32) {
+    critical_section::with(|cs| {
+        let mut hooks = SUSPEND_HOOKS.borrow_ref_mut(cs);
+        let slot = match hooks.iter().position(|h| h.is_some_and(|h| h.key == key)) {
+            Some(slot) => slot,
+            None => hooks
+                .iter()
+                .position(Option::is_none)
+                .expect("Too many drivers registered for suspend"),
+        };
+
+        hooks[slot] = Some(RegisteredSuspendHook {
+            key,
+            suspend: H::suspend,
+            resume: H::resume,
+            context,
+        });
+    });
+}
+
+/// Remove the hook registered with [register_suspend_hook] for `key`, if any.
+pub fn remove_suspend_hook(key: usize) {
+    critical_section::with(|cs| {
+        for slot in SUSPEND_HOOKS.borrow_ref_mut(cs).iter_mut() {
+            if slot.is_some_and(|h| h.key == key) {
+                *slot = None;
+            }
+        }
+    });
+}
+
+/// Keeps a hook registered with [register_suspend_hook] until dropped.
+pub(crate) struct SuspendRegistration {
+    key: usize,
+}
+
+impl SuspendRegistration {
+    pub(crate) fn new<H: SuspendHook>(key: usize, context: u32) -> Self {
+        register_suspend_hook::<H>(key, context);
+        Self { key }
+    }
+}
+
+impl Drop for SuspendRegistration {
+    fn drop(&mut self) {
+        remove_suspend_hook(self.key);
+    }
+}
+
+/// Timing of the last sleep, see [last_sleep_duration] and [last_wake_latency]
+#[cfg(feature = "time")]
+static LAST_SLEEP_TIMING: critical_section::Mutex<Cell<Option<(embassy_time::Duration, embassy_time::Duration)>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// How long the chip slept the last time, from programming the PMC until the core ran again, or `None` if it has not
+/// slept.
+///
+/// Measured with the time driver, so this is only meaningful if the time driver keeps running in the sleep mode, like
+/// a time driver clocked from the `LFCLK`.
+#[cfg(feature = "time")]
+#[must_use]
+pub fn last_sleep_duration() -> Option<embassy_time::Duration> {
+    critical_section::with(|cs| LAST_SLEEP_TIMING.borrow(cs).get()).map(|(sleep, _)| sleep)
+}
+
+/// How long it took the last time to get back to the caller after the core ran again, including the
+/// [SuspendHook::resume] of all registered drivers, or `None` if the chip has not slept.
+#[cfg(feature = "time")]
+#[must_use]
+pub fn last_wake_latency() -> Option<embassy_time::Duration> {
+    critical_section::with(|cs| LAST_SLEEP_TIMING.borrow(cs).get()).map(|(_, latency)| latency)
+}
+
+/// The number of times each [SleepMode] was entered
+#[cfg(feature = "debug")]
+static SLEEP_ENTRIES: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+
+/// Functions called around every sleep, see [set_sleep_hooks]
+#[cfg(feature = "debug")]
+static SLEEP_HOOKS: critical_section::Mutex<Cell<Option<(fn(SleepMode), fn(SleepMode))>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// The number of times each sleep mode was entered, see [sleep_stats].
+#[cfg(feature = "debug")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SleepStats {
+    /// Entries of [SleepMode::Idle]
+    pub idle: u32,
+    /// Entries of [SleepMode::Sleep]
+    pub sleep: u32,
+    /// Entries of [SleepMode::DeepSleepInstantWake]
+    pub deep_sleep_instant_wake: u32,
+    /// Entries of [SleepMode::DeepSleep]
+    pub deep_sleep: u32,
+}
+
+/// The number of times each sleep mode was entered through [enter] or the low-power executor.
+#[cfg(feature = "debug")]
+#[must_use]
+pub fn sleep_stats() -> SleepStats {
+    let [idle, sleep, deep_sleep_instant_wake, deep_sleep] =
+        core::array::from_fn(|mode| SLEEP_ENTRIES[mode].load(Ordering::Relaxed));
+    SleepStats {
+        idle,
+        sleep,
+        deep_sleep_instant_wake,
+        deep_sleep,
+    }
+}
+
+/// Reset the counters of [sleep_stats].
+#[cfg(feature = "debug")]
+pub fn reset_sleep_stats() {
+    for entries in SLEEP_ENTRIES.iter() {
+        entries.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Call `on_enter` right before and `on_exit` right after every sleep, with the mode that is entered.
+///
+/// For example toggle a GPIO in these, to correlate the current measured by a power analyzer with the sleep modes.
+/// They run with interrupts masked when called from [enter], so keep them short.
+#[cfg(feature = "debug")]
+pub fn set_sleep_hooks(on_enter: fn(SleepMode), on_exit: fn(SleepMode)) {
+    critical_section::with(|cs| SLEEP_HOOKS.borrow(cs).set(Some((on_enter, on_exit))));
+}
+
+/// Remove the functions set with [set_sleep_hooks].
+#[cfg(feature = "debug")]
+pub fn clear_sleep_hooks() {
+    critical_section::with(|cs| SLEEP_HOOKS.borrow(cs).set(None));
+}
+
+/// The size of the RAM blocks that are powered down by a single `RAM_PDn` bit
+pub const RAM_BLOCK_SIZE: usize = 32 * 1024;
+/// The address of the RAM block controlled by bit 0 of `RAM_PD0`, in the data alias of the RAM
+const RAM_BLOCK_BASE: usize = 0x2006_0000;
+
+/// Whether the `.noretain` RAM blocks are powered down in deep sleep
+static NORETAIN_POWER_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// The `RAM_PD1:RAM_PD0` bits of the RAM blocks that lie entirely within the `.noretain` section.
+///
+/// Place buffers that do not need to survive deep sleep in this section, to allow powering down their RAM:
+///
+/// ```rust,ignore
+/// #[repr(C, align(32768))]
+/// struct Block([u8; embassy_npcx::pmc::RAM_BLOCK_SIZE]);
+///
+/// #[link_section = ".noretain"]
+/// static mut FRAME_BUFFER: core::mem::MaybeUninit<[Block; 2]> = core::mem::MaybeUninit::uninit();
+/// ```
+///
+/// Only whole [RAM_BLOCK_SIZE] blocks can be powered down, so align the buffers in this section to the blocks.
+#[must_use]
+pub fn noretain_blocks() -> u16 {
+    extern "C" {
+        static __snoretain: u8;
+        static __enoretain: u8;
+    }
+
+    // Safety: only the addresses of the linker symbols are used.
+    let (start, end) = unsafe { (&raw const __snoretain as usize, &raw const __enoretain as usize) };
+
+    let first = (start - RAM_BLOCK_BASE).div_ceil(RAM_BLOCK_SIZE);
+    let end = (end - RAM_BLOCK_BASE) / RAM_BLOCK_SIZE;
+    (first..end).fold(0, |blocks, block| blocks | (1 << block))
+}
+
+/// Power down the RAM blocks of the `.noretain` section in deep sleep, see [noretain_blocks].
+///
+/// The contents of the `.noretain` section are lost on every deep sleep while this is enabled.
+pub fn set_noretain_power_down(enable: bool) {
+    NORETAIN_POWER_DOWN.store(enable, Ordering::Relaxed);
+}
+
+/// Power down the RAM `blocks`, or power all of them up again.
+fn set_ram_power_down(blocks: u16) {
+    // Safety:
+    // the pac ptr functions return pointers to memory that is used for registers for the 'static lifetime
+    // and the created reference is shared.
+    let pmc = unsafe { &*crate::pac::Pmc::ptr() };
+    pmc.ram_pd0().write(|w| unsafe { w.bits(blocks as u8) });
+    pmc.ram_pd1().write(|w| unsafe { w.bits((blocks >> 8) as u8) });
+}
+
+/// Whether the MIWU inputs disabled by the interrupt handler are re-enabled before sleeping
+static REARM_WAKE_INPUTS: AtomicBool = AtomicBool::new(false);
+
+/// Re-enable the MIWU inputs that were disabled by the interrupt handler before every sleep.
+///
+/// By default the interrupt handler disables an input once it triggers, see [crate::miwu], so a wake source armed once
+/// only wakes the chip once. With this enabled, every input enabled through its [crate::miwu::WakeUp] and not disabled
+/// through it since stays a wake source across sleeps. Inputs with their pending bit still set are only re-enabled once
+/// it is cleared, for example by awaiting the input or with [crate::miwu::WakeUp::clear_pending].
+pub fn set_rearm_wake_inputs(enable: bool) {
+    REARM_WAKE_INPUTS.store(enable, Ordering::Relaxed);
+}
+
+/// Wait for an interrupt with `wait` in the given mode, and record what woke the chip.
+pub(crate) fn sleep_with(mode: SleepMode, wait: impl FnOnce()) {
+    if REARM_WAKE_INPUTS.load(Ordering::Relaxed) {
+        crate::miwu::rearm();
+    }
+
+    let before = crate::miwu::pending_snapshot();
+
+    // Copy the hooks, so they can not be changed while they are running
+    let hooks = match mode >= SleepMode::DeepSleepInstantWake {
+        true => critical_section::with(|cs| *SUSPEND_HOOKS.borrow_ref(cs)),
+        false => [None; MAX_SUSPEND_HOOKS],
+    };
+
+    for hook in hooks.iter().flatten() {
+        (hook.suspend)(hook.context);
+    }
+
+    #[cfg(feature = "debug")]
+    let profiling_hooks = {
+        SLEEP_ENTRIES[mode as usize].fetch_add(1, Ordering::Relaxed);
+        let hooks = critical_section::with(|cs| SLEEP_HOOKS.borrow(cs).get());
+        if let Some((on_enter, _)) = hooks {
+            on_enter(mode);
+        }
+        hooks
+    };
+
+    #[cfg(feature = "time")]
+    let entry = embassy_time::Instant::now();
+
+    let ram_power_down = match mode >= SleepMode::DeepSleepInstantWake && NORETAIN_POWER_DOWN.load(Ordering::Relaxed) {
+        true => noretain_blocks(),
+        false => 0,
+    };
+    if ram_power_down != 0 {
+        set_ram_power_down(ram_power_down);
+    }
+
+    set_sleep_mode(mode);
+    wait();
+    set_sleep_mode(SleepMode::Idle);
+
+    if ram_power_down != 0 {
+        set_ram_power_down(0);
+    }
+
+    #[cfg(feature = "debug")]
+    if let Some((_, on_exit)) = profiling_hooks {
+        on_exit(mode);
+    }
+
+    #[cfg(feature = "time")]
+    let exit = embassy_time::Instant::now();
+
+    for hook in hooks.iter().rev().flatten() {
+        (hook.resume)(hook.context);
+    }
+
+    #[cfg(feature = "time")]
+    {
+        let timing = (exit - entry, exit.elapsed());
+        critical_section::with(|cs| LAST_SLEEP_TIMING.borrow(cs).set(Some(timing)));
+    }
+
+    let reason = WakeReason::from_pending(before, crate::miwu::pending_snapshot());
+    critical_section::with(|cs| LAST_WAKE_REASON.borrow(cs).set(Some(reason)));
+}
+
+/// Program the mode to enter on the next `WFI` or `WFE`.
+pub(crate) fn set_sleep_mode(mode: SleepMode) {
+    // Safety:
+    // the pac ptr functions return pointers to memory that is used for registers for the 'static lifetime
+    // and the created reference is shared.
+    let pmc = unsafe { &*crate::pac::Pmc::ptr() };
+    pmc.pmcsr().write(|w| unsafe { w.bits(mode.pmcsr()) });
+}
+
+/// Halt the core until an interrupt is pending, in the given power mode.
+///
+/// The mode is limited to [deepest_allowed_mode], so outstanding [PowerDomainToken]s are respected.
+///
+/// Interrupts are masked while the PMC is programmed, so the pending interrupt is only taken after the PMC is restored
+/// for normal operation.
+pub fn enter(mode: SleepMode) {
+    // Note(cs): an interrupt between programming the PMC and the WFI could itself wait for an interrupt.
+    // The WFI still returns on a pending interrupt while they are masked.
+    critical_section::with(|_cs| {
+        sleep_with(mode.min(deepest_allowed_mode()), || {
+            cortex_m::asm::dsb();
+            cortex_m::asm::wfi();
+        });
+    });
+}
+
+/// Halt the core in the deepest mode allowed by the outstanding [PowerDomainToken]s.
+pub fn enter_deepest() {
+    enter(deepest_allowed_mode());
+}
+
+/// Halt the core until an interrupt is pending. All clocks and peripherals keep running.
+pub fn enter_idle() {
+    enter(SleepMode::Idle);
+}
+
+/// Stop the core clock until an enabled MIWU input triggers.
+pub fn enter_sleep() {
+    enter(SleepMode::Sleep);
+}
+
+/// Stop the core and high frequency clocks until an enabled MIWU input triggers.
+///
+/// Everything clocked from the `APB` busses is frozen, including a time driver that does not run from the `LFCLK`.
+pub fn enter_deep_sleep() {
+    enter(SleepMode::DeepSleep);
+}
+
+/// `RSTCTL`: the last reset was a `VCC1_RST#` reset, cleared by writing 1
+const RSTCTL_VCC1_RST_STS: u8 = 1 << 0;
+/// `RSTCTL`: the last reset was a debugger or software (`SYSRESETREQ`) reset, cleared by writing 1
+const RSTCTL_DBGRST_STS: u8 = 1 << 1;
+/// `T0CSR`: the last reset was a watchdog reset, cleared by writing 1
+const T0CSR_WDRST_STS: u8 = 1 << 4;
+
+/// The kind of reset to trigger with [system_reset].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetType {
+    /// Reset the core and peripherals through the Cortex-M `SYSRESETREQ`, reported as [ResetCause::Software]
+    Core,
+    /// Let the watchdog expire, reported as [ResetCause::Watchdog]
+    Watchdog,
+}
+
+/// What caused the last reset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetCause {
+    /// `VSBY` was powered up, or no other cause was recorded
+    PowerOn,
+    /// The `VCC1_RST#` input was asserted
+    Vcc1Reset,
+    /// A debugger or [system_reset] with [ResetType::Core]
+    Software,
+    /// The watchdog expired
+    Watchdog,
+}
+
+/// The cause of the last reset, read once from the status registers
+static RESET_CAUSE: critical_section::Mutex<Cell<Option<ResetCause>>> = critical_section::Mutex::new(Cell::new(None));
+
+/// What caused the last reset.
+///
+/// The first call reads and clears the reset status registers, so the next reset is reported correctly. Later calls
+/// return the same cause.
+#[must_use]
+pub fn reset_cause() -> ResetCause {
+    critical_section::with(|cs| {
+        let cell = RESET_CAUSE.borrow(cs);
+        if let Some(cause) = cell.get() {
+            return cause;
+        }
+
+        // Safety: the status bits are only accessed here, within a critical section.
+        let sysconfig = unsafe { crate::pac::Sysconfig::steal() };
+        let twd = unsafe { crate::pac::Twd::steal() };
+
+        let rstctl = sysconfig.rstctl().read().bits();
+        let t0csr = twd.t0csr().read().bits();
+
+        let cause = if t0csr & T0CSR_WDRST_STS != 0 {
+            ResetCause::Watchdog
+        } else if rstctl & RSTCTL_DBGRST_STS != 0 {
+            ResetCause::Software
+        } else if rstctl & RSTCTL_VCC1_RST_STS != 0 {
+            ResetCause::Vcc1Reset
+        } else {
+            ResetCause::PowerOn
+        };
+
+        sysconfig
+            .rstctl()
+            .modify(|r, w| unsafe { w.bits(r.bits() | RSTCTL_VCC1_RST_STS | RSTCTL_DBGRST_STS) });
+        twd.t0csr().modify(|r, w| unsafe { w.bits(r.bits() | T0CSR_WDRST_STS) });
+
+        cell.set(Some(cause));
+        cause
+    })
+}
+
+/// Reset the chip.
+pub fn system_reset(reset: ResetType) -> ! {
+    match reset {
+        ResetType::Core => cortex_m::peripheral::SCB::sys_reset(),
+        ResetType::Watchdog => {
+            // Safety: the chip is reset, so any other user of the watchdog is irrelevant.
+            let twd = unsafe { crate::pac::Twd::steal() };
+
+            critical_section::with(|_| {
+                // Let the watchdog expire after a single tick of its prescaled clock.
+                twd.twcp().write(|w| unsafe { w.bits(0) });
+                twd.wdcnt().write(|w| unsafe { w.bits(1) });
+            });
+
+            loop {
+                cortex_m::asm::nop();
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Latency profiling with scoped stopwatches
+//!
+//! A [Stopwatch] measures the time from its creation until it is dropped with [timestamp](crate::timestamp) ticks, and
+//! adds it to the statistics of its label. [measure!](crate::measure) wraps a block in a stopwatch:
+//!
+//! ```rust,ignore
+//! let response = embassy_npcx::measure!("host command", { handle_command(request) });
+//!
+//! // Later, for example from a debug console command:
+//! embassy_npcx::profiling::dump();
+//! ```
+//!
+//! Up to [MAX_LABELS] labels are tracked, measurements of further labels are dropped.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::timestamp;
+
+/// The maximum number of labels that are tracked at once
+pub const MAX_LABELS: usize = 16;
+
+/// The statistics of one label, in [timestamp](crate::timestamp) ticks.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// The label passed to the [Stopwatch]
+    pub label: &'static str,
+    /// The number of measurements
+    pub count: u32,
+    /// The shortest measurement
+    pub min: u64,
+    /// The longest measurement
+    pub max: u64,
+    /// The sum of all measurements
+    pub total: u64,
+}
+
+impl Stats {
+    /// The average measurement.
+    #[must_use]
+    pub fn avg(&self) -> u64 {
+        self.total / u64::from(self.count.max(1))
+    }
+}
+
+static STATS: Mutex<RefCell<[Option<Stats>; MAX_LABELS]>> = Mutex::new(RefCell::new([None; MAX_LABELS]));
+
+/// Add a measurement of `ticks` to the statistics of `label`.
+pub fn record(label: &'static str, ticks: u64) {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+
+        if let Some(stats) = stats.iter_mut().flatten().find(|stats| stats.label == label) {
+            stats.count = stats.count.saturating_add(1);
+            stats.min = stats.min.min(ticks);
+            stats.max = stats.max.max(ticks);
+            stats.total = stats.total.saturating_add(ticks);
+        } else if let Some(slot) = stats.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(Stats {
+                label,
+                count: 1,
+                min: ticks,
+                max: ticks,
+                total: ticks,
+            });
+        }
+    });
+}
+
+/// The statistics of `label`, if it was measured.
+#[must_use]
+pub fn stats(label: &'static str) -> Option<Stats> {
+    critical_section::with(|cs| {
+        STATS
+            .borrow_ref(cs)
+            .iter()
+            .flatten()
+            .find(|stats| stats.label == label)
+            .copied()
+    })
+}
+
+/// The statistics of all labels, in the order they were first measured.
+pub fn all_stats() -> impl Iterator<Item = Stats> {
+    critical_section::with(|cs| *STATS.borrow_ref(cs)).into_iter().flatten()
+}
+
+/// Forget all measurements.
+pub fn reset() {
+    critical_section::with(|cs| *STATS.borrow_ref_mut(cs) = [None; MAX_LABELS]);
+}
+
+/// Log the statistics of all labels with `defmt`, in microseconds.
+#[cfg(feature = "defmt")]
+pub fn dump() {
+    for stats in all_stats() {
+        defmt::info!(
+            "{=str}: {} times, min {} us, avg {} us, max {} us",
+            stats.label,
+            stats.count,
+            timestamp::ticks_to_us(stats.min),
+            timestamp::ticks_to_us(stats.avg()),
+            timestamp::ticks_to_us(stats.max)
+        );
+    }
+}
+
+/// Measures the time until it is dropped, and records it under its label.
+#[must_use = "The stopwatch measures until it is dropped"]
+pub struct Stopwatch {
+    label: &'static str,
+    start: u64,
+}
+
+impl Stopwatch {
+    /// Start measuring for `label`.
+    pub fn start(label: &'static str) -> Self {
+        Self {
+            label,
+            start: timestamp::now_ticks(),
+        }
+    }
+
+    /// The ticks elapsed so far.
+    #[must_use]
+    pub fn elapsed(&self) -> u64 {
+        timestamp::elapsed_since(self.start)
+    }
+}
+
+impl Drop for Stopwatch {
+    fn drop(&mut self) {
+        record(self.label, self.elapsed());
+    }
+}
+
+/// Measure a block with a [Stopwatch](crate::profiling::Stopwatch) under `label`, and evaluate to its value.
+#[macro_export]
+macro_rules! measure {
+    ($label:expr, $body:block) => {{
+        let _stopwatch = $crate::profiling::Stopwatch::start($label);
+        $body
+    }};
+}
@@ -0,0 +1,237 @@
+//! Power Switch Logic (PSL)
+//!
+//! The PSL controls the `VCC1` supply of the chip with the `PSL_OUT` pin. From the PSL state only the PSL itself stays
+//! powered by `VSBY`, which draws far less than deep sleep. An event on one of the `PSL_IN` pins turns `VCC1` back on,
+//! after which the chip boots from reset. [power_up_source] tells which input caused this.
+//!
+//! While the chip is powered, the `PSL_IN` inputs can also wake it from sleep through the MIWU, see
+//! [crate::miwu::internal::PSL_IN1].
+
+use core::convert::Infallible;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::miwu::Level;
+use crate::pmc::{PowerWell, PowerWellError, PowerWellGuard};
+
+/// Pin that controls the `VCC1` supply, named `PSL_OUT` in the datasheet.
+pub type PslOutPin = crate::peripherals::PJ08;
+
+/// The four `PSL_IN` inputs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PslIn {
+    /// `PSL_IN1`, on GPIOD2
+    In1,
+    /// `PSL_IN2`, on GPIO00
+    In2,
+    /// `PSL_IN3`, on GPIO01
+    In3,
+    /// `PSL_IN4`, on GPIO02
+    In4,
+}
+
+impl PslIn {
+    const ALL: [PslIn; 4] = [PslIn::In1, PslIn::In2, PslIn::In3, PslIn::In4];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// How a `PSL_IN` input detects its event.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Detection {
+    /// The event is the input being at the active level
+    Level,
+    /// The event is the input changing to the active level
+    Edge,
+}
+
+/// Configuration of a [PslInput].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PslInputConfig {
+    /// The level at which the input is active
+    pub active: Level,
+    /// How the input detects its event
+    pub detection: Detection,
+}
+
+impl Default for PslInputConfig {
+    fn default() -> Self {
+        Self {
+            active: Level::Low,
+            detection: Detection::Edge,
+        }
+    }
+}
+
+mod sealed {
+    pub trait SealedPslInputPin {
+        const INPUT: super::PslIn;
+    }
+}
+
+/// A pin that can be used as a `PSL_IN` input.
+#[allow(private_bounds)]
+pub trait PslInputPin: sealed::SealedPslInputPin + crate::gpio::Pin {}
+
+macro_rules! impl_psl_input_pin {
+    ($($peripheral:ident => $input:ident,)*) => {
+        $(
+            impl sealed::SealedPslInputPin for crate::peripherals::$peripheral {
+                const INPUT: PslIn = PslIn::$input;
+            }
+            impl PslInputPin for crate::peripherals::$peripheral {}
+        )*
+    };
+}
+
+impl_psl_input_pin!(
+    PG07 => In1,
+    PE07 => In2,
+    PE06 => In3,
+    PF07 => In4,
+);
+
+/// Select the `PSL_IN` function on the pin of `input`, or give the pin back to the GPIO.
+fn select_input(input: PslIn, psl: bool, active_high: bool) {
+    // Note(cs): other peripherals might also be modifying the devaltd register at the same time.
+    critical_section::with(|_| {
+        // Safety: we own the pin, and only change its functions.
+        let sysconfig = unsafe { crate::pac::Sysconfig::steal() };
+        sysconfig.devaltd().modify(|_, w| match input {
+            PslIn::In1 => w.n_psl_in1_sl().bit(!psl).psl_in1_ahi().bit(active_high),
+            PslIn::In2 => w.n_psl_in2_sl().bit(!psl).psl_in2_ahi().bit(active_high),
+            PslIn::In3 => w.psl_in3_sl().bit(psl).psl_in3_ahi().bit(active_high),
+            PslIn::In4 => w.psl_in4_sl().bit(psl).psl_in4_ahi().bit(active_high),
+        });
+    });
+}
+
+fn glue() -> crate::pac::Sysglue {
+    // Safety: the PSL registers are only accessed by this module.
+    unsafe { crate::pac::Sysglue::steal() }
+}
+
+/// A pin configured as `PSL_IN` input, that turns `VCC1` back on when its event is detected.
+pub struct PslInput<'d> {
+    _pin: PeripheralRef<'d, crate::gpio::AnyPin>,
+    /// The `PSL_IN` pins are in the `VSBY` well
+    _well: PowerWellGuard,
+    input: PslIn,
+}
+
+impl<'d> PslInput<'d> {
+    /// Configure the pin as `PSL_IN` input.
+    pub fn new<P: PslInputPin>(pin: impl Peripheral<P = P> + 'd, config: PslInputConfig) -> Self {
+        into_ref!(pin);
+        let input = P::INPUT;
+
+        // Note(cs): the PSL_CTS register is shared between the inputs.
+        critical_section::with(|_| {
+            // The lower nibble holds the event status bits, which are cleared by writing 1.
+            glue().psl_cts().modify(|r, w| unsafe {
+                let mode = r.bits() & 0xF0;
+                let mode = match config.detection {
+                    Detection::Level => mode & !(input.bit() << 4),
+                    Detection::Edge => mode | (input.bit() << 4),
+                };
+                w.bits(mode)
+            });
+        });
+        select_input(input, true, config.active == Level::High);
+
+        Self {
+            _pin: pin.map_into(),
+            _well: PowerWellGuard::new(PowerWell::Vsby),
+            input,
+        }
+    }
+
+    /// The `PSL_IN` input of this pin.
+    #[must_use]
+    pub fn input(&self) -> PslIn {
+        self.input
+    }
+}
+
+impl Drop for PslInput<'_> {
+    fn drop(&mut self) {
+        select_input(self.input, false, false);
+    }
+}
+
+/// Controls the `VCC1` supply with the [PslOutPin].
+pub struct Psl<'d> {
+    _out: PeripheralRef<'d, PslOutPin>,
+    /// The PSL and its `PSL_OUT` pin are in the `VSBY` well
+    _well: PowerWellGuard,
+}
+
+impl<'d> Psl<'d> {
+    /// Select the `PSL_OUT` function on the pin, keeping `VCC1` on.
+    pub fn new(out: impl Peripheral<P = PslOutPin> + 'd) -> Self {
+        into_ref!(out);
+
+        // Note(cs): other peripherals might also be modifying the devaltg register at the same time.
+        critical_section::with(|_| {
+            // Safety: we own the pin, and only change its functions.
+            let sysconfig = unsafe { crate::pac::Sysconfig::steal() };
+            sysconfig.devaltg().modify(|_, w| w.psl_out_sl().set_bit());
+        });
+
+        Self {
+            _out: out,
+            _well: PowerWellGuard::new(PowerWell::Vsby),
+        }
+    }
+
+    /// Turn off `VCC1`, until one of the `inputs` detects its event.
+    ///
+    /// Any stale events are cleared first, as they would turn `VCC1` back on immediately. The chip boots from reset
+    /// when powered up again, so this only returns when the PSL state is refused because a driver still depends on
+    /// `VCC1`. Drop all drivers before entering the PSL state.
+    ///
+    /// Panics if no inputs are given, as the chip could then only be powered up by removing `VSBY`.
+    pub fn enter(&mut self, inputs: &[PslInput<'_>]) -> Result<Infallible, PowerWellError> {
+        assert!(
+            !inputs.is_empty(),
+            "At least one PSL_IN input is needed to power up again"
+        );
+
+        crate::pmc::check_power_down(&[PowerWell::Vcc1])?;
+
+        critical_section::with(|_| {
+            glue().psl_cts().modify(|r, w| unsafe { w.bits(r.bits() | 0x0F) });
+
+            // Safety: the PSL_OUT pin is owned by self, and only its data bit is changed.
+            let gpio8 = unsafe { &*crate::pac::Gpio8::ptr() };
+            gpio8.px_dout().modify(|_, w| w.pin(5).set_bit());
+        });
+
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+}
+
+/// The `PSL_IN` input that caused the most recent power-up, or `None` if the chip was not powered up by the PSL.
+///
+/// When several inputs detected their event, the lowest numbered one is reported. The events stay recorded until
+/// [clear_power_up_source] is called, or the PSL is entered again.
+#[must_use]
+pub fn power_up_source() -> Option<PslIn> {
+    let events = glue().psl_cts().read().bits() & 0x0F;
+    PslIn::ALL.into_iter().find(|input| events & input.bit() != 0)
+}
+
+/// Clear the events recorded by the PSL inputs.
+pub fn clear_power_up_source() {
+    // Note(cs): the PSL_CTS register is shared between the inputs.
+    critical_section::with(|_| {
+        glue().psl_cts().modify(|r, w| unsafe { w.bits(r.bits() | 0x0F) });
+    });
+}
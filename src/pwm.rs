@@ -0,0 +1,995 @@
+//! Pulse width modulation outputs on the PWM channels.
+//!
+//! Each of the eight PWM channels has its own 16-bit prescaler, cycle counter and duty cycle register, and drives a
+//! single output pin. A cycle lasts `period` ticks of the prescaled clock, of which the output is active for `duty`
+//! ticks, so the output frequency is `clock / ((prescaler + 1) * period)`. The channels count either the `APB2_CLK`
+//! or the `LFCLK`. [Pwm] implements the embedded-hal `SetDutyCycle` trait for generic LED and servo drivers,
+//! and [PwmGroup] updates several channels at once. With the `time` feature, [Pwm::ramp_to] soft-starts a load by
+//! stepping the duty cycle over time. [PwmConfig] derives the register values from an output frequency.
+//! [LowPowerPwm] keeps a fixed output running from the `LFCLK` while the chip is in deep sleep.
+//!
+//! [PwmInput] measures an incoming PWM signal with an MFT16 timer, for control loops that receive PWM requests.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::cdcg::{ClockChangeHook, ClockChangeRegistration};
+use crate::gpio::sealed::SealedPin as _;
+use crate::gpio::AnyPin;
+use crate::pmc::{Apb2, ClockGuard, PowerDomainToken, SuspendHook, SuspendRegistration};
+use crate::timer::capture::{DutyCycleCapture, MeasureError, PwmReading};
+use crate::timer::low_level::ClockConfig;
+use crate::timer::{CapturePin, MultiFunctionInstance};
+
+/// `PWMCTL`: invert the output, making it active-low
+const PWMCTL_INVP: u8 = 1 << 0;
+/// `PWMCTL`: clock the channel from the `LFCLK` instead of the `APB2_CLK`
+const PWMCTL_CKSEL: u8 = 1 << 1;
+/// `PWMCTL`: run the channel, while cleared the output is held at its inactive level
+const PWMCTL_PWR: u8 = 1 << 7;
+/// `PWMCTLEX`: drive the output as open-drain
+const PWMCTLEX_OD_OUT: u8 = 1 << 7;
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Clock source for PWM channels.
+pub enum ClockSource {
+    /// Prescaled APB2 clock. (The output is frozen in deep sleep)
+    #[default]
+    PrescaledAPB2Clock,
+    /// Prescaled LFCLK at 32KHz, which keeps running in deep sleep.
+    LowFrequencyClock,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Level of the output while the duty cycle is active.
+pub enum Polarity {
+    /// The output is high for `duty` ticks of each cycle.
+    #[default]
+    ActiveHigh,
+    /// The output is low for `duty` ticks of each cycle.
+    ActiveLow,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// How the output pin is driven.
+pub enum OutputType {
+    /// Drive both levels.
+    #[default]
+    PushPull,
+    /// Only drive the low level, and release the pin for the high level.
+    ///
+    /// With an external pullup this drives control lines at a higher voltage than the pin, like the 5V PWM input of a
+    /// fan, without an external transistor. Check in the datasheet that the ball tolerates the pullup voltage.
+    OpenDrain,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// What the output pin does while the channel is disabled, and after its driver is dropped.
+///
+/// A floating or wrongly parked control line can make a fan spin at full speed or stop, so pick the level the load
+/// on the pin fails safe with.
+pub enum DisabledLevel {
+    /// Drive the inactive level of the [Polarity].
+    #[default]
+    Inactive,
+    /// Keep driving the level the output had when it was disabled.
+    HoldLast,
+    /// Stop driving the pin, leaving the level to external pull resistors.
+    HighImpedance,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Configuration for a PWM channel.
+pub struct Config {
+    /// The clock the channel counts.
+    pub clock: ClockSource,
+    /// The clock is divided by `prescaler + 1`.
+    pub prescaler: u16,
+    /// The length of a cycle in prescaled clock ticks, at least 1.
+    pub period: u16,
+    /// The number of ticks per cycle the output is active, at most `period`.
+    pub duty: u16,
+    /// The level of the output while it is active.
+    pub polarity: Polarity,
+    /// How the output pin is driven.
+    pub output_type: OutputType,
+    /// What the output pin does while the channel is disabled.
+    pub disabled_level: DisabledLevel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clock: ClockSource::PrescaledAPB2Clock,
+            prescaler: 0,
+            period: u16::MAX,
+            duty: 0,
+            polarity: Polarity::ActiveHigh,
+            output_type: OutputType::PushPull,
+            disabled_level: DisabledLevel::Inactive,
+        }
+    }
+}
+
+impl Config {
+    /// The frequency in Hz at which a channel with this configuration counts.
+    pub fn tick_frequency(&self) -> u32 {
+        let clock_hz = match self.clock {
+            ClockSource::PrescaledAPB2Clock => crate::cdcg::apb2_hz(),
+            ClockSource::LowFrequencyClock => crate::cdcg::lfclk_hz(),
+        };
+
+        clock_hz / (u32::from(self.prescaler) + 1)
+    }
+
+    /// The frequency in Hz of the output cycle of a channel with this configuration.
+    pub fn frequency(&self) -> u32 {
+        self.tick_frequency() / u32::from(self.period.max(1))
+    }
+}
+
+/// A reason a [PwmConfig] cannot be turned into a [Config] by [PwmConfig::build]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The frequency is higher than the clock source, or too high for the requested resolution
+    FrequencyTooHigh,
+    /// The frequency is 0, or too low to reach even with the largest prescaler and period
+    FrequencyTooLow,
+    /// The requested resolution is 0
+    ResolutionZero,
+}
+
+/// A builder for a PWM [Config] from an output frequency, instead of prescaler and period register values.
+///
+/// [PwmConfig::build] picks the smallest prescaler that reaches the frequency, which gives the longest period and so
+/// the finest duty cycle steps the clock source allows.
+///
+/// ```rust,ignore
+/// let config = pwm::PwmConfig::new(25_000).with_resolution(1000).build()?;
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmConfig {
+    /// The frequency in Hz of the output cycle
+    pub frequency: u32,
+    /// The minimum number of duty cycle steps per cycle, the period is at least this many ticks
+    pub resolution: u16,
+    /// The clock the channel counts
+    pub clock: ClockSource,
+    /// The level of the output while it is active
+    pub polarity: Polarity,
+    /// How the output pin is driven
+    pub output_type: OutputType,
+}
+
+impl PwmConfig {
+    /// A configuration for the given output frequency in Hz, counting the APB2 clock without a minimum resolution.
+    pub const fn new(frequency: u32) -> Self {
+        Self {
+            frequency,
+            resolution: 1,
+            clock: ClockSource::PrescaledAPB2Clock,
+            polarity: Polarity::ActiveHigh,
+            output_type: OutputType::PushPull,
+        }
+    }
+
+    /// Set the [PwmConfig::resolution].
+    #[must_use]
+    pub const fn with_resolution(mut self, resolution: u16) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Set the [PwmConfig::clock].
+    #[must_use]
+    pub const fn with_clock(mut self, clock: ClockSource) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the [PwmConfig::polarity].
+    #[must_use]
+    pub const fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Set the [PwmConfig::output_type].
+    #[must_use]
+    pub const fn with_output_type(mut self, output_type: OutputType) -> Self {
+        self.output_type = output_type;
+        self
+    }
+
+    /// Compute the prescaler and period for the current frequency of the clock source.
+    ///
+    /// The frequency is rounded to the nearest one the clock source can reach, check [Config::frequency] for the
+    /// exact value. The duty cycle of the returned configuration is 0.
+    pub fn build(&self) -> Result<Config, ConfigError> {
+        if self.resolution == 0 {
+            return Err(ConfigError::ResolutionZero);
+        }
+        if self.frequency == 0 {
+            return Err(ConfigError::FrequencyTooLow);
+        }
+
+        let clock_hz = match self.clock {
+            ClockSource::PrescaledAPB2Clock => crate::cdcg::apb2_hz(),
+            ClockSource::LowFrequencyClock => crate::cdcg::lfclk_hz(),
+        };
+
+        // The number of clock ticks per cycle, to be split into prescaler and period
+        let total = (u64::from(clock_hz) + u64::from(self.frequency) / 2) / u64::from(self.frequency);
+        if total == 0 {
+            return Err(ConfigError::FrequencyTooHigh);
+        }
+
+        let divider = total.div_ceil(u64::from(u16::MAX));
+        if divider > u64::from(u16::MAX) + 1 {
+            return Err(ConfigError::FrequencyTooLow);
+        }
+
+        let period = (total + divider / 2) / divider;
+        if period < u64::from(self.resolution) {
+            return Err(ConfigError::FrequencyTooHigh);
+        }
+
+        Ok(Config {
+            clock: self.clock,
+            prescaler: (divider - 1) as u16,
+            period: period.min(u64::from(u16::MAX)) as u16,
+            duty: 0,
+            polarity: self.polarity,
+            output_type: self.output_type,
+            disabled_level: DisabledLevel::Inactive,
+        })
+    }
+}
+
+impl TryFrom<PwmConfig> for Config {
+    type Error = ConfigError;
+
+    fn try_from(config: PwmConfig) -> Result<Self, Self::Error> {
+        config.build()
+    }
+}
+
+mod sealed {
+    #[allow(private_bounds)]
+    pub trait SealedInstance: crate::pmc::ClockGated {
+        fn regs() -> &'static crate::pac::pwm0::RegisterBlock;
+        fn suspended() -> &'static critical_section::Mutex<core::cell::Cell<crate::pwm::Suspended>>;
+    }
+
+    pub trait SealedChannel {
+        /// Set the duty cycle in ticks, clamped to the period, and write it to the channel.
+        fn stage_duty(&mut self, duty: u16);
+
+        /// Stop the counter of the channel without changing its state.
+        fn halt(&mut self);
+
+        /// Start the counter of the channel again if its state says it runs.
+        fn resume(&mut self);
+    }
+
+    pub trait SealedPin {
+        /// Route the PWM output to this pin.
+        ///
+        /// # Safety
+        /// The caller must own the pin.
+        unsafe fn setup(cs: critical_section::CriticalSection);
+    }
+}
+
+/// A marker trait implemented by all PWM channels.
+pub trait Instance: sealed::SealedInstance + Peripheral<P = Self> + 'static {}
+
+/// A marker trait implemented by all pins that can output a PWM channel.
+pub trait OutputPin: sealed::SealedPin + crate::gpio::Pin {
+    /// The PWM channel this pin can output
+    type Instance: Instance;
+}
+
+macro_rules! impl_instance {
+    ($instance:ident, $pac:ident) => {
+        impl sealed::SealedInstance for crate::peripherals::$instance {
+            fn regs() -> &'static crate::pac::pwm0::RegisterBlock {
+                // Safety: not owned, memory is always present
+                unsafe { &*crate::pac::$pac::PTR }
+            }
+
+            fn suspended() -> &'static critical_section::Mutex<Cell<Suspended>> {
+                static SUSPENDED: critical_section::Mutex<Cell<Suspended>> =
+                    critical_section::Mutex::new(Cell::new(Suspended {
+                        prsc: 0,
+                        ctr: 0,
+                        dcr: 0,
+                        pwmctl: 0,
+                        pwmctlex: 0,
+                    }));
+                &SUSPENDED
+            }
+        }
+
+        impl Instance for crate::peripherals::$instance {}
+    };
+}
+
+macro_rules! impl_pin {
+    ($instance:ident, $pin:ident, $pin_config:expr) => {
+        impl sealed::SealedPin for crate::peripherals::$pin {
+            unsafe fn setup(_cs: critical_section::CriticalSection) {
+                fn internal_set(f: impl FnOnce(crate::pac::Sysconfig)) {
+                    f(unsafe { crate::pac::Sysconfig::steal() });
+                }
+                internal_set($pin_config);
+            }
+        }
+
+        impl OutputPin for crate::peripherals::$pin {
+            type Instance = crate::peripherals::$instance;
+        }
+    };
+}
+
+/// The registers of a PWM channel, saved while suspended
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Suspended {
+    prsc: u16,
+    ctr: u16,
+    dcr: u16,
+    pwmctl: u8,
+    pwmctlex: u8,
+}
+
+impl Suspended {
+    fn read(r: &crate::pac::pwm0::RegisterBlock) -> Self {
+        Self {
+            prsc: r.prscn().read().bits(),
+            ctr: r.ctrn().read().bits(),
+            dcr: r.dcrn().read().bits(),
+            pwmctl: r.pwmctln().read().bits(),
+            pwmctlex: r.pwmctlexn().read().bits(),
+        }
+    }
+}
+
+/// Re-tunes PWM channel `T` after the clocks changed, and restores its registers after deep sleep.
+///
+/// The context is the tick frequency of a channel counting the `APB2_CLK`, or 0 for a channel counting the `LFCLK`.
+struct Suspend<T>(PhantomData<T>);
+
+impl<T: Instance> ClockChangeHook for Suspend<T> {
+    fn retune(tick_hz: u32) {
+        if tick_hz == 0 {
+            return;
+        }
+
+        // Keep the tick frequency, so the period and duty cycle in ticks keep the output frequency and duty cycle.
+        let divider = (crate::cdcg::apb2_hz() + tick_hz / 2) / tick_hz;
+        let prescaler = (divider.clamp(1, u32::from(u16::MAX) + 1) - 1) as u16;
+        T::regs().prscn().write(|w| unsafe { w.bits(prescaler) });
+    }
+}
+
+impl<T: Instance> SuspendHook for Suspend<T> {
+    fn suspend(_tick_hz: u32) {
+        let saved = Suspended::read(T::regs());
+        critical_section::with(|cs| T::suspended().borrow(cs).set(saved));
+    }
+
+    fn resume(tick_hz: u32) {
+        let r = T::regs();
+        let saved = critical_section::with(|cs| T::suspended().borrow(cs).get());
+
+        // A channel on the `LFCLK` runs on through deep sleep, only write the registers if they lost their values, so
+        // its output is not restarted.
+        if Suspended::read(r) != saved {
+            r.pwmctln().write(|w| unsafe { w.bits(saved.pwmctl & !PWMCTL_PWR) });
+            r.prscn().write(|w| unsafe { w.bits(saved.prsc) });
+            r.ctrn().write(|w| unsafe { w.bits(saved.ctr) });
+            r.dcrn().write(|w| unsafe { w.bits(saved.dcr) });
+            r.pwmctlexn().write(|w| unsafe { w.bits(saved.pwmctlex) });
+            r.pwmctln().write(|w| unsafe { w.bits(saved.pwmctl) });
+        }
+
+        <Self as ClockChangeHook>::retune(tick_hz);
+    }
+}
+
+/// A PWM output on a single channel.
+///
+/// While the channel runs from the APB2 clock, the chip does not sleep deeper than that clock allows. When the
+/// `APB2_CLK` changes, the prescaler is recomputed for the tick frequency the channel was created with, so the output
+/// frequency only changes by the rounding of the prescaler, and the period and duty cycle in ticks are kept.
+pub struct Pwm<'d, T: Instance> {
+    _instance: PeripheralRef<'d, T>,
+    _clock: ClockGuard,
+    _retune: ClockChangeRegistration,
+    _suspend: SuspendRegistration,
+    pin: PeripheralRef<'d, AnyPin>,
+    /// Routes the PWM output to the pin
+    pin_setup: unsafe fn(critical_section::CriticalSection),
+    /// Whether the pin is muxed to the PWM output, instead of parked as a GPIO
+    muxed: bool,
+    config: Config,
+    enabled: bool,
+    domain: Option<PowerDomainToken<Apb2>>,
+}
+
+impl<'d, T: Instance> Pwm<'d, T> {
+    /// Instantiate the PWM driver for this channel and output pin, and start the output.
+    ///
+    /// # Panics
+    /// If `config.period` is 0 or `config.duty` exceeds it.
+    pub fn new<P: OutputPin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = P> + 'd,
+        config: Config,
+    ) -> Self {
+        assert!(config.period > 0, "A PWM cycle cannot be 0 ticks long");
+        assert!(
+            config.duty <= config.period,
+            "The PWM duty cycle cannot exceed the period"
+        );
+
+        let tick_hz = match config.clock {
+            ClockSource::PrescaledAPB2Clock => config.tick_frequency(),
+            ClockSource::LowFrequencyClock => 0,
+        };
+
+        into_ref!(instance, pin);
+        let mut this = Self {
+            _instance: instance,
+            _clock: ClockGuard::new::<T>(),
+            _retune: ClockChangeRegistration::new::<Suspend<T>>(T::regs() as *const _ as usize, tick_hz),
+            _suspend: SuspendRegistration::new::<Suspend<T>>(T::regs() as *const _ as usize, tick_hz),
+            pin: pin.map_into(),
+            pin_setup: P::setup,
+            muxed: false,
+            config,
+            enabled: false,
+            domain: None,
+        };
+
+        let r = T::regs();
+        r.pwmctln().write(|w| unsafe { w.bits(this.pwmctl()) });
+        r.prscn().write(|w| unsafe { w.bits(config.prescaler) });
+        r.ctrn().write(|w| unsafe { w.bits(config.period - 1) });
+
+        let od_out = match config.output_type {
+            OutputType::PushPull => 0,
+            OutputType::OpenDrain => PWMCTLEX_OD_OUT,
+        };
+        r.pwmctlexn()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !PWMCTLEX_OD_OUT) | od_out) });
+
+        this.enable();
+        this
+    }
+
+    /// The `PWMCTL` value for the configuration, with the channel stopped.
+    fn pwmctl(&self) -> u8 {
+        let invp = match self.config.polarity {
+            Polarity::ActiveHigh => 0,
+            Polarity::ActiveLow => PWMCTL_INVP,
+        };
+        let cksel = match self.config.clock {
+            ClockSource::PrescaledAPB2Clock => 0,
+            ClockSource::LowFrequencyClock => PWMCTL_CKSEL,
+        };
+
+        invp | cksel
+    }
+
+    /// Write the duty cycle, and run the channel only while the output is enabled and active for some of the cycle.
+    fn apply_duty(&mut self) {
+        let r = T::regs();
+        let run = self.enabled && self.config.duty > 0;
+        if run {
+            r.dcrn().write(|w| unsafe { w.bits(self.config.duty - 1) });
+        }
+
+        if run && self.config.clock == ClockSource::PrescaledAPB2Clock {
+            self.domain.get_or_insert_with(PowerDomainToken::new);
+        } else {
+            self.domain = None;
+        }
+
+        let pwmctl = self.pwmctl() | if run { PWMCTL_PWR } else { 0 };
+        r.pwmctln().write(|w| unsafe { w.bits(pwmctl) });
+    }
+
+    /// The configuration the channel currently runs with, including a prescaler recomputed after a clock change.
+    #[must_use]
+    pub fn config(&self) -> Config {
+        Config {
+            prescaler: T::regs().prscn().read().bits(),
+            ..self.config
+        }
+    }
+
+    /// The frequency in Hz of the output cycle.
+    #[must_use]
+    pub fn frequency(&self) -> u32 {
+        self.config().frequency()
+    }
+
+    /// The length of a cycle in prescaled clock ticks.
+    #[must_use]
+    pub fn period(&self) -> u16 {
+        self.config.period
+    }
+
+    /// The number of ticks per cycle the output is active.
+    #[must_use]
+    pub fn duty(&self) -> u16 {
+        self.config.duty
+    }
+
+    /// Set the number of ticks per cycle the output is active, clamped to the period.
+    ///
+    /// The new duty cycle takes effect at the start of the next cycle. A duty cycle of 0 holds the output at its
+    /// inactive level.
+    pub fn set_duty(&mut self, duty: u16) {
+        self.config.duty = duty.min(self.config.period);
+        self.apply_duty();
+    }
+
+    /// Step the duty cycle linearly from its current value to `duty` over `duration`, for example to soft-start a fan.
+    ///
+    /// The duty cycle changes by one tick per step, but at most once per millisecond. The target is clamped to the
+    /// period, as in [Pwm::set_duty]. If the future is dropped before it completes, the duty cycle stays at the value
+    /// of the last step.
+    #[cfg(feature = "time")]
+    pub async fn ramp_to(&mut self, duty: u16, duration: embassy_time::Duration) {
+        let start = i64::from(self.config.duty);
+        let delta = i64::from(duty.min(self.config.period)) - start;
+
+        let steps = delta.unsigned_abs().min(duration.as_millis());
+        if steps == 0 {
+            embassy_time::Timer::after(duration).await;
+            self.set_duty(duty);
+            return;
+        }
+
+        // steps is at most u16::MAX, the largest possible change of the duty cycle
+        let mut ticker = embassy_time::Ticker::every(duration / steps as u32);
+        for step in 1..=steps as i64 {
+            ticker.next().await;
+            self.set_duty((start + delta * step / steps as i64) as u16);
+        }
+    }
+
+    /// Set the length of a cycle in prescaled clock ticks, keeping the duty cycle in ticks.
+    ///
+    /// A duty cycle longer than the new period is clamped to it, making the output active for the whole cycle.
+    ///
+    /// # Panics
+    /// If `period` is 0.
+    pub fn set_period(&mut self, period: u16) {
+        assert!(period > 0, "A PWM cycle cannot be 0 ticks long");
+
+        self.config.period = period;
+        self.config.duty = self.config.duty.min(period);
+        T::regs().ctrn().write(|w| unsafe { w.bits(period - 1) });
+        self.apply_duty();
+    }
+
+    /// Start the output with the current period and duty cycle.
+    pub fn enable(&mut self) {
+        self.enabled = true;
+        self.apply_duty();
+
+        if !self.muxed {
+            // Note(cs): other peripherals might also be modifying devalt* at the same time.
+            critical_section::with(|cs| {
+                // Safety: We have exclusive ownership over the pin.
+                unsafe { (self.pin_setup)(cs) };
+            });
+            self.muxed = true;
+        }
+    }
+
+    /// Stop the output, leaving the pin as set by [Config::disabled_level].
+    pub fn disable(&mut self) {
+        if self.muxed && self.config.disabled_level != DisabledLevel::Inactive {
+            // Note(cs): other peripherals might also be modifying devalt* at the same time.
+            critical_section::with(|cs| {
+                let regs = self.pin.port();
+                let pin = self.pin.pin();
+
+                if self.config.disabled_level == DisabledLevel::HoldLast {
+                    let level = regs.px_din().read().pin(pin).is_high();
+                    regs.px_dout().modify(|_, w| w.pin(pin).bit(level));
+                    match self.config.output_type {
+                        OutputType::PushPull => regs.px_otype().modify(|_, w| w.pin(pin).pushpull()),
+                        OutputType::OpenDrain => regs.px_otype().modify(|_, w| w.pin(pin).opendrain()),
+                    }
+                    regs.px_dir().modify(|_, w| w.pin(pin).output());
+                } else {
+                    regs.px_dir().modify(|_, w| w.pin(pin).input());
+                }
+
+                // Safety: We have exclusive ownership over the pin.
+                unsafe { self.pin.set_pin_function(cs) };
+            });
+            self.muxed = false;
+        }
+
+        self.enabled = false;
+        self.apply_duty();
+    }
+
+    /// Set what the output pin does while the channel is disabled, and after the driver is dropped.
+    pub fn set_disabled_level(&mut self, level: DisabledLevel) {
+        self.config.disabled_level = level;
+    }
+
+    /// Whether the output is enabled.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl<T: Instance> Drop for Pwm<'_, T> {
+    fn drop(&mut self) {
+        self.disable();
+    }
+}
+
+impl<T: Instance> embedded_hal::pwm::ErrorType for Pwm<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+/// The duty cycle is counted in ticks, so the maximum duty cycle is the period and the resolution of the output is
+/// set by [Config::period].
+impl<T: Instance> embedded_hal::pwm::SetDutyCycle for Pwm<'_, T> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.period()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.set_duty(duty);
+        Ok(())
+    }
+}
+
+impl<T: Instance> sealed::SealedChannel for Pwm<'_, T> {
+    fn stage_duty(&mut self, duty: u16) {
+        self.set_duty(duty);
+    }
+
+    fn halt(&mut self) {
+        T::regs().pwmctln().write(|w| unsafe { w.bits(self.pwmctl()) });
+    }
+
+    fn resume(&mut self) {
+        self.apply_duty();
+    }
+}
+
+/// A PWM channel driver that can be part of a [PwmGroup].
+pub trait Channel: sealed::SealedChannel {}
+
+impl<T: Instance> Channel for Pwm<'_, T> {}
+
+/// Several PWM channels whose duty cycles are updated together.
+///
+/// Writing the duty cycles of the channels one by one lets some of them start a cycle with the new duty cycle while
+/// others still use the old one, which shows as a color glitch on an RGB LED. A group writes all duty cycles within one
+/// critical section, and restarts the channels together when it is created, so channels with the same period also
+/// start their cycles at the same time.
+///
+/// The update is not glitch-free: the channels have no shared latch, and each picks up its new duty cycle at its own
+/// next cycle boundary. When a boundary falls in the few microseconds between writing the first and the last channel,
+/// the channels written after it run one more cycle with their old duty cycle.
+pub struct PwmGroup<'a, const N: usize> {
+    channels: [&'a mut dyn Channel; N],
+}
+
+impl<'a, const N: usize> PwmGroup<'a, N> {
+    /// Group the channels, restarting them so their cycles are aligned.
+    pub fn new(channels: [&'a mut dyn Channel; N]) -> Self {
+        let mut this = Self { channels };
+        this.synchronize();
+        this
+    }
+
+    /// Restart all enabled channels at the same time, aligning the start of their cycles.
+    ///
+    /// Each output restarts with a new cycle, so the current cycles are cut short.
+    pub fn synchronize(&mut self) {
+        // Note(cs): the channels are stopped and started again without other code running in between.
+        critical_section::with(|_| {
+            for channel in self.channels.iter_mut() {
+                channel.halt();
+            }
+            for channel in self.channels.iter_mut() {
+                channel.resume();
+            }
+        });
+    }
+
+    /// Set the duty cycles of all channels in ticks, in the order the channels were grouped.
+    ///
+    /// Each duty cycle is clamped to the period of its channel, as in [Pwm::set_duty]. The channels can disagree for
+    /// one cycle, see [PwmGroup].
+    pub fn set_duties(&mut self, duties: [u16; N]) {
+        // Note(cs): all duty cycle registers are written before the next cycle boundary of the channels.
+        critical_section::with(|_| {
+            for (channel, duty) in self.channels.iter_mut().zip(duties) {
+                channel.stage_duty(duty);
+            }
+        });
+    }
+
+    /// Release the channels.
+    pub fn into_inner(self) -> [&'a mut dyn Channel; N] {
+        self.channels
+    }
+}
+
+/// Configuration for a [LowPowerPwm].
+///
+/// The `LFCLK` is much slower than the `APB2_CLK`, so a channel counting it only reaches useful output frequencies
+/// with short periods. The period is limited to 8 bits, which gives at most 255 duty cycle steps, at an output of
+/// about 128 Hz from the undivided `LFCLK`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LowPowerConfig {
+    /// The `LFCLK` is divided by `prescaler + 1`.
+    pub prescaler: u16,
+    /// The length of a cycle in prescaled `LFCLK` ticks, at least 1.
+    pub period: u8,
+    /// The number of ticks per cycle the output is active, at most `period`.
+    pub duty: u8,
+    /// The level of the output while it is active.
+    pub polarity: Polarity,
+    /// How the output pin is driven.
+    pub output_type: OutputType,
+    /// What the output pin does while the channel is disabled.
+    pub disabled_level: DisabledLevel,
+}
+
+impl Default for LowPowerConfig {
+    fn default() -> Self {
+        Self {
+            prescaler: 0,
+            period: u8::MAX,
+            duty: 0,
+            polarity: Polarity::ActiveHigh,
+            output_type: OutputType::PushPull,
+            disabled_level: DisabledLevel::Inactive,
+        }
+    }
+}
+
+impl From<LowPowerConfig> for Config {
+    fn from(config: LowPowerConfig) -> Self {
+        Self {
+            clock: ClockSource::LowFrequencyClock,
+            prescaler: config.prescaler,
+            period: u16::from(config.period),
+            duty: u16::from(config.duty),
+            polarity: config.polarity,
+            output_type: config.output_type,
+            disabled_level: config.disabled_level,
+        }
+    }
+}
+
+/// A PWM output counting the `LFCLK`, which keeps running while the chip is in deep sleep.
+///
+/// Unlike a [Pwm] on the APB2 clock, this does not keep the chip out of deep sleep, so a fan keeps spinning at a fixed
+/// speed while the EC sleeps. The period and duty cycle are 8-bit, see [LowPowerConfig].
+///
+/// Only the bus clock of the PWM registers stops in deep sleep, the channel counts on and keeps its configuration.
+/// Like every [Pwm], its registers are saved before deep sleep, but they are only written back after waking if they
+/// lost their values, so the output runs on undisturbed. A clock change does not touch the prescaler of a channel on
+/// the `LFCLK`. Writing its registers needs the `APB2_CLK`, so the duty cycle can only be changed while the chip is
+/// awake.
+pub struct LowPowerPwm<'d, T: Instance> {
+    pwm: Pwm<'d, T>,
+}
+
+impl<'d, T: Instance> LowPowerPwm<'d, T> {
+    /// Instantiate the PWM driver for this channel and output pin on the `LFCLK`, and start the output.
+    ///
+    /// # Panics
+    /// If `config.period` is 0 or `config.duty` exceeds it.
+    pub fn new<P: OutputPin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = P> + 'd,
+        config: LowPowerConfig,
+    ) -> Self {
+        Self {
+            pwm: Pwm::new(instance, pin, config.into()),
+        }
+    }
+
+    /// The frequency in Hz of the output cycle.
+    #[must_use]
+    pub fn frequency(&self) -> u32 {
+        self.pwm.frequency()
+    }
+
+    /// The length of a cycle in prescaled `LFCLK` ticks.
+    #[must_use]
+    pub fn period(&self) -> u8 {
+        self.pwm.period() as u8
+    }
+
+    /// The number of ticks per cycle the output is active.
+    #[must_use]
+    pub fn duty(&self) -> u8 {
+        self.pwm.duty() as u8
+    }
+
+    /// Set the number of ticks per cycle the output is active, clamped to the period.
+    ///
+    /// See [Pwm::set_duty].
+    pub fn set_duty(&mut self, duty: u8) {
+        self.pwm.set_duty(u16::from(duty));
+    }
+
+    /// Set the length of a cycle in prescaled `LFCLK` ticks, keeping the duty cycle in ticks.
+    ///
+    /// See [Pwm::set_period].
+    ///
+    /// # Panics
+    /// If `period` is 0.
+    pub fn set_period(&mut self, period: u8) {
+        self.pwm.set_period(u16::from(period));
+    }
+
+    /// Start the output with the current period and duty cycle.
+    pub fn enable(&mut self) {
+        self.pwm.enable();
+    }
+
+    /// Stop the output, leaving the pin as set by [LowPowerConfig::disabled_level].
+    pub fn disable(&mut self) {
+        self.pwm.disable();
+    }
+
+    /// Whether the output is enabled.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.pwm.is_enabled()
+    }
+}
+
+impl<T: Instance> embedded_hal::pwm::ErrorType for LowPowerPwm<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+impl<T: Instance> embedded_hal::pwm::SetDutyCycle for LowPowerPwm<'_, T> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.pwm.period()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.pwm.set_duty(duty);
+        Ok(())
+    }
+}
+
+impl<T: Instance> sealed::SealedChannel for LowPowerPwm<'_, T> {
+    fn stage_duty(&mut self, duty: u16) {
+        self.pwm.stage_duty(duty);
+    }
+
+    fn halt(&mut self) {
+        self.pwm.halt();
+    }
+
+    fn resume(&mut self) {
+        self.pwm.resume();
+    }
+}
+
+impl<T: Instance> Channel for LowPowerPwm<'_, T> {}
+
+/// A reading of an incoming PWM signal by [PwmInput].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmInputReading {
+    /// The frequency of the signal in Hz, 0 for a constant level
+    pub frequency_hz: u32,
+    /// The duty cycle of the signal in tenths of a percent, 0 or 1000 for a constant level
+    pub duty_permille: u16,
+}
+
+/// Measures an incoming PWM signal on the `TAn` input of an MFT16, like a fan speed request from the host.
+///
+/// This routes the pin to the MFT16 and measures with a [DutyCycleCapture]. A signal that does not toggle within 65536
+/// ticks of the counter clock is read as a constant level, which is how 0% and 100% duty cycle requests arrive, so
+/// choose the clock for the slowest expected signal.
+pub struct PwmInput<'d, T: MultiFunctionInstance> {
+    capture: DutyCycleCapture<'d, T>,
+}
+
+impl<'d, T: MultiFunctionInstance> PwmInput<'d, T> {
+    /// Instantiate the PWM input on this MFT16 and its `TAn` pin, counting `clk`.
+    pub fn new<P: CapturePin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = P> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, crate::timer::low_level::InterruptHandler<T>>,
+        clk: ClockConfig,
+    ) -> Self {
+        Self {
+            capture: DutyCycleCapture::new(instance, pin, irqs, clk),
+        }
+    }
+
+    /// Measure the frequency and duty cycle of the next full period of the signal.
+    pub async fn read(&mut self) -> PwmInputReading {
+        match self.capture.measure().await {
+            Ok(reading) => PwmInputReading {
+                frequency_hz: reading.frequency_hz(),
+                duty_permille: reading.duty_permille().min(1000),
+            },
+            Err(MeasureError::NoSignal) => {
+                let pin = self.capture.pin();
+                let high = pin.port().px_din().read().pin(pin.pin()).is_high();
+                PwmInputReading {
+                    frequency_hz: 0,
+                    duty_permille: if high { 1000 } else { 0 },
+                }
+            }
+        }
+    }
+
+    /// Measure the next full period of the signal in counter ticks, failing if the level is constant.
+    pub async fn read_raw(&mut self) -> Result<PwmReading, MeasureError> {
+        self.capture.measure().await
+    }
+}
+
+impl_instance!(PWM0, Pwm0);
+impl_instance!(PWM1, Pwm1);
+impl_instance!(PWM2, Pwm2);
+impl_instance!(PWM3, Pwm3);
+impl_instance!(PWM4, Pwm4);
+impl_instance!(PWM5, Pwm5);
+impl_instance!(PWM6, Pwm6);
+impl_instance!(PWM7, Pwm7);
+
+impl_pin!(PWM0, PG09, |config| {
+    config.devalt4().modify(|_, w| w.pwm0_sl().set_bit());
+});
+impl_pin!(PWM1, PH10, |config| {
+    config.devalt2().modify(|_, w| w.i2c6_0_sl().clear_bit());
+    config.devalt4().modify(|_, w| w.pwm1_sl().set_bit());
+});
+impl_pin!(PWM2, PG08, |config| {
+    config.devalt4().modify(|_, w| w.pwm2_sl().set_bit());
+});
+impl_pin!(PWM3, PK05, |config| {
+    config.devalt4().modify(|_, w| w.pwm3_sl().set_bit());
+});
+impl_pin!(PWM4, PL09, |config| {
+    config.devalt4().modify(|_, w| w.pwm4_sl().set_bit());
+});
+impl_pin!(PWM5, PJ07, |config| {
+    config.devaltk().modify(|_, w| w.i2c7_1_sl().clear_bit());
+    config.devalt4().modify(|_, w| w.pwm5_sl().set_bit());
+});
+impl_pin!(PWM6, PH08, |config| {
+    config.devaltk().modify(|_, w| w.i2c7_1_sl().clear_bit());
+    config.devalt4().modify(|_, w| w.pwm6_sl().set_bit());
+});
+impl_pin!(PWM7, PG06, |config| {
+    config.devalt4().modify(|_, w| w.pwm7_sl().set_bit());
+});
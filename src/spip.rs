@@ -2,7 +2,7 @@
 //!
 //! Implements the general purpose SPI Peripheral Interface that enables the connection of SPI-based peripheral devices.
 
-use crate::{cdcg, interrupt::typelevel::Interrupt, pac, peripherals::SPIP};
+use crate::{cdcg, interrupt::typelevel::Interrupt, pac, peripherals::SPIP, pmc::{Apb2, ClockGuard, PowerDomainToken}};
 use core::{convert::Infallible, future::poll_fn, marker::PhantomData, task::Poll};
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
@@ -44,7 +44,7 @@ impl Default for Config {
 
 #[allow(private_bounds)]
 mod sealed {
-    pub trait SealedInstance {}
+    pub trait SealedInstance: crate::pmc::ClockGated {}
 }
 
 /// A marker trait implemented by the SPIP peripherals.
@@ -93,6 +93,7 @@ impl<T: Instance> crate::interrupt::typelevel::Handler<T::Interrupt> for Interru
 /// Driver for the SPI (Master) Peripheral.
 pub struct Spip<'d, T: Instance, U = u8> {
     _peri: PeripheralRef<'d, T>,
+    _clock: ClockGuard,
     _mod: PhantomData<U>,
 }
 
@@ -164,6 +165,9 @@ impl<T: Instance, U: SpipPrimitive> Spip<'_, T, U> {
         // specified in the MOD register.
         let ptr = ptr as *mut U;
 
+        // Keep the SPIP clocked while the chip sleeps until the word is transferred.
+        let _domain = PowerDomainToken::<Apb2>::new();
+
         // Starts the transaction.
         unsafe { ptr.write_volatile(data) };
 
@@ -206,10 +210,12 @@ impl<'d, T: Instance> Spip<'d, T, u8> {
         // We only tie the pins to our lifetime, discard.
         let _ = (mosi, miso, sclk, legacy);
 
+        let clock = ClockGuard::new::<T>();
         Self::init(irqs, config, false);
 
         Self {
             _peri: peri,
+            _clock: clock,
             _mod: Default::default(),
         }
     }
@@ -231,10 +237,12 @@ impl<'d, T: Instance> Spip<'d, T, u16> {
         // We only tie the pins to our lifetime, discard.
         let _ = (mosi, miso, sclk, legacy);
 
+        let clock = ClockGuard::new::<T>();
         Self::init(irqs, config, true);
 
         Self {
             _peri: peri,
+            _clock: clock,
             _mod: Default::default(),
         }
     }
@@ -0,0 +1,54 @@
+//! Supply voltage monitoring
+//!
+//! This is not a `VSBY` monitor, and its threshold can not be configured in firmware. The HAL drives no programmable
+//! brown-out detector for either supply: sampling `VSBY` against a threshold would take the threshold detectors of
+//! the ADC, which this HAL has no driver for.
+//!
+//! What is supported is the `VCC1` supply, which a supervisor on the board watches. The supervisor asserts the
+//! `VCC1_RST#` input when the supply drops below its threshold, and the MIWU reports this input as
+//! [VCC1_RST](crate::miwu::internal::VCC1_RST), on which the [SupplyMonitor] is built. The threshold is set by
+//! choosing the supervisor.
+
+use embassy_hal_internal::Peripheral;
+
+use crate::miwu::internal::VCC1_RST;
+use crate::miwu::{Edge, InterruptHandler, WakeUp, WakeUpInput};
+
+/// Notifies the firmware when the board supervisor reports the `VCC1` supply dropping, so it can save its state before
+/// power is lost.
+pub struct SupplyMonitor<'d> {
+    wui: WakeUp<'d>,
+}
+
+impl<'d> SupplyMonitor<'d> {
+    /// Claim the `VCC1_RST#` wake-up input.
+    pub fn new(
+        wui: impl Peripheral<P = VCC1_RST> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<<VCC1_RST as WakeUpInput>::Interrupt, InterruptHandler<VCC1_RST>>,
+    ) -> Self {
+        Self {
+            wui: crate::miwu::claim_for_peripheral(wui, irqs),
+        }
+    }
+
+    /// Returns true while the supervisor reports the supply below its threshold.
+    #[must_use]
+    pub fn is_dropping(&self) -> bool {
+        !self.wui.is_high()
+    }
+
+    /// Wait until the supply drops below the threshold, returning immediately if it already is.
+    pub async fn wait_for_drop(&mut self) {
+        self.wui.wait_for_low().await;
+    }
+
+    /// Wait until the supply is back above the threshold, returning immediately if it already is.
+    pub async fn wait_for_restore(&mut self) {
+        self.wui.wait_for_high().await;
+    }
+
+    /// Arm a supply drop for waking up from a low power state.
+    pub fn enable_wake(&mut self) {
+        self.wui.enable(Edge::Falling);
+    }
+}
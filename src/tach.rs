@@ -0,0 +1,160 @@
+//! Fan tachometer inputs on the MFT16 timers.
+//!
+//! A fan tachometer output pulses a fixed number of times per revolution, usually twice. [Tach] routes a `TAn` pin to
+//! its MFT16, captures the edges with [InputCapture] and turns the time of a full revolution into RPM. When the
+//! counter underflows twice without an edge the fan is reported as stalled, instead of waiting for a pulse forever.
+//! [Tach::wait_for_stall] completes once a fan stays below a speed floor, to flag failed fans without polling.
+
+use embassy_hal_internal::Peripheral;
+
+use crate::timer::capture::{Channel, Edge, InputCapture};
+use crate::timer::low_level::{ClockConfig, InterruptHandler};
+use crate::timer::{CapturePin, MultiFunctionInstance};
+
+/// The number of counter underflows without an edge after which a fan counts as stalled
+const STALL_UNDERFLOWS: u64 = 2;
+
+/// The error type for [Tach]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// No tachometer pulse arrived for two underflows of the counter, the fan is stopped or too slow to measure
+    Stalled,
+}
+
+/// Configuration for a [Tach].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// Clock configuration, counter 1 should run from a fixed frequency source.
+    ///
+    /// The slowest measurable fan pulses once per 65536 ticks, the `LFCLK` allows down to 15 RPM at 2 pulses per
+    /// revolution with a resolution that drops at high speeds.
+    pub clk: ClockConfig,
+    /// The number of tachometer pulses per revolution of the fan, at least 1.
+    pub pulses_per_revolution: u8,
+    /// The edge of the tachometer signal that is counted.
+    pub edge: Edge,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clk: ClockConfig::low_frequency(),
+            pulses_per_revolution: 2,
+            edge: Edge::Falling,
+        }
+    }
+}
+
+/// A floor for the speed of a fan, for [Tach::wait_for_stall].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StallThreshold {
+    /// The lowest speed in RPM at which the fan still counts as running
+    pub min_rpm: u32,
+    /// The number of consecutive measurements below `min_rpm` before the fan counts as failed, at least 1
+    pub windows: u16,
+}
+
+/// Fan speed measurement on the `TAn` input of an MFT16.
+pub struct Tach<'d, T: MultiFunctionInstance> {
+    capture: InputCapture<'d, T>,
+    pulses_per_revolution: u8,
+    tick_hz: u32,
+}
+
+impl<'d, T: MultiFunctionInstance> Tach<'d, T> {
+    /// Instantiate the tachometer driver for this MFT16 and its `TAn` pin, and start counting.
+    ///
+    /// # Panics
+    /// If `config.pulses_per_revolution` is 0, or counter 1 does not run from a fixed frequency clock.
+    pub fn new<P: CapturePin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = P> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        config: Config,
+    ) -> Self {
+        assert!(
+            config.pulses_per_revolution > 0,
+            "A fan pulses at least once per revolution"
+        );
+
+        let capture = InputCapture::new_a(instance, pin, irqs, config.clk, config.edge);
+        let tick_hz = capture
+            .frequency(Channel::A)
+            .expect("The tachometer counter needs a fixed frequency clock");
+
+        Self {
+            capture,
+            pulses_per_revolution: config.pulses_per_revolution,
+            tick_hz,
+        }
+    }
+
+    /// The number of tachometer pulses per revolution of the fan.
+    #[must_use]
+    pub fn pulses_per_revolution(&self) -> u8 {
+        self.pulses_per_revolution
+    }
+
+    /// Set the number of tachometer pulses per revolution of the fan.
+    ///
+    /// # Panics
+    /// If `pulses_per_revolution` is 0.
+    pub fn set_pulses_per_revolution(&mut self, pulses_per_revolution: u8) {
+        assert!(pulses_per_revolution > 0, "A fan pulses at least once per revolution");
+        self.pulses_per_revolution = pulses_per_revolution;
+    }
+
+    /// Measure the time of the next full revolution of the fan, in counter ticks.
+    pub async fn revolution_ticks(&mut self) -> Result<u64, Error> {
+        self.capture.discard_capture(Channel::A);
+
+        let start = self.capture_pulse().await?;
+        let mut end = start;
+        for _ in 0..self.pulses_per_revolution {
+            end = self.capture_pulse().await?;
+        }
+
+        Ok(end - start)
+    }
+
+    /// Measure the speed of the fan in revolutions per minute, over its next full revolution.
+    pub async fn rpm(&mut self) -> Result<u32, Error> {
+        let ticks = self.revolution_ticks().await?;
+        if ticks == 0 {
+            return Err(Error::Stalled);
+        }
+
+        Ok((u64::from(self.tick_hz) * 60 / ticks) as u32)
+    }
+
+    /// Measure the fan continuously, completing once it ran below the threshold for its number of consecutive
+    /// measurements, with the speed of the last one.
+    ///
+    /// Every measurement takes one revolution of the fan, or the stall timeout, and a stalled fan counts as 0 RPM. A
+    /// single measurement at or above `min_rpm` restarts the count, so short dips do not raise an alert.
+    pub async fn wait_for_stall(&mut self, threshold: StallThreshold) -> u32 {
+        let mut below = 0;
+        loop {
+            let rpm = self.rpm().await.unwrap_or(0);
+            if rpm >= threshold.min_rpm {
+                below = 0;
+                continue;
+            }
+
+            below += 1;
+            if below >= threshold.windows.max(1) {
+                return rpm;
+            }
+        }
+    }
+
+    async fn capture_pulse(&mut self) -> Result<u64, Error> {
+        self.capture
+            .capture_within(Channel::A, STALL_UNDERFLOWS)
+            .await
+            .ok_or(Error::Stalled)
+    }
+}
@@ -0,0 +1,297 @@
+//! Time driver backed by the ITIM timers.
+//!
+//! ITIM64 counts down freely from `u64::MAX` and provides the time, so it never overflows. ITIM32_1 is loaded with
+//! the number of ticks until the next alarm. Both run from the `LFCLK` for a tick rate of 32768 Hz, and from the
+//! prescaled `APB2_CLK` for other tick rates.
+//!
+//! The `LFCLK` keeps running in deep sleep, so at 32768 Hz the driver is tickless: the timers keep counting while the
+//! chip sleeps, and the timeout of ITIM32_1 wakes it up through its MIWU input. The `time-driver-lfclk` feature
+//! selects this driver together with the 32768 Hz tick rate.
+//!
+//! That the timeout signals `MIWU0_43` is checked once at boot. If it does not, the driver keeps the core clock
+//! running, so the chip sleeps no deeper than [SleepMode::Idle](crate::pmc::SleepMode::Idle) instead of missing alarms.
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::CriticalSection;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time_driver::{Driver, TICK_HZ};
+use embassy_time_queue_utils::Queue;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac;
+
+/// `ITCTS`: the counter reached zero, cleared by writing 1
+const ITCTS_TO_STS: u8 = 1 << 0;
+/// `ITCTS`: interrupt when the counter reaches zero
+const ITCTS_TO_IE: u8 = 1 << 2;
+/// `ITCTS`: signal the MIWU when the counter reaches zero, to wake up from deep sleep
+const ITCTS_TO_WUE: u8 = 1 << 3;
+/// `ITCTS`: clock the timer from the `LFCLK` instead of the `APB2_CLK`
+const ITCTS_CKSEL: u8 = 1 << 4;
+/// `ITCTS`: run the timer
+const ITCTS_ITEN: u8 = 1 << 7;
+
+fn system_timer() -> &'static pac::itim64::RegisterBlock {
+    // Safety: the ITIM64 is reserved for the time driver.
+    unsafe { &*pac::Itim64::PTR }
+}
+
+fn event_timer() -> &'static pac::itim32_1::RegisterBlock {
+    // Safety: the ITIM32_1 is reserved for the time driver.
+    unsafe { &*pac::Itim32_1::PTR }
+}
+
+/// The 0-indexed group and input of MIWU0 signalled by the timeout of ITIM32_1.
+const WAKE_UP_INPUT: (usize, u8) = (3, 3);
+
+fn wake_up_input() -> &'static pac::miwu0::RegisterBlock {
+    // Safety: the WakeUpInput of the ITIM32_1 is reserved for the time driver.
+    unsafe { &*pac::Miwu0::PTR }
+}
+
+/// Whether the timers are driven by the prescaled APB2 clock, and thus depend on its frequency.
+pub(crate) fn uses_apb() -> bool {
+    TICK_HZ != 32768
+}
+
+/// The `ITCTS` clock select and wake-up bits, and the prescaler for the tick rate.
+fn clock_config() -> (u8, u8) {
+    if !uses_apb() {
+        return (ITCTS_CKSEL | ITCTS_TO_WUE, 0);
+    }
+
+    // Note(unsafe): time driver is initialized after the clocks have been initialized.
+    let clocks = unsafe { crate::cdcg::get_clocks() };
+
+    if clocks.apb2_clk % TICK_HZ as u32 != 0 {
+        panic!(
+            "APB2CLK ({}) is not a multiple of desired time_driver tickrate ({})",
+            clocks.apb2_clk, TICK_HZ
+        );
+    }
+
+    let prescaler = clocks.apb2_clk / TICK_HZ as u32;
+    if prescaler > 256 {
+        panic!(
+            "Cannot derive a prescaling for APB2CLK ({}) and desired time_driver tickrate ({})",
+            clocks.apb2_clk, TICK_HZ
+        );
+    }
+
+    (0, (prescaler - 1) as u8)
+}
+
+pub(crate) struct ItimDriver {
+    /// Clock select and wake-up bits for the `ITCTS` registers
+    ctrl: Cell<u8>,
+    alarm: Mutex<CriticalSectionRawMutex, Cell<u64>>,
+    queue: Mutex<CriticalSectionRawMutex, RefCell<Queue>>,
+}
+
+// Safety: ctrl is only written during init, before any other use of the driver.
+unsafe impl Sync for ItimDriver {}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: ItimDriver = ItimDriver {
+    ctrl: Cell::new(0),
+    alarm: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(u64::MAX)),
+    queue: Mutex::new(RefCell::new(Queue::new()))
+});
+
+impl ItimDriver {
+    fn init(&'static self, _cs: CriticalSection) {
+        // The time driver runs for the lifetime of the program, so its clocks are never gated again.
+        crate::pmc::ungate(crate::pmc::ITIM64_PWDWN);
+        crate::pmc::ungate(crate::pmc::ITIM32_PWDWN[0]);
+
+        let (ctrl, prescaler) = clock_config();
+        self.ctrl.set(ctrl);
+        let cksel = ctrl & ITCTS_CKSEL;
+
+        let sys = system_timer();
+        sys.itcts64().write(|w| unsafe { w.bits(cksel) });
+        sys.itpre64().write(|w| unsafe { w.bits(prescaler) });
+        sys.itcnt64h().write(|w| unsafe { w.bits(u32::MAX) });
+        sys.itcnt64l().write(|w| unsafe { w.bits(u32::MAX) });
+        sys.itcts64().write(|w| unsafe { w.bits(cksel | ITCTS_ITEN) });
+        // The enable crosses into the clock domain of the timer, wait for it to take effect.
+        while sys.itcts64().read().bits() & ITCTS_ITEN == 0 {}
+
+        let evt = event_timer();
+        evt.itcts32().write(|w| unsafe { w.bits(cksel | ITCTS_TO_STS) });
+        evt.itpre32().write(|w| unsafe { w.bits(prescaler) });
+
+        if ctrl & ITCTS_TO_WUE != 0 {
+            self.enable_wake_up();
+
+            if !self.wake_up_self_test() {
+                #[cfg(feature = "defmt")]
+                defmt::error!("ITIM32_1 timeout does not signal MIWU0_43, not sleeping deeper than idle");
+                // Without the wake-up, an alarm would not end a sleep, so keep the core clock running instead.
+                core::mem::forget(crate::pmc::PowerDomainToken::<crate::pmc::Core>::new());
+            }
+        }
+
+        unsafe { crate::interrupt::typelevel::ITIM32_1::enable() };
+    }
+
+    /// Let the timeout of the event timer wake the chip from deep sleep.
+    ///
+    /// Only the wake-up signal of the MIWU is used, its `WKINTx_n` interrupt stays disabled: once awake, the timeout
+    /// is handled by the interrupt of the event timer itself.
+    fn enable_wake_up(&self) {
+        use pac::miwu0::*;
+
+        let (group, input) = WAKE_UP_INPUT;
+        let port = wake_up_input();
+        port.wkenn(group).modify(|_, w| w.input(input).disabled());
+        port.wkmodn(group)
+            .modify(|_, w| w.input(input).variant(wkmodn::InputMode::Edge));
+        port.wkaedgn(group)
+            .modify(|_, w| w.input(input).variant(wkaedgn::AnyEdge::Edge));
+        port.wkedgn(group)
+            .modify(|_, w| w.input(input).variant(wkedgn::Edge::HighRising));
+        port.wkinenn(group).modify(|_, w| w.input(input).enabled());
+        port.wkpcln(group).write(|w| w.input(input).clear());
+        port.wkenn(group).modify(|_, w| w.input(input).enabled());
+    }
+
+    /// Let the event timer expire once, and check that its timeout sets the pending bit of [WAKE_UP_INPUT].
+    ///
+    /// The tickless operation relies on this input, so the mapping is verified once at boot.
+    fn wake_up_self_test(&self) -> bool {
+        let (group, input) = WAKE_UP_INPUT;
+        let is_pending = || wake_up_input().wkpndn(group).read().input(input).is_pending();
+
+        let evt = event_timer();
+        evt.itcnt32().write(|w| unsafe { w.bits(1) });
+        evt.itcts32().write(|w| unsafe { w.bits(self.ctrl.get() | ITCTS_ITEN) });
+        // The LFCLK always runs, so the timer expires within a few of its ticks.
+        while evt.itcts32().read().bits() & ITCTS_TO_STS == 0 {}
+
+        // The signal crosses from the LFCLK domain into the MIWU, give it another few ticks of the LFCLK.
+        let pending = is_pending() || {
+            crate::delay::block_for_us(100);
+            is_pending()
+        };
+
+        self.disarm();
+        pending
+    }
+
+    /// Stop the event timer and clear its status.
+    fn disarm(&self) {
+        let evt = event_timer();
+        evt.itcts32()
+            .write(|w| unsafe { w.bits(self.ctrl.get() | ITCTS_TO_STS) });
+        while evt.itcts32().read().bits() & ITCTS_ITEN != 0 {}
+
+        if self.ctrl.get() & ITCTS_TO_WUE != 0 {
+            // A pending wake-up would keep the chip from entering deep sleep.
+            let (group, input) = WAKE_UP_INPUT;
+            wake_up_input().wkpcln(group).write(|w| w.input(input).clear());
+        }
+    }
+
+    #[allow(unused)]
+    fn on_interrupt(&self) {
+        critical_section::with(|cs| {
+            self.disarm();
+            self.trigger_alarm(cs);
+        });
+    }
+
+    fn trigger_alarm(&self, cs: CriticalSection) {
+        let mut next = self.queue.borrow(cs).borrow_mut().next_expiration(self.now());
+        while !self.set_alarm(cs, next) {
+            next = self.queue.borrow(cs).borrow_mut().next_expiration(self.now());
+        }
+    }
+
+    fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
+        let alarm = self.alarm.borrow(cs);
+        alarm.set(timestamp);
+        self.disarm();
+
+        let t = self.now();
+        if timestamp <= t {
+            // If alarm timestamp has passed the alarm will not fire.
+            // Return `false` to indicate that.
+            alarm.set(u64::MAX);
+            return false;
+        }
+
+        if timestamp == u64::MAX {
+            // Nothing is scheduled.
+            return true;
+        }
+
+        // Alarms further away than the event timer can count are re-evaluated when it expires.
+        let ticks = (timestamp - t).min(u32::MAX as u64) as u32;
+
+        let evt = event_timer();
+        evt.itcnt32().write(|w| unsafe { w.bits(ticks) });
+        evt.itcts32()
+            .write(|w| unsafe { w.bits(self.ctrl.get() | ITCTS_ITEN | ITCTS_TO_IE) });
+        while evt.itcts32().read().bits() & ITCTS_ITEN == 0 {}
+
+        // Reevaluate if the alarm timestamp is still in the future
+        if timestamp <= self.now() {
+            // If alarm timestamp has passed since we set it, we have a race condition and
+            // the alarm may or may not have fired.
+            // Disarm the alarm and return `false` to indicate that.
+            // It is the caller's responsibility to handle this ambiguity.
+            self.disarm();
+            alarm.set(u64::MAX);
+            return false;
+        }
+
+        // We're confident the alarm will ring in the future.
+        true
+    }
+}
+
+impl Driver for ItimDriver {
+    fn now(&self) -> u64 {
+        let sys = system_timer();
+
+        // The counter is read in two halves, retry when the low half wrapped in between.
+        let counter = loop {
+            let high = sys.itcnt64h().read().bits();
+            let low = sys.itcnt64l().read().bits();
+            if sys.itcnt64h().read().bits() == high {
+                break ((high as u64) << 32) | low as u64;
+            }
+        };
+
+        // We have a down-counting counter, thus we need to invert.
+        u64::MAX - counter
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &core::task::Waker) {
+        critical_section::with(|cs| {
+            let mut queue = self.queue.borrow(cs).borrow_mut();
+
+            if queue.schedule_wake(at, waker) {
+                let mut next = queue.next_expiration(self.now());
+                while !self.set_alarm(cs, next) {
+                    next = queue.next_expiration(self.now());
+                }
+            }
+        })
+    }
+}
+
+#[allow(unused)]
+use crate::interrupt;
+
+#[cfg(feature = "rt")]
+#[pac::interrupt]
+fn ITIM32_1() {
+    DRIVER.on_interrupt()
+}
+
+pub(crate) fn init(cs: CriticalSection) {
+    DRIVER.init(cs)
+}
@@ -9,7 +9,9 @@ use embassy_time_driver::{Driver, TICK_HZ};
 use embassy_time_queue_utils::Queue;
 
 macro_rules! impl_instance {
-    ($instance:ident, $interrupt:ident) => {
+    ($instance:ident, $interrupt:ident, $pwdwn:expr) => {
+        const PWDWN: crate::pmc::PwdwnBit = $pwdwn;
+
         const fn regs() -> &'static crate::pac::mft16_1::RegisterBlock {
             unsafe { &*pac::$instance::PTR }
         }
@@ -31,14 +33,19 @@ macro_rules! impl_instance {
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "time-driver-mft16-1")] {
-        impl_instance!(Mft16_1, MFT16_1);
+        impl_instance!(Mft16_1, MFT16_1, crate::pmc::MFT16_PWDWN[0]);
     } else if #[cfg(feature = "time-driver-mft16-2")] {
-        impl_instance!(Mft16_2, MFT16_2);
+        impl_instance!(Mft16_2, MFT16_2, crate::pmc::MFT16_PWDWN[1]);
     } else if #[cfg(feature = "time-driver-mft16-3")] {
-        impl_instance!(Mft16_3, MFT16_3);
+        impl_instance!(Mft16_3, MFT16_3, crate::pmc::MFT16_PWDWN[2]);
     }
 }
 
+/// Whether the counters are driven by the prescaled APB1 clock, and thus depend on its frequency.
+pub(crate) fn uses_apb() -> bool {
+    TICK_HZ != 32768
+}
+
 // Clock timekeeping works with something we call "periods", which are time intervals
 // of 2^15 ticks. The Clock counter value is 16 bits, so one "overflow cycle" is 2 periods.
 //
@@ -93,6 +100,9 @@ impl MultiFunctionTimerDriver {
     fn init(&'static self, _cs: critical_section::CriticalSection) {
         let r = regs();
 
+        // The time driver runs for the lifetime of the program, so its clock is never gated again.
+        crate::pmc::ungate(PWDWN);
+
         unsafe { enable_interrupt() };
 
         // Disable the clocks.
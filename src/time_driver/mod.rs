@@ -0,0 +1,19 @@
+//! Time drivers, selected with the `time-driver-*` features.
+
+#[cfg(any(
+    feature = "time-driver-mft16-1",
+    feature = "time-driver-mft16-2",
+    feature = "time-driver-mft16-3"
+))]
+mod mft;
+#[cfg(any(
+    feature = "time-driver-mft16-1",
+    feature = "time-driver-mft16-2",
+    feature = "time-driver-mft16-3"
+))]
+pub(crate) use mft::{init, uses_apb};
+
+#[cfg(feature = "time-driver-itim")]
+mod itim;
+#[cfg(feature = "time-driver-itim")]
+pub(crate) use itim::{init, uses_apb};
@@ -0,0 +1,437 @@
+//! Input capture on the MFT16 timers.
+//!
+//! In Dual-Independent Input Capture mode both counters of an MFT16 count down freely, and an edge on the `TAn` or
+//! `TBn` input copies counter 1 or counter 2 into its capture register. [InputCapture] extends these 16-bit captures
+//! with the number of counter underflows into timestamps, to measure pulse widths and frequencies of external
+//! signals.
+//!
+//! [DutyCycleCapture] instead uses Duty Cycle Capture mode, where both edges of `TAn` are captured, to measure the
+//! period and high time of a PWM signal at once.
+//!
+//! The drivers take the `TAn` and `TBn` pins they capture on, see [CapturePin] and [CapturePinB], and route them to
+//! the MFT16 when they are created.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::low_level::{
+    ClockConfig, ClockSource, Config, Counter, InterruptHandler, Mode, MultiFunctionTimer, WakeUpEvent,
+};
+use super::{CapturePin, CapturePinB, MultiFunctionInstance};
+use crate::gpio::AnyPin;
+use crate::pmc::{Apb1, PowerDomainToken};
+
+/// `TnMCTRL`: capture on a rising edge of `TAn` instead of a falling edge
+const MCTRL_TAEDG: u8 = 1 << 3;
+/// `TnMCTRL`: capture on a rising edge of `TBn` instead of a falling edge
+const MCTRL_TBEDG: u8 = 1 << 4;
+/// `TnMCTRL`: enable the `TAn` input
+const MCTRL_TAEN: u8 = 1 << 5;
+/// `TnMCTRL`: enable the `TBn` input
+const MCTRL_TBEN: u8 = 1 << 6;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// One of the two capture units of an MFT16.
+pub enum Channel {
+    /// Counter 1, captured on the `TAn` input.
+    A,
+    /// Counter 2, captured on the `TBn` input.
+    B,
+}
+
+impl Channel {
+    const fn counter(&self) -> Counter {
+        match self {
+            Channel::A => Counter::Counter1,
+            Channel::B => Counter::Counter2,
+        }
+    }
+
+    /// The pending bits of the capture and of the counter underflow.
+    const fn events(&self) -> (u8, u8) {
+        match self {
+            Channel::A => (1 << WakeUpEvent::A as u8, 1 << WakeUpEvent::C as u8),
+            Channel::B => (1 << WakeUpEvent::B as u8, 1 << WakeUpEvent::D as u8),
+        }
+    }
+
+    const fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// The edge of the input signal that is captured.
+pub enum Edge {
+    /// Capture on a falling edge.
+    #[default]
+    Falling,
+    /// Capture on a rising edge.
+    Rising,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Configuration for an [InputCapture] on both channels.
+pub struct CaptureConfig {
+    /// Clock configuration. Both counters should run from a fixed frequency source.
+    pub clk: ClockConfig,
+    /// The captured edge of the `TAn` input.
+    pub edge_a: Edge,
+    /// The captured edge of the `TBn` input.
+    pub edge_b: Edge,
+}
+
+/// Route a `TAn` pin to its MFT16.
+fn setup_pin_a<P: CapturePin>(_pin: &mut PeripheralRef<'_, P>) {
+    // Note(cs): other peripherals might also be modifying devalt* at the same time.
+    critical_section::with(|cs| {
+        // Safety: We have exclusive ownership over the pin.
+        unsafe { P::setup(cs) };
+    });
+}
+
+/// Route a `TBn` pin to its MFT16.
+fn setup_pin_b<P: CapturePinB>(_pin: &mut PeripheralRef<'_, P>) {
+    // Note(cs): other peripherals might also be modifying devalt* at the same time.
+    critical_section::with(|cs| {
+        // Safety: We have exclusive ownership over the pin.
+        unsafe { P::setup(cs) };
+    });
+}
+
+/// Input capture driver for one of the MFT16 timers.
+///
+/// Timestamps count the ticks of the channel's counter since the driver was created. Underflows of the 16-bit
+/// counter are only seen while a capture is awaited, so await a capture at least once every 65536 ticks to keep
+/// the timestamps correct.
+pub struct InputCapture<'d, T: MultiFunctionInstance> {
+    timer: MultiFunctionTimer<'d, T>,
+    /// The number of underflows per channel
+    underflows: [u64; 2],
+    _pin_a: Option<PeripheralRef<'d, AnyPin>>,
+    _pin_b: Option<PeripheralRef<'d, AnyPin>>,
+}
+
+impl<'d, T: MultiFunctionInstance> InputCapture<'d, T> {
+    /// Instantiate the capture driver for this peripheral on both its `TAn` and `TBn` pins, and start the counters.
+    pub fn new<A: CapturePin<Instance = T>, B: CapturePinB<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin_a: impl Peripheral<P = A> + 'd,
+        pin_b: impl Peripheral<P = B> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        config: CaptureConfig,
+    ) -> Self {
+        into_ref!(pin_a, pin_b);
+        setup_pin_a(&mut pin_a);
+        setup_pin_b(&mut pin_b);
+
+        Self::new_inner(
+            instance,
+            irqs,
+            config.clk,
+            Some((pin_a.map_into(), config.edge_a)),
+            Some((pin_b.map_into(), config.edge_b)),
+        )
+    }
+
+    /// Instantiate the capture driver for this peripheral on its `TAn` pin only, capturing `edge` on
+    /// [Channel::A], and start the counters.
+    pub fn new_a<A: CapturePin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = A> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        clk: ClockConfig,
+        edge: Edge,
+    ) -> Self {
+        into_ref!(pin);
+        setup_pin_a(&mut pin);
+
+        Self::new_inner(instance, irqs, clk, Some((pin.map_into(), edge)), None)
+    }
+
+    /// Instantiate the capture driver for this peripheral on its `TBn` pin only, capturing `edge` on
+    /// [Channel::B], and start the counters.
+    pub fn new_b<B: CapturePinB<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = B> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        clk: ClockConfig,
+        edge: Edge,
+    ) -> Self {
+        into_ref!(pin);
+        setup_pin_b(&mut pin);
+
+        Self::new_inner(instance, irqs, clk, None, Some((pin.map_into(), edge)))
+    }
+
+    fn new_inner(
+        instance: impl Peripheral<P = T> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        clk: ClockConfig,
+        a: Option<(PeripheralRef<'d, AnyPin>, Edge)>,
+        b: Option<(PeripheralRef<'d, AnyPin>, Edge)>,
+    ) -> Self {
+        let mut timer = MultiFunctionTimer::new(instance, irqs);
+        timer.enable(Config { clk, mode: Mode::Mode5 });
+
+        let mut mctrl = 0;
+        if let Some((_, edge)) = a {
+            mctrl |= MCTRL_TAEN | if edge == Edge::Rising { MCTRL_TAEDG } else { 0 };
+        }
+        if let Some((_, edge)) = b {
+            mctrl |= MCTRL_TBEN | if edge == Edge::Rising { MCTRL_TBEDG } else { 0 };
+        }
+
+        let r = T::regs();
+        r.tn_mctrl().modify(|r, w| unsafe { w.bits(r.bits() | mctrl) });
+        r.tn_eclr().write(|w| unsafe { w.bits(0b0011_1111) });
+
+        Self {
+            timer,
+            underflows: [0; 2],
+            _pin_a: a.map(|(pin, _)| pin),
+            _pin_b: b.map(|(pin, _)| pin),
+        }
+    }
+
+    /// The frequency in Hz at which the counter of `channel` counts, or `None` if it is stopped or driven by an
+    /// external signal.
+    pub fn frequency(&self, channel: Channel) -> Option<u32> {
+        self.timer.counter_frequency(channel.counter())
+    }
+
+    /// The clock source the counter of `channel` counts.
+    pub fn clock_source(&self, channel: Channel) -> ClockSource {
+        self.timer.counter_source(channel.counter())
+    }
+
+    /// The current time of `channel`, in ticks since the driver was created.
+    #[must_use]
+    pub fn now(&self, channel: Channel) -> u64 {
+        self.timestamp(channel, self.timer.counter(channel.counter()))
+    }
+
+    fn timestamp(&self, channel: Channel, counter: u16) -> u64 {
+        // The counters count down.
+        (self.underflows[channel.index()] << 16) | u64::from(u16::MAX - counter)
+    }
+
+    /// Wait for the next captured edge on `channel`, returning its timestamp.
+    ///
+    /// Returns immediately with the last capture if an edge was captured since the previous call.
+    pub async fn capture(&mut self, channel: Channel) -> u64 {
+        // An u64 of underflows is never reached.
+        self.capture_within(channel, u64::MAX).await.unwrap_or_default()
+    }
+
+    /// Wait for the next captured edge on `channel` like [InputCapture::capture], but give up with `None` once the
+    /// counter underflowed `underflows` times without an edge.
+    ///
+    /// Each underflow takes 65536 ticks, so this bounds the wait for a signal that may have stopped.
+    pub async fn capture_within(&mut self, channel: Channel, underflows: u64) -> Option<u64> {
+        let (capture, underflow) = channel.events();
+        let r = T::regs();
+        let limit = self.underflows[channel.index()].saturating_add(underflows);
+
+        // Keep the timer clocked while the chip sleeps until the edge is captured.
+        let _domain = PowerDomainToken::<Apb1>::new();
+        let pending = poll_fn(|cx| {
+            T::waker().register(cx.waker());
+
+            let pending = r.tn_ectrl().read().bits();
+            if pending & capture != 0 {
+                return Poll::Ready(Some(pending));
+            }
+
+            if pending & underflow != 0 {
+                r.tn_eclr().write(|w| unsafe { w.bits(underflow) });
+                self.underflows[channel.index()] += 1;
+                if self.underflows[channel.index()] >= limit {
+                    return Poll::Ready(None);
+                }
+            }
+
+            // Note(cs): interrupt handler changes this register as well.
+            critical_section::with(|_| {
+                r.tn_ien()
+                    .modify(|r, w| unsafe { w.bits(r.bits() | capture | underflow) });
+            });
+            Poll::Pending
+        })
+        .await?;
+
+        let captured = match channel {
+            Channel::A => r.tn_cra().read().bits(),
+            Channel::B => r.tn_crb().read().bits(),
+        };
+
+        // With both pending, the underflow came first if the counter had only just reloaded when it was captured.
+        let underflowed_first = pending & underflow != 0 && captured > u16::MAX / 2;
+        if underflowed_first {
+            self.underflows[channel.index()] += 1;
+        }
+
+        let timestamp = self.timestamp(channel, captured);
+
+        if pending & underflow != 0 && !underflowed_first {
+            self.underflows[channel.index()] += 1;
+        }
+        r.tn_eclr()
+            .write(|w| unsafe { w.bits(pending & (capture | underflow)) });
+
+        Some(timestamp)
+    }
+
+    /// Drop an edge captured on `channel` since the previous capture, so the next capture waits for a new edge.
+    pub fn discard_capture(&mut self, channel: Channel) {
+        T::regs().tn_eclr().write(|w| unsafe { w.bits(channel.events().0) });
+    }
+
+    /// Measure the period of the signal on `channel` in ticks, as the time between two captured edges.
+    pub async fn measure_period(&mut self, channel: Channel) -> u64 {
+        // Drop a capture that happened before this call.
+        self.discard_capture(channel);
+
+        let start = self.capture(channel).await;
+        self.capture(channel).await - start
+    }
+}
+
+/// The error type for [DutyCycleCapture]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MeasureError {
+    /// The counter underflowed before the signal completed a period, the signal is stuck or too slow for the clock
+    NoSignal,
+}
+
+/// A measurement of a PWM signal by [DutyCycleCapture].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmReading {
+    /// The period of the signal, in counter ticks
+    pub period_ticks: u16,
+    /// The time the signal was high, in counter ticks
+    pub high_ticks: u16,
+    /// The frequency of the counter in Hz
+    pub tick_hz: u32,
+}
+
+impl PwmReading {
+    /// The frequency of the signal in Hz.
+    #[must_use]
+    pub fn frequency_hz(&self) -> u32 {
+        self.tick_hz / u32::from(self.period_ticks)
+    }
+
+    /// The duty cycle of the signal, in tenths of a percent.
+    #[must_use]
+    pub fn duty_permille(&self) -> u16 {
+        (u32::from(self.high_ticks) * 1000 / u32::from(self.period_ticks)) as u16
+    }
+}
+
+/// Measures the frequency and duty cycle of a PWM signal on the `TAn` input of an MFT16.
+///
+/// In Duty Cycle Capture mode counter 1 restarts at every rising edge of `TAn`, after capturing the period into the
+/// first capture register, and captures the high time into the second capture register at every falling edge.
+/// Periods longer than 65535 ticks of counter 1 cannot be measured, so choose its clock for the slowest expected
+/// signal.
+pub struct DutyCycleCapture<'d, T: MultiFunctionInstance> {
+    timer: MultiFunctionTimer<'d, T>,
+    pin: PeripheralRef<'d, AnyPin>,
+}
+
+impl<'d, T: MultiFunctionInstance> DutyCycleCapture<'d, T> {
+    /// Instantiate the measurement driver for this peripheral and its `TAn` pin, and start counter 1 from `clk`.
+    pub fn new<A: CapturePin<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = A> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        clk: ClockConfig,
+    ) -> Self {
+        into_ref!(pin);
+        setup_pin_a(&mut pin);
+
+        let mut timer = MultiFunctionTimer::new(instance, irqs);
+        timer.enable(Config { clk, mode: Mode::Mode6 });
+
+        let r = T::regs();
+        r.tn_mctrl()
+            .modify(|r, w| unsafe { w.bits(r.bits() | MCTRL_TAEN | MCTRL_TAEDG) });
+        r.tn_eclr().write(|w| unsafe { w.bits(0b0011_1111) });
+
+        Self {
+            timer,
+            pin: pin.map_into(),
+        }
+    }
+
+    /// The `TAn` pin, to read the level of a signal that does not toggle.
+    pub(crate) fn pin(&self) -> &AnyPin {
+        &self.pin
+    }
+
+    /// Wait for one of `events`, failing on an underflow of counter 1.
+    async fn wait_for(&mut self, events: u8) -> Result<(), MeasureError> {
+        let (_, underflow) = Channel::A.events();
+        let r = T::regs();
+
+        // Keep the timer clocked while the chip sleeps until the event happens.
+        let _domain = PowerDomainToken::<Apb1>::new();
+        let pending = poll_fn(|cx| {
+            T::waker().register(cx.waker());
+
+            let pending = r.tn_ectrl().read().bits() & (events | underflow);
+            if pending != 0 {
+                return Poll::Ready(pending);
+            }
+
+            // Note(cs): interrupt handler changes this register as well.
+            critical_section::with(|_| {
+                r.tn_ien()
+                    .modify(|r, w| unsafe { w.bits(r.bits() | events | underflow) });
+            });
+            Poll::Pending
+        })
+        .await;
+
+        r.tn_eclr().write(|w| unsafe { w.bits(pending) });
+        if pending & underflow != 0 {
+            Err(MeasureError::NoSignal)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Measure a full period of the signal, starting at its next rising edge.
+    pub async fn measure(&mut self) -> Result<PwmReading, MeasureError> {
+        let (period, high) = (Channel::A.events().0, Channel::B.events().0);
+        let r = T::regs();
+
+        // Start at a rising edge, so the next captures cover a whole period.
+        r.tn_eclr()
+            .write(|w| unsafe { w.bits(period | high | Channel::A.events().1) });
+        self.wait_for(period).await?;
+        self.wait_for(high).await?;
+        self.wait_for(period).await?;
+
+        // The counter counts down from its full range since the rising edge.
+        let period_ticks = u16::MAX - r.tn_cra().read().bits();
+        let high_ticks = u16::MAX - r.tn_crb().read().bits();
+        if period_ticks == 0 {
+            return Err(MeasureError::NoSignal);
+        }
+
+        Ok(PwmReading {
+            period_ticks,
+            high_ticks,
+            tick_hz: self.timer.counter_frequency(Counter::Counter1).unwrap_or(0),
+        })
+    }
+}
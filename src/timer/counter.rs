@@ -0,0 +1,169 @@
+//! External event counting on the MFT16 timers.
+//!
+//! Counter 1 of an MFT16 can be clocked by the edges of its `TBn` input, to count pulses from a flow meter or a
+//! single encoder channel in hardware. In pulse accumulate mode it instead counts its clock while `TBn` is active,
+//! accumulating the time the input is asserted. The MFT16 has no quadrature decoder, so the direction of a rotary
+//! encoder cannot be counted.
+//!
+//! The driver takes the `TBn` pin, see [CapturePinB], and routes it to the MFT16 when it is created.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::capture::Edge;
+use super::low_level::{
+    ClockConfig, ClockSource, Config, Counter, InterruptHandler, Mode, MultiFunctionTimer, PulseAccumulateClockSelect,
+    WakeUpEvent,
+};
+use super::{CapturePinB, MultiFunctionInstance};
+use crate::gpio::AnyPin;
+
+/// `TnMCTRL`: count rising edges of `TBn` instead of falling edges
+const MCTRL_TBEDG: u8 = 1 << 4;
+/// `TnMCTRL`: enable the `TBn` input
+const MCTRL_TBEN: u8 = 1 << 6;
+
+/// The pending bit of the counter 1 underflow
+const UNDERFLOW: u8 = 1 << WakeUpEvent::C as u8;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// What the counter counts.
+pub enum CountSource {
+    /// Count the edges of `TBn`.
+    Edges(Edge),
+    /// Count the clock while `TBn` is active, with the clock prescaled by `clkps + 1` for the APB1 clock.
+    PulseAccumulate {
+        /// The counted clock.
+        clock: PulseAccumulateClockSelect,
+        /// The prescaler for the APB1 clock.
+        clkps: u8,
+    },
+}
+
+impl Default for CountSource {
+    fn default() -> Self {
+        CountSource::Edges(Edge::Rising)
+    }
+}
+
+/// Event counter on counter 1 of one of the MFT16 timers.
+///
+/// The hardware counter is 16 bits wide. Its underflows are noticed on every access and while awaiting
+/// [EventCounter::wait_for_overflow], so access the counter at least once every 65536 events to keep the count
+/// correct.
+pub struct EventCounter<'d, T: MultiFunctionInstance> {
+    timer: MultiFunctionTimer<'d, T>,
+    /// The number of underflows since the count was last taken
+    overflows: u32,
+    _pin: PeripheralRef<'d, AnyPin>,
+}
+
+impl<'d, T: MultiFunctionInstance> EventCounter<'d, T> {
+    /// Instantiate the event counter for this peripheral and its `TBn` pin, and start counting from 0.
+    pub fn new<B: CapturePinB<Instance = T>>(
+        instance: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = B> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        source: CountSource,
+    ) -> Self {
+        into_ref!(pin);
+
+        // Note(cs): other peripherals might also be modifying devalt* at the same time.
+        critical_section::with(|cs| {
+            // Safety: We have exclusive ownership over the pin.
+            unsafe { B::setup(cs) };
+        });
+
+        let clk = match source {
+            CountSource::Edges(_) => ClockConfig {
+                counter1_src: ClockSource::ExternalEvent,
+                ..Default::default()
+            },
+            CountSource::PulseAccumulate { clock, clkps } => ClockConfig {
+                counter1_src: ClockSource::PulseAccumulate,
+                clkps,
+                pls_acc_clk: clock,
+                low_pwr: clock == PulseAccumulateClockSelect::LowFrequencyClock,
+                ..Default::default()
+            },
+        };
+
+        let mut timer = MultiFunctionTimer::new(instance, irqs);
+        timer.enable(Config { clk, mode: Mode::Mode3 });
+
+        // The counter counts down, and reloads from the full range when it underflows.
+        timer.set_counter(Counter::Counter1, u16::MAX);
+        timer.set_reload_capture(Counter::Counter1, u16::MAX);
+
+        let mctrl = match source {
+            CountSource::Edges(Edge::Rising) => MCTRL_TBEN | MCTRL_TBEDG,
+            _ => MCTRL_TBEN,
+        };
+
+        let r = T::regs();
+        r.tn_mctrl().modify(|r, w| unsafe { w.bits(r.bits() | mctrl) });
+        r.tn_eclr().write(|w| unsafe { w.bits(UNDERFLOW) });
+
+        Self {
+            timer,
+            overflows: 0,
+            _pin: pin.map_into(),
+        }
+    }
+
+    /// Account for a pending underflow, returning true if there was one.
+    fn take_underflow(&mut self) -> bool {
+        let r = T::regs();
+        let underflowed = r.tn_ectrl().read().bits() & UNDERFLOW != 0;
+        if underflowed {
+            r.tn_eclr().write(|w| unsafe { w.bits(UNDERFLOW) });
+            self.overflows = self.overflows.wrapping_add(1);
+        }
+        underflowed
+    }
+
+    /// The number of events counted since the driver was created or the count was last taken.
+    pub fn count(&mut self) -> u32 {
+        let mut counter = self.timer.counter(Counter::Counter1);
+        if self.take_underflow() {
+            // The counter may have been read before it underflowed.
+            counter = self.timer.counter(Counter::Counter1);
+        }
+
+        (self.overflows << 16) | u32::from(u16::MAX - counter)
+    }
+
+    /// Return the count and restart counting from 0.
+    pub fn take_count(&mut self) -> u32 {
+        // Note(cs): the count is read and reset without events being counted in between.
+        critical_section::with(|_| {
+            let count = self.count();
+            self.timer.set_counter(Counter::Counter1, u16::MAX);
+            self.overflows = 0;
+            count
+        })
+    }
+
+    /// Wait until the 16-bit hardware counter overflows, which happens every 65536 events.
+    pub async fn wait_for_overflow(&mut self) {
+        poll_fn(|cx| {
+            T::waker().register(cx.waker());
+
+            if self.take_underflow() {
+                return Poll::Ready(());
+            }
+
+            // Note(cs): interrupt handler changes this register as well.
+            critical_section::with(|_| {
+                T::regs()
+                    .tn_ien()
+                    .modify(|r, w| unsafe { w.bits(r.bits() | UNDERFLOW) });
+            });
+            Poll::Pending
+        })
+        .await
+    }
+}
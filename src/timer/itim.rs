@@ -0,0 +1,595 @@
+//! General-purpose timers on the ITIM32 instances.
+//!
+//! Each ITIM32 is a 32-bit down counter that signals a timeout when it reaches zero, after which it reloads and
+//! counts down again. [IntervalTimer] uses them as one-shot or periodic timers, for driver-internal timeouts that do
+//! not need an embassy-time alarm, and [PeriodicTimer] turns them into a drift-free fixed-rate tick. [Delay] implements
+//! the blocking and async embedded-hal `DelayNs` traits on them for external driver crates, and [TimeoutGuard] puts a
+//! hardware deadline on blocking busy-wait loops, like the bus recovery of the I2C driver. [SlowTicker] ticks at
+//! periods of seconds from the `LFCLK` on ITIM32_1, waking the chip from deep sleep, for heartbeats in low-power
+//! states. ITIM32_1 is reserved when the `time-driver-itim` feature is enabled.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pmc::{Apb2, ClockGuard, PowerDomainToken};
+use crate::timer::IntervalInstance;
+
+/// `ITCTS`: the counter reached zero, cleared by writing 1
+const ITCTS_TO_STS: u8 = 1 << 0;
+/// `ITCTS`: interrupt when the counter reaches zero
+const ITCTS_TO_IE: u8 = 1 << 2;
+/// `ITCTS`: signal the MIWU when the counter reaches zero, to wake up from deep sleep
+const ITCTS_TO_WUE: u8 = 1 << 3;
+/// `ITCTS`: clock the timer from the `LFCLK` instead of the `APB2_CLK`
+const ITCTS_CKSEL: u8 = 1 << 4;
+/// `ITCTS`: run the timer
+const ITCTS_ITEN: u8 = 1 << 7;
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Clock source for ITIM32 timers.
+pub enum ClockSource {
+    /// Prescaled APB2 clock. (The counter is frozen in deep sleep)
+    #[default]
+    PrescaledAPB2Clock,
+    /// LFCLK at 32KHz, which keeps running in deep sleep.
+    LowFrequencyClock,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Configuration for an ITIM32 timer.
+pub struct Config {
+    /// The clock the counter runs from.
+    pub clock: ClockSource,
+    /// The clock is divided by `prescaler + 1`.
+    pub prescaler: u8,
+}
+
+impl Config {
+    /// Count the APB2 clock at the given frequency in Hz.
+    ///
+    /// Returns `None` if the APB2 clock cannot be divided down to exactly `hz`.
+    pub fn apb2(hz: u32) -> Option<Self> {
+        let apb2_hz = crate::cdcg::apb2_hz();
+        if hz == 0 || apb2_hz % hz != 0 {
+            return None;
+        }
+
+        let prescaler = u8::try_from(apb2_hz / hz - 1).ok()?;
+        Some(Self {
+            clock: ClockSource::PrescaledAPB2Clock,
+            prescaler,
+        })
+    }
+
+    /// Count the undivided `LFCLK`.
+    pub const fn low_frequency() -> Self {
+        Self {
+            clock: ClockSource::LowFrequencyClock,
+            prescaler: 0,
+        }
+    }
+
+    /// The frequency in Hz at which a timer with this configuration counts.
+    pub fn frequency(&self) -> u32 {
+        let clock_hz = match self.clock {
+            ClockSource::PrescaledAPB2Clock => crate::cdcg::apb2_hz(),
+            ClockSource::LowFrequencyClock => crate::cdcg::lfclk_hz(),
+        };
+
+        clock_hz / (u32::from(self.prescaler) + 1)
+    }
+}
+
+/// A one-shot or periodic timer on an ITIM32 instance.
+///
+/// The timer counts in ticks of [IntervalTimer::frequency]. While it runs from the APB2 clock, the chip does not
+/// sleep deeper than that clock allows.
+pub struct IntervalTimer<'d, T: IntervalInstance> {
+    _instance: PeripheralRef<'d, T>,
+    _clock: ClockGuard,
+    config: Config,
+    domain: Option<PowerDomainToken<Apb2>>,
+    /// Whether the timeout signals the MIWU, see [SlowTicker]
+    wake_up: bool,
+}
+
+impl<'d, T: IntervalInstance> IntervalTimer<'d, T> {
+    /// Instantiate the ITIM32 driver for this peripheral, leaving the timer stopped.
+    pub fn new(
+        instance: impl Peripheral<P = T> + 'd,
+        _irqs: impl crate::interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        config: Config,
+    ) -> Self {
+        into_ref!(instance);
+        let mut this = Self {
+            _instance: instance,
+            _clock: ClockGuard::new::<T>(),
+            config,
+            domain: None,
+            wake_up: false,
+        };
+        this.stop();
+
+        T::regs().itpre32().write(|w| unsafe { w.bits(config.prescaler) });
+
+        // Safety: _irqs ensures an interrupt handler is bound
+        unsafe {
+            T::Interrupt::enable();
+        }
+
+        this
+    }
+
+    /// The frequency in Hz at which the timer counts.
+    #[must_use]
+    pub fn frequency(&self) -> u32 {
+        self.config.frequency()
+    }
+
+    /// The `ITCTS` clock select and wake-up bits
+    fn ctrl(&self) -> u8 {
+        let cksel = match self.config.clock {
+            ClockSource::PrescaledAPB2Clock => 0,
+            ClockSource::LowFrequencyClock => ITCTS_CKSEL,
+        };
+        let wue = if self.wake_up { ITCTS_TO_WUE } else { 0 };
+        cksel | wue
+    }
+
+    fn start(&mut self, ticks: u32, oneshot: bool) {
+        assert!(ticks > 0, "An ITIM32 timer cannot expire after 0 ticks");
+
+        self.stop();
+        T::oneshot().store(oneshot, core::sync::atomic::Ordering::Relaxed);
+        T::period().store(ticks, core::sync::atomic::Ordering::Relaxed);
+
+        if self.config.clock == ClockSource::PrescaledAPB2Clock {
+            self.domain = Some(PowerDomainToken::new());
+        }
+
+        // A one-shot timer interrupts on its timeout, so the handler can stop it before it expires again.
+        let ie = if oneshot { ITCTS_TO_IE } else { 0 };
+
+        let r = T::regs();
+        r.itcnt32().write(|w| unsafe { w.bits(ticks) });
+        r.itcts32().write(|w| unsafe { w.bits(self.ctrl() | ie | ITCTS_ITEN) });
+        // The enable crosses into the clock domain of the timer, wait for it to take effect.
+        while r.itcts32().read().bits() & ITCTS_ITEN == 0 {}
+    }
+
+    /// Start the timer, expiring once after `ticks` ticks.
+    ///
+    /// Restarts the timer if it was already running.
+    pub fn start_oneshot(&mut self, ticks: u32) {
+        self.start(ticks, true);
+    }
+
+    /// Start the timer, expiring every `ticks` ticks until stopped.
+    ///
+    /// Restarts the timer if it was already running.
+    pub fn start_periodic(&mut self, ticks: u32) {
+        self.start(ticks, false);
+    }
+
+    /// Change the length of the next period of the running periodic timer to `ticks`, without restarting it.
+    ///
+    /// The period that is running keeps its length. `ITCNT32` is the counter itself, so the interrupt handler writes
+    /// the new length into it when the running period expires, less the ticks that already passed by then. Only that
+    /// period is sure to get the new length, so call this once every period to keep it, as [PeriodicTimer] does.
+    pub fn set_reload(&mut self, ticks: u32) {
+        assert!(ticks > 0, "An ITIM32 timer cannot expire after 0 ticks");
+        T::reload().store(ticks, core::sync::atomic::Ordering::Relaxed);
+
+        // Note(cs): interrupt handler changes this register as well.
+        critical_section::with(|_| {
+            T::regs()
+                .itcts32()
+                .modify(|r, w| unsafe { w.bits((r.bits() & !ITCTS_TO_STS) | ITCTS_TO_IE) });
+        });
+    }
+
+    /// Stop the timer, and forget an expiration that was not waited for.
+    pub fn stop(&mut self) {
+        let r = T::regs();
+        // Note(cs): interrupt handler changes this register as well.
+        critical_section::with(|_| {
+            r.itcts32().write(|w| unsafe { w.bits(self.ctrl() | ITCTS_TO_STS) });
+        });
+        while r.itcts32().read().bits() & ITCTS_ITEN != 0 {}
+        T::reload().store(0, core::sync::atomic::Ordering::Relaxed);
+
+        self.domain = None;
+    }
+
+    /// Returns true while the timer is counting.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        T::regs().itcts32().read().bits() & ITCTS_ITEN != 0
+    }
+
+    /// The number of ticks until the timer expires next.
+    #[must_use]
+    pub fn remaining(&self) -> u32 {
+        T::regs().itcnt32().read().bits()
+    }
+
+    /// Returns true, and clears the expiration, if the timer expired since it was started or last waited for.
+    pub fn take_expired(&mut self) -> bool {
+        let r = T::regs();
+        let expired = r.itcts32().read().bits() & ITCTS_TO_STS != 0;
+        if expired {
+            // Note(cs): interrupt handler changes this register as well.
+            critical_section::with(|_| {
+                r.itcts32().modify(|r, w| unsafe { w.bits(r.bits() | ITCTS_TO_STS) });
+            });
+        }
+        expired
+    }
+
+    /// Wait until the timer expires.
+    ///
+    /// Returns immediately if it already expired since it was started or last waited for. A one-shot timer stops
+    /// when it expires, so waiting on it again never returns until it is restarted.
+    pub async fn wait(&mut self) {
+        poll_fn(|cx| {
+            T::waker().register(cx.waker());
+
+            if self.take_expired() {
+                return Poll::Ready(());
+            }
+
+            // Note(cs): interrupt handler changes this register as well.
+            critical_section::with(|_| {
+                T::regs()
+                    .itcts32()
+                    .modify(|r, w| unsafe { w.bits((r.bits() & !ITCTS_TO_STS) | ITCTS_TO_IE) });
+            });
+            Poll::Pending
+        })
+        .await;
+
+        if !self.is_running() {
+            self.domain = None;
+        }
+    }
+}
+
+impl<T: IntervalInstance> Drop for IntervalTimer<'_, T> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The interrupt handler for the ITIM32 driver.
+pub struct InterruptHandler<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: IntervalInstance> crate::interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        T::waker().wake();
+
+        // The counter already started the next period, keep the ticks that passed since in the new length.
+        let reload = T::reload().swap(0, core::sync::atomic::Ordering::Relaxed);
+        if reload != 0 {
+            let r = T::regs();
+            let period = T::period().swap(reload, core::sync::atomic::Ordering::Relaxed);
+            let elapsed = period.saturating_sub(r.itcnt32().read().bits());
+            r.itcnt32()
+                .write(|w| unsafe { w.bits(reload.saturating_sub(elapsed).max(1)) });
+        }
+
+        // Deconfigure the interrupt, but do not clear the timeout. A one-shot timer is also stopped, before it reloads
+        // and expires again.
+        let oneshot = T::oneshot().load(core::sync::atomic::Ordering::Relaxed);
+        T::regs().itcts32().modify(|r, w| {
+            let mut bits = r.bits() & !(ITCTS_TO_IE | ITCTS_TO_STS);
+            if oneshot {
+                bits &= !ITCTS_ITEN;
+            }
+            unsafe { w.bits(bits) }
+        });
+    }
+}
+
+/// A fixed-rate tick on an [IntervalTimer], for control loops that need a stable sampling interval.
+///
+/// Each period is programmed by the interrupt at the end of the one before it, counting the ticks that passed since
+/// the timer expired, so the ticks do not drift with the time it takes to handle them. A period that is
+/// not a whole number of timer ticks is approximated by alternating between the two nearest lengths, so the error
+/// never accumulates beyond a single tick.
+pub struct PeriodicTimer<'d, T: IntervalInstance> {
+    timer: IntervalTimer<'d, T>,
+    /// The whole number of ticks per period
+    ticks: u32,
+    /// The fraction of a tick per period, in millionths of a tick
+    fraction: u32,
+    /// The accumulated fraction, in millionths of a tick
+    error: u32,
+}
+
+impl<'d, T: IntervalInstance> PeriodicTimer<'d, T> {
+    /// Tick every `period_us` microseconds, starting now.
+    ///
+    /// # Panics
+    /// Panics if the period is shorter than a tick of the timer, or longer than it can count.
+    pub fn new(timer: IntervalTimer<'d, T>, period_us: u32) -> Self {
+        let ticks = u64::from(period_us) * u64::from(timer.frequency());
+        let whole = u32::try_from(ticks / 1_000_000).expect("The period is too long for the timer");
+        assert!(whole > 0, "The period is shorter than a tick of the timer");
+
+        let mut this = Self {
+            ticks: whole,
+            fraction: (ticks % 1_000_000) as u32,
+            error: 0,
+            timer,
+        };
+
+        let first = this.next_period();
+        this.timer.start_periodic(first);
+        let second = this.next_period();
+        this.timer.set_reload(second);
+        this
+    }
+
+    /// The length in ticks of the next period, carrying the fraction of a tick over to the periods after it.
+    fn next_period(&mut self) -> u32 {
+        self.error += self.fraction;
+        if self.error >= 1_000_000 {
+            self.error -= 1_000_000;
+            self.ticks + 1
+        } else {
+            self.ticks
+        }
+    }
+
+    /// Wait for the next tick.
+    ///
+    /// Returns immediately if the tick already passed. Ticks that pass while nobody waits for them are not counted,
+    /// so wait at least once every period.
+    pub async fn next(&mut self) {
+        self.timer.wait().await;
+
+        // The period that just started was programmed on the previous tick, program the one after it.
+        let ticks = self.next_period();
+        self.timer.set_reload(ticks);
+    }
+
+    /// Stop ticking, and return the timer.
+    pub fn into_inner(mut self) -> IntervalTimer<'d, T> {
+        self.timer.stop();
+        self.timer
+    }
+}
+
+/// Delays on an [IntervalTimer], implementing the blocking and async `DelayNs` traits of embedded-hal.
+///
+/// Delays are rounded up to whole ticks of the timer, so run it from the APB2 clock for microsecond precision. The
+/// blocking delay polls the timer instead of counting core clock cycles, so it is not affected by interrupts or
+/// changes of the core clock.
+pub struct Delay<'d, T: IntervalInstance> {
+    timer: IntervalTimer<'d, T>,
+}
+
+impl<'d, T: IntervalInstance> Delay<'d, T> {
+    /// Use `timer` for delays.
+    pub fn new(timer: IntervalTimer<'d, T>) -> Self {
+        Self { timer }
+    }
+
+    /// Release the timer.
+    pub fn into_inner(self) -> IntervalTimer<'d, T> {
+        self.timer
+    }
+
+    /// The number of ticks in `ns` nanoseconds, rounded up.
+    fn ticks(&self, ns: u64) -> u64 {
+        (u128::from(ns) * u128::from(self.timer.frequency())).div_ceil(1_000_000_000) as u64
+    }
+}
+
+impl<T: IntervalInstance> embedded_hal::delay::DelayNs for Delay<'_, T> {
+    fn delay_ns(&mut self, ns: u32) {
+        let mut ticks = self.ticks(u64::from(ns));
+        while ticks > 0 {
+            let chunk = ticks.min(u64::from(u32::MAX)) as u32;
+            self.timer.start_oneshot(chunk);
+            while !self.timer.take_expired() {}
+            ticks -= u64::from(chunk);
+        }
+    }
+}
+
+impl<T: IntervalInstance> embedded_hal_async::delay::DelayNs for Delay<'_, T> {
+    async fn delay_ns(&mut self, ns: u32) {
+        let mut ticks = self.ticks(u64::from(ns));
+        while ticks > 0 {
+            let chunk = ticks.min(u64::from(u32::MAX)) as u32;
+            self.timer.start_oneshot(chunk);
+            self.timer.wait().await;
+            ticks -= u64::from(chunk);
+        }
+    }
+}
+
+/// The deadline of a [TimeoutGuard] passed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timeout;
+
+/// A hardware deadline for a blocking busy-wait loop, so a stuck peripheral returns an error instead of hanging.
+///
+/// The deadline is counted by an [IntervalTimer], so it holds regardless of how long each iteration of the loop takes,
+/// and the timer is stopped when the guard is dropped. The HAL uses it where a driver polls the bus without an
+/// interrupt to wait on, like [I2CController::recover_bus](crate::i2c::I2CController::recover_bus), and it is just as
+/// useful in blocking code on top of the drivers, like polling an external device over a bus.
+///
+/// ```rust,ignore
+/// let mut guard = TimeoutGuard::new(&mut timer, 10_000);
+/// guard.wait_until(|| regs.status().read().busy().bit_is_clear().then_some(()))
+///     .map_err(|Timeout| Error::Timeout)?;
+/// ```
+pub struct TimeoutGuard<'a, 'd, T: IntervalInstance> {
+    timer: &'a mut IntervalTimer<'d, T>,
+}
+
+impl<'a, 'd, T: IntervalInstance> TimeoutGuard<'a, 'd, T> {
+    /// Start a deadline of `timeout_us` microseconds on `timer`, rounded up to whole ticks of the timer.
+    pub fn new(timer: &'a mut IntervalTimer<'d, T>, timeout_us: u32) -> Self {
+        let ticks = (u64::from(timeout_us) * u64::from(timer.frequency())).div_ceil(1_000_000);
+        timer.start_oneshot(ticks.clamp(1, u64::from(u32::MAX)) as u32);
+        Self { timer }
+    }
+
+    /// Returns [Timeout] once the deadline passed.
+    pub fn check(&mut self) -> Result<(), Timeout> {
+        // The timeout flag is also set while interrupts are masked, when the handler cannot stop the timer.
+        if self.timer.take_expired() || !self.timer.is_running() {
+            Err(Timeout)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Call `f` until it returns `Some`, or until the deadline passes.
+    ///
+    /// `f` is called at least once, even if the deadline already passed.
+    pub fn wait_until<R>(&mut self, mut f: impl FnMut() -> Option<R>) -> Result<R, Timeout> {
+        loop {
+            let expired = self.check();
+            if let Some(result) = f() {
+                return Ok(result);
+            }
+            expired?;
+        }
+    }
+}
+
+impl<T: IntervalInstance> Drop for TimeoutGuard<'_, '_, T> {
+    fn drop(&mut self) {
+        self.timer.stop();
+    }
+}
+
+/// A periodic tick with periods from milliseconds up to weeks, counted from the `LFCLK`, for heartbeat LEDs and
+/// periodic polling in low-power states.
+///
+/// The ticker runs on ITIM32_1, the only ITIM32 whose timeout is a MIWU input (`MIWU0_43`). The timer counts the
+/// `LFCLK` divided down to 1024 Hz, so it keeps counting while the chip sleeps, and its timeout wakes the chip up from
+/// sleep and deep sleep. As in the time driver, only the wake-up signal of the MIWU is used: once awake, the tick is
+/// handled by the interrupt of the timer itself, so no MIWU interrupt handler needs to be bound.
+///
+/// Not available with the `time-driver-itim` feature, which reserves ITIM32_1 and its MIWU input.
+#[cfg(not(feature = "time-driver-itim"))]
+pub struct SlowTicker<'d> {
+    timer: IntervalTimer<'d, crate::peripherals::ITIM32_1>,
+    _wui: PeripheralRef<'d, crate::peripherals::MIWU0_43>,
+}
+
+#[cfg(not(feature = "time-driver-itim"))]
+impl<'d> SlowTicker<'d> {
+    /// The prescaler for a 1024 Hz tick from the 32768 Hz `LFCLK`
+    const PRESCALER: u8 = 31;
+
+    /// The 0-indexed group and input of MIWU0 signalled by the timeout of ITIM32_1.
+    const WAKE_UP_INPUT: (usize, u8) = (3, 3);
+
+    /// Tick every `period_ms` milliseconds, starting now.
+    ///
+    /// # Panics
+    /// Panics if `period_ms` is 0.
+    pub fn new(
+        instance: impl Peripheral<P = crate::peripherals::ITIM32_1> + 'd,
+        wui: impl Peripheral<P = crate::peripherals::MIWU0_43> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<
+            crate::interrupt::typelevel::ITIM32_1,
+            InterruptHandler<crate::peripherals::ITIM32_1>,
+        >,
+        period_ms: u32,
+    ) -> Self {
+        into_ref!(wui);
+        let config = Config {
+            clock: ClockSource::LowFrequencyClock,
+            prescaler: Self::PRESCALER,
+        };
+
+        let mut timer = IntervalTimer::new(instance, irqs, config);
+        timer.wake_up = true;
+
+        let mut this = Self { timer, _wui: wui };
+        this.enable_wake_up();
+        this.set_period_ms(period_ms);
+        this
+    }
+
+    fn wake_up_input() -> &'static crate::pac::miwu0::RegisterBlock {
+        // Safety: the WakeUpInput of the ITIM32_1 is owned by the ticker.
+        unsafe { &*crate::pac::Miwu0::PTR }
+    }
+
+    /// Let the timeout wake the chip from deep sleep, without enabling the `WKINTD_0` interrupt.
+    fn enable_wake_up(&mut self) {
+        use crate::pac::miwu0::*;
+
+        let (group, input) = Self::WAKE_UP_INPUT;
+        let port = Self::wake_up_input();
+        // Note(cs): WakeUpInputs can share MIWU and group, which use the same registers.
+        critical_section::with(|_cs| {
+            port.wkenn(group).modify(|_, w| w.input(input).disabled());
+            port.wkmodn(group)
+                .modify(|_, w| w.input(input).variant(wkmodn::InputMode::Edge));
+            port.wkaedgn(group)
+                .modify(|_, w| w.input(input).variant(wkaedgn::AnyEdge::Edge));
+            port.wkedgn(group)
+                .modify(|_, w| w.input(input).variant(wkedgn::Edge::HighRising));
+            port.wkinenn(group).modify(|_, w| w.input(input).enabled());
+            port.wkpcln(group).write(|w| w.input(input).clear());
+            port.wkenn(group).modify(|_, w| w.input(input).enabled());
+        });
+    }
+
+    /// Clear the wake-up of the previous tick, which would otherwise keep the chip from entering deep sleep.
+    fn clear_wake_up(&mut self) {
+        let (group, input) = Self::WAKE_UP_INPUT;
+        // Note(no-cs): atomic write to clear a single bit, safe.
+        Self::wake_up_input().wkpcln(group).write(|w| w.input(input).clear());
+    }
+
+    /// Restart the ticker with a period of `period_ms` milliseconds.
+    ///
+    /// # Panics
+    /// Panics if `period_ms` is 0.
+    pub fn set_period_ms(&mut self, period_ms: u32) {
+        assert!(period_ms > 0, "The period of a SlowTicker cannot be 0");
+        let ticks = (u64::from(period_ms) * u64::from(self.timer.frequency())).div_ceil(1000);
+        self.timer.start_periodic(ticks.clamp(1, u64::from(u32::MAX)) as u32);
+        self.clear_wake_up();
+    }
+
+    /// Wait for the next tick.
+    ///
+    /// The wake-up of a tick is only cleared here, so a tick that is not waited for keeps waking the chip right away.
+    pub async fn next(&mut self) {
+        self.timer.wait().await;
+        self.clear_wake_up();
+    }
+}
+
+#[cfg(not(feature = "time-driver-itim"))]
+impl Drop for SlowTicker<'_> {
+    fn drop(&mut self) {
+        let (group, input) = Self::WAKE_UP_INPUT;
+        let port = Self::wake_up_input();
+        // Note(cs): WakeUpInputs can share MIWU and group, which use the same registers.
+        critical_section::with(|_cs| {
+            port.wkenn(group).modify(|_, w| w.input(input).disabled());
+        });
+        self.clear_wake_up();
+    }
+}
@@ -1,6 +1,6 @@
 //! Partial low-level implementation for the MFT16 timer.
 
-use crate::{interrupt::typelevel::Interrupt, timer::MultiFunctionInstance};
+use crate::{interrupt::typelevel::Interrupt, pmc::ClockGuard, timer::MultiFunctionInstance};
 use core::{future::poll_fn, marker::PhantomData, task::Poll};
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 
@@ -21,6 +21,29 @@ pub enum ClockSource {
     SlowSpeedClock = 0b100,
 }
 
+impl ClockSource {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            0b001 => ClockSource::PrescaledAPB1Clock,
+            0b010 => ClockSource::ExternalEvent,
+            0b011 => ClockSource::PulseAccumulate,
+            0b100 => ClockSource::SlowSpeedClock,
+            _ => ClockSource::NoClock,
+        }
+    }
+
+    /// The frequency in Hz at which a counter driven by this source counts, given the prescaler `clkps`.
+    ///
+    /// Returns `None` for sources that do not run at a fixed frequency.
+    pub fn frequency(&self, clkps: u8) -> Option<u32> {
+        match self {
+            ClockSource::PrescaledAPB1Clock => Some(crate::cdcg::apb1_hz() / (u32::from(clkps) + 1)),
+            ClockSource::SlowSpeedClock => Some(crate::cdcg::lfclk_hz()),
+            ClockSource::NoClock | ClockSource::ExternalEvent | ClockSource::PulseAccumulate => None,
+        }
+    }
+}
+
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// Clock source when using Pulse Accumulate mode for MFT16 timers.
@@ -48,6 +71,40 @@ pub struct ClockConfig {
     pub low_pwr: bool,
 }
 
+impl ClockConfig {
+    /// Drive both counters from the 32 kHz LFCLK, which keeps running while the core sleeps.
+    pub const fn low_frequency() -> Self {
+        Self {
+            counter1_src: ClockSource::SlowSpeedClock,
+            counter2_src: ClockSource::SlowSpeedClock,
+            clkps: 0,
+            pls_acc_clk: PulseAccumulateClockSelect::LowFrequencyClock,
+            low_pwr: true,
+        }
+    }
+
+    /// Drive both counters from the APB1 clock, prescaled to count at `hz` or the closest frequency above it.
+    ///
+    /// Returns `None` if `hz` is 0, faster than the APB1 clock, or too slow for the 8-bit prescaler.
+    pub fn apb1(hz: u32) -> Option<Self> {
+        if hz == 0 {
+            return None;
+        }
+
+        let prescaler = crate::cdcg::apb1_hz() / hz;
+        if !(1..=256).contains(&prescaler) {
+            return None;
+        }
+
+        Some(Self {
+            counter1_src: ClockSource::PrescaledAPB1Clock,
+            counter2_src: ClockSource::PrescaledAPB1Clock,
+            clkps: (prescaler - 1) as u8,
+            ..Default::default()
+        })
+    }
+}
+
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// Mode for a MFT16 peripheral.
@@ -140,6 +197,7 @@ impl From<WakeUpEvent> for WakeUpEvents {
 /// Control of clock inputs and the various other modes has not yet been implemented.
 pub struct MultiFunctionTimer<'d, T: MultiFunctionInstance> {
     _instance: PeripheralRef<'d, T>,
+    _clock: ClockGuard,
 }
 
 impl<'d, T: MultiFunctionInstance> MultiFunctionTimer<'d, T> {
@@ -154,7 +212,10 @@ impl<'d, T: MultiFunctionInstance> MultiFunctionTimer<'d, T> {
         }
 
         into_ref!(instance);
-        Self { _instance: instance }
+        Self {
+            _instance: instance,
+            _clock: ClockGuard::new::<T>(),
+        }
     }
 
     /// Enable the MFT16 driver for this peripheral with a specific configuration, and starts running the timer.
@@ -187,6 +248,40 @@ impl<'d, T: MultiFunctionInstance> MultiFunctionTimer<'d, T> {
         });
     }
 
+    /// Change the clock sources and prescaler of the counters, keeping the mode and counter values.
+    pub fn set_clock(&mut self, clk: ClockConfig) {
+        let r = T::regs();
+
+        self.disable();
+        r.tn_prsc().write(|w| unsafe { w.bits(clk.clkps) });
+        r.tn_ckc().write(|w| unsafe {
+            w.low_pwr()
+                .bit(clk.low_pwr)
+                .pls_acc_clk()
+                .bit(clk.pls_acc_clk as u8 == 0b1)
+                .c1csel()
+                .bits(clk.counter1_src as u8)
+                .c2csel()
+                .bits(clk.counter2_src as u8)
+        });
+    }
+
+    /// The clock source the selected counter currently counts.
+    pub fn counter_source(&self, counter: Counter) -> ClockSource {
+        let ckc = T::regs().tn_ckc().read();
+        match counter {
+            Counter::Counter1 => ClockSource::from_bits(ckc.c1csel().bits()),
+            Counter::Counter2 => ClockSource::from_bits(ckc.c2csel().bits()),
+        }
+    }
+
+    /// The frequency in Hz at which the selected counter counts, or `None` if it is stopped or driven by an external
+    /// signal.
+    pub fn counter_frequency(&self, counter: Counter) -> Option<u32> {
+        self.counter_source(counter)
+            .frequency(T::regs().tn_prsc().read().bits())
+    }
+
     /// Disable running the timer.
     pub fn disable(&mut self) {
         T::regs()
@@ -231,6 +326,11 @@ impl<'d, T: MultiFunctionInstance> MultiFunctionTimer<'d, T> {
         };
     }
 
+    /// Clear the pending `events`, so they are only seen again when they happen again.
+    pub fn clear_events(&mut self, events: impl Into<WakeUpEvents>) {
+        T::regs().tn_eclr().write(|w| unsafe { w.bits(events.into().0) });
+    }
+
     /// Await for at least a single wake up event to be pending.
     ///
     /// Will reset the pending event, deconfigure the interrupt, and will return all events that ended up triggering.
@@ -1,17 +1,51 @@
 //! Drivers for the timers in this device.
 
+pub mod capture;
+pub mod counter;
+pub mod itim;
 pub mod low_level;
 
 #[allow(unused)]
 use embassy_sync::waitqueue::AtomicWaker;
 
 mod sealed {
+    use core::sync::atomic::{AtomicBool, AtomicU32};
+
     use embassy_sync::waitqueue::AtomicWaker;
 
-    pub trait SealedMultiFunctionInstance {
+    #[allow(private_bounds)]
+    pub trait SealedMultiFunctionInstance: crate::pmc::ClockGated {
         fn waker() -> &'static AtomicWaker;
         fn regs() -> &'static crate::pac::mft16_1::RegisterBlock;
     }
+
+    #[allow(private_bounds)]
+    pub trait SealedIntervalInstance: crate::pmc::ClockGated {
+        fn waker() -> &'static AtomicWaker;
+        fn oneshot() -> &'static AtomicBool;
+        /// The length in ticks of the period the timer is counting.
+        fn period() -> &'static AtomicU32;
+        /// The length in ticks of the next period, applied by the interrupt handler when the running one expires.
+        /// 0 if the timer repeats the running period.
+        fn reload() -> &'static AtomicU32;
+        fn regs() -> &'static crate::pac::itim32_1::RegisterBlock;
+    }
+
+    pub trait SealedCapturePin {
+        /// Route this pin to the `TAn` input of its MFT16.
+        ///
+        /// # Safety
+        /// The caller must own the pin.
+        unsafe fn setup(cs: critical_section::CriticalSection);
+    }
+
+    pub trait SealedCapturePinB {
+        /// Route this pin to the `TBn` input of its MFT16.
+        ///
+        /// # Safety
+        /// The caller must own the pin.
+        unsafe fn setup(cs: critical_section::CriticalSection);
+    }
 }
 
 #[allow(unused)]
@@ -48,3 +82,127 @@ impl_instance!(MFT16_1, Mft16_1);
 impl_instance!(MFT16_2, Mft16_2);
 #[cfg(not(feature = "time-driver-mft16-3"))]
 impl_instance!(MFT16_3, Mft16_3);
+
+/// A pin that can be used as the `TAn` input of an MFT16, for drivers that route the input themselves.
+pub trait CapturePin: sealed::SealedCapturePin + crate::gpio::InputPin {
+    /// The MFT16 this pin is an input of.
+    type Instance: MultiFunctionInstance;
+}
+
+macro_rules! impl_capture_pin {
+    ($instance:ident, $pin:ident, $pin_config:expr) => {
+        impl_capture_pin!(SealedCapturePin, CapturePin, $instance, $pin, $pin_config);
+    };
+    ($sealed:ident, $trait:ident, $instance:ident, $pin:ident, $pin_config:expr) => {
+        impl sealed::$sealed for crate::peripherals::$pin {
+            unsafe fn setup(_cs: critical_section::CriticalSection) {
+                fn internal_set(f: impl FnOnce(crate::pac::Sysconfig)) {
+                    f(unsafe { crate::pac::Sysconfig::steal() });
+                }
+                internal_set($pin_config);
+            }
+        }
+
+        impl $trait for crate::peripherals::$pin {
+            type Instance = crate::peripherals::$instance;
+        }
+    };
+}
+
+#[cfg(not(feature = "time-driver-mft16-1"))]
+impl_capture_pin!(MFT16_1, PE05, |config| {
+    config.devalt3().modify(|_, w| w.ta1_sl1().set_bit());
+});
+#[cfg(not(feature = "time-driver-mft16-1"))]
+impl_capture_pin!(MFT16_1, PE11, |config| {
+    config.devaltc().modify(|_, w| w.ta1_sl2().set_bit());
+});
+#[cfg(not(feature = "time-driver-mft16-2"))]
+impl_capture_pin!(MFT16_2, PG05, |config| {
+    config.devalt3().modify(|_, w| w.ta2_sl1().set_bit());
+});
+#[cfg(not(feature = "time-driver-mft16-2"))]
+impl_capture_pin!(MFT16_2, PF11, |config| {
+    config.devaltc().modify(|_, w| w.ps2_3_sl2().clear_bit());
+    config.devalt0().modify(|_, w| w.f_spi_cs1().clear_bit());
+    config.devaltc().modify(|_, w| w.ta2_sl2().set_bit());
+});
+
+/// A pin that can be used as the `TBn` input of an MFT16, for capturing on counter 2 or clocking counter 1.
+pub trait CapturePinB: sealed::SealedCapturePinB + crate::gpio::InputPin {
+    /// The MFT16 this pin is an input of.
+    type Instance: MultiFunctionInstance;
+}
+
+macro_rules! impl_capture_pin_b {
+    ($instance:ident, $pin:ident, $pin_config:expr) => {
+        impl_capture_pin!(SealedCapturePinB, CapturePinB, $instance, $pin, $pin_config);
+    };
+}
+
+#[cfg(not(feature = "time-driver-mft16-1"))]
+impl_capture_pin_b!(MFT16_1, PH11, |config| {
+    config.devalth().modify(|_, w| w.flm_sl().clear_bit());
+    config.devalt0().modify(|_, w| w.no_f_spi().set_bit());
+    config.devalt3().modify(|_, w| w.tb1_sl1().set_bit());
+});
+#[cfg(not(feature = "time-driver-mft16-1"))]
+impl_capture_pin_b!(MFT16_1, PE10, |config| {
+    config.devaltc().modify(|_, w| w.tb1_sl2().set_bit());
+});
+#[cfg(not(feature = "time-driver-mft16-2"))]
+impl_capture_pin_b!(MFT16_2, PJ11, |config| {
+    config.devaltc().modify(|_, w| w.ps2_3_sl2().clear_bit());
+    config.devalt0().modify(|_, w| w.f_spi_quad().clear_bit());
+    config.devalth().modify(|_, w| w.flm_sl().clear_bit());
+    config.devaltc().modify(|_, w| w.tb2_sl2().set_bit());
+});
+
+/// An instance of the ITIM32 peripheral.
+pub trait IntervalInstance: sealed::SealedIntervalInstance {
+    /// The interrupt used by this instance.
+    type Interrupt: crate::interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_interval_instance {
+    ($instance:ident, $pac:ident) => {
+        impl sealed::SealedIntervalInstance for crate::peripherals::$instance {
+            fn waker() -> &'static AtomicWaker {
+                static WAKER: AtomicWaker = AtomicWaker::new();
+                &WAKER
+            }
+
+            fn oneshot() -> &'static core::sync::atomic::AtomicBool {
+                static ONESHOT: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+                &ONESHOT
+            }
+
+            fn period() -> &'static core::sync::atomic::AtomicU32 {
+                static PERIOD: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+                &PERIOD
+            }
+
+            fn reload() -> &'static core::sync::atomic::AtomicU32 {
+                static RELOAD: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+                &RELOAD
+            }
+
+            fn regs() -> &'static crate::pac::itim32_1::RegisterBlock {
+                // Safety: not owned, memory is always present
+                unsafe { &*crate::pac::$pac::PTR }
+            }
+        }
+
+        impl IntervalInstance for crate::peripherals::$instance {
+            type Interrupt = crate::interrupt::typelevel::$instance;
+        }
+    };
+}
+
+#[cfg(not(feature = "time-driver-itim"))]
+impl_interval_instance!(ITIM32_1, Itim32_1);
+impl_interval_instance!(ITIM32_2, Itim32_2);
+impl_interval_instance!(ITIM32_3, Itim32_3);
+impl_interval_instance!(ITIM32_4, Itim32_4);
+impl_interval_instance!(ITIM32_5, Itim32_5);
+impl_interval_instance!(ITIM32_6, Itim32_6);
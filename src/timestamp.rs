@@ -0,0 +1,79 @@
+//! High-resolution timestamps
+//!
+//! [now_ticks] counts core clock cycles with the cycle counter of the DWT, independent of embassy-time and its tick
+//! rate. This makes for cycle-accurate measurements of interrupt latencies and bus transactions. The 32-bit hardware
+//! counter is extended to 64 bits in software, which requires [now_ticks] to be called at least once per wrap of the
+//! counter, about every 45 seconds at 96 MHz.
+//!
+//! The cycle counter stops while the core sleeps, so timestamps do not measure time across a sleep. Use embassy-time
+//! for that instead.
+
+use core::cell::Cell;
+
+use cortex_m::peripheral::{DCB, DWT};
+use critical_section::Mutex;
+
+/// `DEMCR`: enable the DWT
+const DEMCR_TRCENA: u32 = 1 << 24;
+/// `DWT_CTRL`: run the cycle counter
+const DWT_CTRL_CYCCNTENA: u32 = 1 << 0;
+
+/// The extended counter: the number of wraps, and the last value read from the hardware counter
+static EXTENDED: Mutex<Cell<(u32, u32)>> = Mutex::new(Cell::new((0, 0)));
+
+/// Start the cycle counter.
+pub(crate) fn init() {
+    // Safety: the DWT is only used for the cycle counter, and the bits are set with a read-modify-write.
+    unsafe {
+        (*DCB::PTR).demcr.modify(|r| r | DEMCR_TRCENA);
+        (*DWT::PTR).cyccnt.write(0);
+        (*DWT::PTR).ctrl.modify(|r| r | DWT_CTRL_CYCCNTENA);
+    }
+}
+
+/// The number of core clock cycles since the HAL was initialized.
+#[must_use]
+pub fn now_ticks() -> u64 {
+    critical_section::with(|cs| {
+        let extended = EXTENDED.borrow(cs);
+        let (mut wraps, last) = extended.get();
+
+        let now = DWT::cycle_count();
+        if now < last {
+            wraps += 1;
+        }
+        extended.set((wraps, now));
+
+        (u64::from(wraps) << 32) | u64::from(now)
+    })
+}
+
+/// The rate of the ticks, the current core clock frequency in Hz.
+#[must_use]
+pub fn ticks_per_second() -> u32 {
+    crate::cdcg::core_hz()
+}
+
+/// Convert a number of ticks to nanoseconds, at the current core clock frequency.
+#[must_use]
+pub fn ticks_to_ns(ticks: u64) -> u64 {
+    (u128::from(ticks) * 1_000_000_000 / u128::from(ticks_per_second())) as u64
+}
+
+/// Convert a number of ticks to microseconds, at the current core clock frequency.
+#[must_use]
+pub fn ticks_to_us(ticks: u64) -> u64 {
+    (u128::from(ticks) * 1_000_000 / u128::from(ticks_per_second())) as u64
+}
+
+/// Convert a number of microseconds to ticks, at the current core clock frequency.
+#[must_use]
+pub fn us_to_ticks(us: u64) -> u64 {
+    (u128::from(us) * u128::from(ticks_per_second()) / 1_000_000) as u64
+}
+
+/// The number of ticks elapsed since `start`, a value returned by [now_ticks].
+#[must_use]
+pub fn elapsed_since(start: u64) -> u64 {
+    now_ticks().saturating_sub(start)
+}
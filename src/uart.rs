@@ -5,6 +5,7 @@
 //! Implements the full-duplex receiver transmitter integration with 16-byte FIFO buffers for receive and transmit.
 //! Does not (yet) support DMA transactions.
 
+use core::cell::{Cell, RefCell};
 use core::convert::Infallible;
 use core::future::poll_fn;
 use core::marker::PhantomData;
@@ -14,7 +15,9 @@ use core::task::Poll;
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 
+use crate::cdcg::{ClockChangeHook, ClockChangeRegistration};
 use crate::interrupt::typelevel::Interrupt;
+use crate::pmc::{Apb4, PowerDomainToken, SuspendRegistration};
 
 /// Configuration for the number of stopbits
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -100,6 +103,8 @@ pub enum Error {
 
 struct AnyUart {
     regs: &'static crate::pac::cr_uart1::RegisterBlock,
+    pwdwn: crate::pmc::PwdwnBit,
+    power_well: crate::pmc::PowerWell,
     rx_waker: &'static AtomicWaker,
     tx_waker: &'static AtomicWaker,
     state: &'static State,
@@ -109,6 +114,8 @@ impl<T: Instance> From<T> for AnyUart {
     fn from(_uart: T) -> Self {
         AnyUart {
             regs: T::regs(),
+            pwdwn: T::PWDWN,
+            power_well: T::POWER_WELL,
             rx_waker: T::rx_waker(),
             tx_waker: T::tx_waker(),
             state: T::state(),
@@ -123,6 +130,8 @@ impl Peripheral for AnyUart {
     unsafe fn clone_unchecked(&self) -> Self::P {
         AnyUart {
             regs: self.regs,
+            pwdwn: self.pwdwn,
+            power_well: self.power_well,
             rx_waker: self.rx_waker,
             tx_waker: self.tx_waker,
             state: self.state,
@@ -201,6 +210,76 @@ impl ClockConfiguration {
     }
 }
 
+/// Program the dividers for the `baudrate` closest to the requested one, given the current APB4 clock.
+fn set_baudrate(regs: &crate::pac::cr_uart1::RegisterBlock, baudrate: u32) {
+    // Safety: UART can only be initialized after the clocks have been initialized.
+    let srcclk = unsafe { crate::cdcg::get_clocks() }.apb4_clk;
+
+    let clkcfg = ClockConfiguration::generate_valid(srcclk, baudrate)
+        // Minimize baudrate error.
+        .min_by_key(move |cfg| cfg.baudrate(srcclk).abs_diff(baudrate))
+        .expect("Failed to find clock configuration for requested baudrate");
+
+    #[cfg(feature = "defmt")]
+    {
+        let eff = clkcfg.baudrate(srcclk);
+        defmt::debug!(
+            "uart: {}, target: {}, eff:{}, diff:{}",
+            clkcfg,
+            baudrate,
+            eff,
+            eff as i64 - baudrate as i64
+        );
+    }
+
+    regs.ubaudn()
+        .write(|w| unsafe { w.bits((clkcfg.udiv10() & 0xff) as u8) });
+    // Setting the prescaler to non-zero also enables the peripheral.
+    regs.upsrn().write(|w| unsafe {
+        w.upsc()
+            .bits(clkcfg.upsc())
+            .udiv10_8()
+            .bits(((clkcfg.udiv10() & 0x700) >> 8) as u8)
+    });
+}
+
+impl<T: Instance> ClockChangeHook for Uart<'_, T> {
+    fn prepare(_baudrate: u32) {
+        // Let the transmitter finish the data in its FIFO at the old baudrate.
+        while T::regs().uftstsn().read().nxmip().bit_is_clear() {}
+    }
+
+    fn retune(baudrate: u32) {
+        set_baudrate(T::regs(), baudrate);
+    }
+}
+
+impl<T: Instance> crate::pmc::SuspendHook for Uart<'_, T> {
+    fn suspend(_baudrate: u32) {
+        let r = T::regs();
+
+        // Let the transmitter finish the byte it is shifting out.
+        while r.uftstsn().read().nxmip().bit_is_clear() {}
+
+        let saved = [
+            r.ucntln().read().bits(),
+            r.ufrsn().read().bits(),
+            r.uftctln().read().bits(),
+        ];
+        critical_section::with(|cs| T::state().suspended.borrow(cs).set(saved));
+    }
+
+    fn resume(baudrate: u32) {
+        let r = T::regs();
+        let [ucntl, ufrs, uftctl] = critical_section::with(|cs| T::state().suspended.borrow(cs).get());
+
+        r.ucntln().write(|w| unsafe { w.bits(ucntl) });
+        r.ufrsn().write(|w| unsafe { w.bits(ufrs) });
+        r.uftctln().write(|w| unsafe { w.bits(uftctl) });
+        set_baudrate(r, baudrate);
+    }
+}
+
 impl<'a, T: Instance + 'a> Uart<'a, T> {
     /// Configure the base registers for the peripheral and enables it.
     fn configure_enable(config: Config) {
@@ -209,6 +288,9 @@ impl<'a, T: Instance + 'a> Uart<'a, T> {
             T::Interrupt::enable();
         }
 
+        // Gated again when the last of UartRx and UartTx is dropped.
+        crate::pmc::enable_peripheral_clock::<T>();
+
         let r = T::regs();
 
         r.ucntln().modify(|_, w| {
@@ -235,34 +317,15 @@ impl<'a, T: Instance + 'a> Uart<'a, T> {
         // Set TX FIFO watermark level to 1.
         r.uftctln().modify(|_, w| unsafe { w.tempty_level_sel().bits(0x01) });
 
-        // Safety: UART can only be initialized after the clocks have been initialized.
-        let srcclk = unsafe { crate::cdcg::get_clocks() }.apb4_clk;
+        set_baudrate(r, config.baudrate);
 
-        let clkcfg = ClockConfiguration::generate_valid(srcclk, config.baudrate)
-            // Minimize baudrate error.
-            .min_by_key(move |cfg| cfg.baudrate(srcclk).abs_diff(config.baudrate))
-            .expect("Failed to find clock configuration for requested baudrate");
-
-        #[cfg(feature = "defmt")]
-        {
-            let eff = clkcfg.baudrate(srcclk);
-            defmt::debug!(
-                "uart: {}, target: {}, eff:{}, diff:{}",
-                clkcfg,
-                config.baudrate,
-                eff,
-                eff as i64 - config.baudrate as i64
-            );
-        }
-
-        r.ubaudn().write(|w| unsafe { w.bits((clkcfg.udiv10() & 0xff) as u8) });
-        // Setting the prescaler to non-zero also enables the peripheral.
-        r.upsrn().write(|w| unsafe {
-            w.upsc()
-                .bits(clkcfg.upsc())
-                .udiv10_8()
-                .bits(((clkcfg.udiv10() & 0x700) >> 8) as u8)
-        });
+        // Re-tune the baudrate when the APB4 clock changes, and restore the configuration after deep sleep,
+        // until the last of UartRx and UartTx is dropped.
+        let registrations = Registrations {
+            _retune: ClockChangeRegistration::new::<Self>(r as *const _ as usize, config.baudrate),
+            _suspend: SuspendRegistration::new::<Self>(r as *const _ as usize, config.baudrate),
+        };
+        critical_section::with(|cs| T::state().registrations.borrow(cs).replace(Some(registrations)));
     }
 
     /// Configure the base registers and general common mode registers for the peripheral, and enables it.
@@ -407,6 +470,9 @@ fn drop_rx_tx(dev: &PeripheralRef<'_, AnyUart>) {
 
         // Setting the prescaler to 0 disables the clock and disables the peripheral.
         dev.regs.upsrn().write(|w| unsafe { w.upsc().bits(0b0_0000) });
+        drop(critical_section::with(|cs| dev.state.registrations.borrow(cs).take()));
+        crate::pmc::gate(dev.pwdwn);
+        crate::pmc::release_power_well(dev.power_well);
     }
 }
 
@@ -489,6 +555,9 @@ impl embedded_io_async::Read for UartRx<'_> {
         // If we have no bytes pending, await until we have at least a single byte pending or error.
         let rsts = r.ufrstsn().read();
         if rsts.rfifo_nempty_sts().bit_is_clear() && rsts.err().bit_is_clear() {
+            // Keep the uart clocked while the chip sleeps until the byte arrives.
+            let _domain = PowerDomainToken::<Apb4>::new();
+
             // Note(cs): register is also modified in interrupt handler.
             critical_section::with(|_| {
                 r.ufrctln()
@@ -546,6 +615,9 @@ impl embedded_io_async::Write for UartTx<'_> {
 
         // Await until space in the FIFO buffer.
         if r.uftstsn().read().tempty_level().bits() == 0 {
+            // Keep the uart clocked while the chip sleeps until the FIFO has room.
+            let _domain = PowerDomainToken::<Apb4>::new();
+
             // Note(cs): register is also modified in interrupt handler.
             critical_section::with(|_| {
                 // Note: in configure we set watermark level to at least 1 byte.
@@ -581,6 +653,9 @@ impl embedded_io_async::Write for UartTx<'_> {
 
         // Await until the driver has transmitted all bits.
         if r.uftstsn().read().nxmip().bit_is_clear() {
+            // Keep the uart clocked while the chip sleeps until the transmitter is done.
+            let _domain = PowerDomainToken::<Apb4>::new();
+
             // Note(cs): register is also modified in interrupt handler.
             critical_section::with(|_| {
                 r.uftctln().modify(|_, w| w.nxmip_en().set_bit());
@@ -607,14 +682,26 @@ impl embedded_io_async::WriteReady for UartTx<'_> {
     }
 }
 
+/// The clock change and suspend registrations, shared by the UartRx and UartTx of a uart
+struct Registrations {
+    _retune: ClockChangeRegistration,
+    _suspend: SuspendRegistration,
+}
+
 struct State {
     rx_tx_refcount: AtomicU8,
+    /// The `UCNTL`, `UFRS` and `UFTCTL` registers, saved while suspended
+    suspended: critical_section::Mutex<Cell<[u8; 3]>>,
+    /// Dropped together with the last of UartRx and UartTx
+    registrations: critical_section::Mutex<RefCell<Option<Registrations>>>,
 }
 
 impl State {
     const fn new() -> Self {
         Self {
             rx_tx_refcount: AtomicU8::new(0),
+            suspended: critical_section::Mutex::new(Cell::new([0; 3])),
+            registrations: critical_section::Mutex::new(RefCell::new(None)),
         }
     }
 }
@@ -622,7 +709,8 @@ impl State {
 mod sealed {
     use embassy_sync::waitqueue::AtomicWaker;
 
-    pub trait SealedInstance {
+    #[allow(private_bounds)]
+    pub trait SealedInstance: crate::pmc::ClockGated {
         fn rx_waker() -> &'static AtomicWaker;
         fn tx_waker() -> &'static AtomicWaker;
         fn regs() -> &'static crate::pac::cr_uart1::RegisterBlock;
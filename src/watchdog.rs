@@ -0,0 +1,385 @@
+//! Watchdog on the Timer and Watchdog (TWD) block
+//!
+//! The TWD runs from the `LFCLK`, so it keeps running in deep sleep. Its prescaled clock drives two counters: the
+//! 8-bit watchdog counter `WDCNT`, which resets the chip when it expires, and the 16-bit timer `T0`. [Watchdog] starts
+//! both when it is fed, and lets `T0` expire shortly before `WDCNT`. The `T0OUT` event of the timer is reported
+//! through the MIWU as [T0OUT](crate::miwu::internal::T0OUT), which makes for an early warning to save state or log
+//! the cause before the reset fires.
+//!
+//! After a watchdog reset, [crate::pmc::reset_cause] returns [crate::pmc::ResetCause::Watchdog].
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use crate::miwu::internal::T0OUT;
+use crate::miwu::{Edge, InterruptHandler, WakeUp, WakeUpInput};
+use crate::peripherals::TWD;
+
+/// `TWCFG`: lock `TWCFG` until reset
+const TWCFG_LTWCFG: u8 = 1 << 0;
+/// `TWCFG`: lock `TWCP` until reset
+const TWCFG_LTWCP: u8 = 1 << 1;
+/// `TWCFG`: lock `TWDT0` until reset
+const TWCFG_LTWDT0: u8 = 1 << 2;
+/// `TWCFG`: lock `WDCNT` until reset
+const TWCFG_LWDCNT: u8 = 1 << 3;
+/// `TWCFG`: clock `WDCNT` from `T0OUT` instead of the prescaled clock
+const TWCFG_WDCT0I: u8 = 1 << 4;
+/// `TWCFG`: feed the watchdog by writing `WDSDM` instead of `WDCNT`
+const TWCFG_WDSDME: u8 = 1 << 5;
+/// `T0CSR`: reload `T0` from `TWDT0`
+const T0CSR_RST: u8 = 1 << 0;
+/// `T0CSR`: `T0` reached zero, cleared by writing 1
+const T0CSR_TC: u8 = 1 << 1;
+/// `T0CSR`: the watchdog reset the chip, cleared by writing 1
+const T0CSR_WDRST_STS: u8 = 1 << 4;
+/// `T0CSR`: the watchdog counter is running
+const T0CSR_WD_RUN: u8 = 1 << 5;
+
+/// The value written to `WDSDM` to feed the watchdog
+const WDSDM_FEED: u8 = 0x5c;
+/// The values written to `WDSDM` to stop the watchdog
+const WDSDM_STOP: [u8; 3] = [0x87, 0x61, 0x63];
+
+/// The `TWCP` prescaler, for a 1024 Hz clock from the 32768 Hz `LFCLK`
+const TWCP: u8 = 5;
+/// The frequency of the prescaled clock in Hz
+const TICK_HZ: u32 = 32768 >> TWCP;
+
+/// The error type for the watchdog driver
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The timeout is 0 or too long for the watchdog counter
+    TimeoutOutOfRange,
+    /// The early warning does not fit in the timeout, or is too early for `T0`
+    WarningOutOfRange,
+    /// The configuration was locked until reset by [Watchdog::lock]
+    Locked,
+    /// All task slots of the [TaskWatchdog] are taken
+    NoFreeSlot,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Configuration for the [Watchdog].
+pub struct Config {
+    /// The time from feeding the watchdog until it resets the chip, in milliseconds.
+    pub timeout_ms: u32,
+    /// How long before the reset the early warning fires, in milliseconds.
+    pub warning_ms: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 1000,
+            warning_ms: 100,
+        }
+    }
+}
+
+/// The register values for a [Config].
+struct Registers {
+    twdt0: u16,
+    wdcp: u8,
+    wdcnt: u8,
+}
+
+impl Config {
+    fn registers(&self) -> Result<Registers, Error> {
+        let timeout = self.timeout_ms.checked_mul(TICK_HZ).ok_or(Error::TimeoutOutOfRange)? / 1000;
+        if timeout == 0 {
+            return Err(Error::TimeoutOutOfRange);
+        }
+
+        // WDCNT is clocked by the prescaled clock, divided once more by 2^WDCP to fit the timeout in 8 bits.
+        let wdcp = (0..16u8)
+            .find(|wdcp| timeout.div_ceil(1 << wdcp) <= u32::from(u8::MAX))
+            .ok_or(Error::TimeoutOutOfRange)?;
+        let wdcnt = timeout.div_ceil(1 << wdcp) as u8;
+
+        let warning = self.warning_ms.checked_mul(TICK_HZ).ok_or(Error::WarningOutOfRange)? / 1000;
+        let t0 = timeout
+            .checked_sub(warning)
+            .filter(|t0| *t0 > 0)
+            .ok_or(Error::WarningOutOfRange)?;
+        let twdt0 = u16::try_from(t0 - 1).map_err(|_| Error::WarningOutOfRange)?;
+
+        Ok(Registers { twdt0, wdcp, wdcnt })
+    }
+}
+
+/// Driver for the TWD watchdog.
+///
+/// The watchdog runs from [Watchdog::new] on, and resets the chip unless it is fed with [Watchdog::feed] within the
+/// configured timeout. Dropping the driver leaves the watchdog running.
+pub struct Watchdog<'d> {
+    _twd: PeripheralRef<'d, TWD>,
+    warning: Option<WakeUp<'d>>,
+}
+
+impl<'d> Watchdog<'d> {
+    /// Configure and start the watchdog.
+    pub fn new(twd: impl Peripheral<P = TWD> + 'd, config: Config) -> Result<Self, Error> {
+        into_ref!(twd);
+        let mut this = Self {
+            _twd: twd,
+            warning: None,
+        };
+        this.start(config)?;
+        Ok(this)
+    }
+
+    /// Configure and start the watchdog, with an early warning that can be awaited with
+    /// [Watchdog::wait_for_early_warning].
+    pub fn new_with_warning(
+        twd: impl Peripheral<P = TWD> + 'd,
+        wui: impl Peripheral<P = T0OUT> + 'd,
+        irqs: impl crate::interrupt::typelevel::Binding<<T0OUT as WakeUpInput>::Interrupt, InterruptHandler<T0OUT>>,
+        config: Config,
+    ) -> Result<Self, Error> {
+        into_ref!(twd);
+        let mut this = Self {
+            _twd: twd,
+            warning: Some(crate::miwu::claim_for_peripheral(wui, irqs)),
+        };
+        this.start(config)?;
+        Ok(this)
+    }
+
+    fn regs() -> &'static crate::pac::twd::RegisterBlock {
+        // Safety: the TWD is owned by this driver.
+        unsafe { &*crate::pac::Twd::PTR }
+    }
+
+    /// Stop the watchdog, and start it again with a new configuration.
+    ///
+    /// Fails with [Error::Locked] after [Watchdog::lock].
+    pub fn start(&mut self, config: Config) -> Result<(), Error> {
+        let registers = config.registers()?;
+        if self.is_locked() {
+            return Err(Error::Locked);
+        }
+
+        let r = Self::regs();
+        // Note(cs): the stop sequence is only accepted without other writes in between.
+        critical_section::with(|_| {
+            for value in WDSDM_STOP {
+                r.wdsdm().write(|w| unsafe { w.bits(value) });
+            }
+        });
+        while r.t0csr().read().bits() & T0CSR_WD_RUN != 0 {}
+
+        r.twcfg()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !TWCFG_WDCT0I) | TWCFG_WDSDME) });
+        r.twcp().write(|w| unsafe { w.bits(TWCP) });
+        r.twdt0().write(|w| unsafe { w.bits(registers.twdt0) });
+        r.wdcp().write(|w| unsafe { w.bits(registers.wdcp) });
+
+        self.clear_early_warning();
+        self.write_t0csr(T0CSR_RST);
+        // Writing the watchdog counter starts it.
+        r.wdcnt().write(|w| unsafe { w.bits(registers.wdcnt) });
+
+        Ok(())
+    }
+
+    /// Write `T0CSR`, without clearing the watchdog reset status that [crate::pmc::reset_cause] reads.
+    fn write_t0csr(&mut self, bits: u8) {
+        Self::regs()
+            .t0csr()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(T0CSR_WDRST_STS | T0CSR_TC)) | bits) });
+    }
+
+    fn clear_early_warning(&mut self) {
+        self.write_t0csr(T0CSR_TC);
+        if let Some(warning) = &mut self.warning {
+            warning.clear_pending();
+        }
+    }
+
+    /// Restart the timeout of the watchdog, and of its early warning.
+    pub fn feed(&mut self) {
+        let r = Self::regs();
+        r.wdsdm().write(|w| unsafe { w.bits(WDSDM_FEED) });
+        self.clear_early_warning();
+        self.write_t0csr(T0CSR_RST);
+    }
+
+    /// Lock the configuration of the watchdog until the next reset, so it can no longer be stopped or changed.
+    ///
+    /// [crate::pmc::system_reset] with [crate::pmc::ResetType::Watchdog] then waits for the configured timeout.
+    pub fn lock(&mut self) {
+        Self::regs()
+            .twcfg()
+            .modify(|r, w| unsafe { w.bits(r.bits() | TWCFG_LTWCFG | TWCFG_LTWCP | TWCFG_LTWDT0 | TWCFG_LWDCNT) });
+    }
+
+    /// Returns true if the configuration was locked until reset.
+    #[must_use]
+    pub fn is_locked(&self) -> bool {
+        Self::regs().twcfg().read().bits() & TWCFG_LTWCFG != 0
+    }
+
+    /// Returns true if the early warning fired since the watchdog was last fed.
+    #[must_use]
+    pub fn is_early_warning(&self) -> bool {
+        Self::regs().t0csr().read().bits() & T0CSR_TC != 0
+    }
+
+    /// Wait for the early warning, which fires the configured time before the watchdog resets the chip.
+    ///
+    /// Returns immediately if it already fired since the watchdog was last fed.
+    ///
+    /// # Panics
+    /// Panics if the driver was created without the `T0OUT` wake-up input, with [Watchdog::new].
+    pub async fn wait_for_early_warning(&mut self) {
+        if self.is_early_warning() {
+            return;
+        }
+
+        self.warning
+            .as_mut()
+            .expect("The early warning requires the T0OUT wake-up input")
+            .wait_for(Edge::Rising)
+            .await;
+    }
+}
+
+/// A task that missed its window, returned by [TaskWatchdog::service].
+#[cfg(feature = "time")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stalled {
+    /// The slot of the task, see [TaskHandle::slot]
+    pub slot: usize,
+    /// The time since the task last checked in
+    pub since: embassy_time::Duration,
+}
+
+/// The check-in window and last check-in of a registered task.
+#[cfg(feature = "time")]
+#[derive(Copy, Clone)]
+struct TaskSlot {
+    window: embassy_time::Duration,
+    last: embassy_time::Instant,
+}
+
+/// Feeds the [Watchdog] only while every registered task keeps checking in.
+///
+/// Each task registers with [TaskWatchdog::register] and gets a [TaskHandle] with a window, within which it must
+/// call [TaskHandle::pet]. [TaskWatchdog::run] periodically feeds the watchdog when all tasks checked in within their
+/// windows, and stops feeding it when one did not, so a stuck task resets the chip. Up to `N` tasks can be registered
+/// at once; a task that finishes releases its slot by dropping its handle.
+#[cfg(feature = "time")]
+pub struct TaskWatchdog<'d, const N: usize> {
+    watchdog: critical_section::Mutex<core::cell::RefCell<Watchdog<'d>>>,
+    slots: critical_section::Mutex<core::cell::RefCell<[Option<TaskSlot>; N]>>,
+}
+
+#[cfg(feature = "time")]
+impl<'d, const N: usize> TaskWatchdog<'d, N> {
+    /// Take over feeding a running [Watchdog].
+    pub fn new(watchdog: Watchdog<'d>) -> Self {
+        Self {
+            watchdog: critical_section::Mutex::new(core::cell::RefCell::new(watchdog)),
+            slots: critical_section::Mutex::new(core::cell::RefCell::new([None; N])),
+        }
+    }
+
+    /// Register a task that must check in at least once every `window`, counting from now.
+    pub fn register(&self, window: embassy_time::Duration) -> Result<TaskHandle<'_, 'd, N>, Error> {
+        critical_section::with(|cs| {
+            let mut slots = self.slots.borrow_ref_mut(cs);
+            let slot = slots.iter().position(Option::is_none).ok_or(Error::NoFreeSlot)?;
+            slots[slot] = Some(TaskSlot {
+                window,
+                last: embassy_time::Instant::now(),
+            });
+
+            Ok(TaskHandle { watchdog: self, slot })
+        })
+    }
+
+    /// Feed the watchdog if all registered tasks checked in within their windows.
+    ///
+    /// Returns the first task that did not, leaving the watchdog unfed.
+    pub fn service(&self) -> Result<(), Stalled> {
+        let now = embassy_time::Instant::now();
+        critical_section::with(|cs| {
+            let slots = self.slots.borrow_ref(cs);
+            for (slot, task) in slots.iter().enumerate() {
+                if let Some(task) = task {
+                    let since = now.saturating_duration_since(task.last);
+                    if since > task.window {
+                        return Err(Stalled { slot, since });
+                    }
+                }
+            }
+
+            self.watchdog.borrow_ref_mut(cs).feed();
+            Ok(())
+        })
+    }
+
+    /// Call [TaskWatchdog::service] every `period`, which should be well below the watchdog timeout.
+    ///
+    /// Once a task stalls the watchdog is no longer fed, and the chip resets after its timeout. The stalled task is
+    /// logged with `defmt` first, and can be reported from the early warning of the [Watchdog].
+    pub async fn run(&self, period: embassy_time::Duration) -> ! {
+        let mut ticker = embassy_time::Ticker::every(period);
+        loop {
+            if let Err(_stalled) = self.service() {
+                #[cfg(feature = "defmt")]
+                defmt::error!(
+                    "Task {} did not check in for {} ms",
+                    _stalled.slot,
+                    _stalled.since.as_millis()
+                );
+            }
+            ticker.next().await;
+        }
+    }
+
+    /// Access the underlying [Watchdog], for example to lock it.
+    pub fn with_watchdog<R>(&self, f: impl FnOnce(&mut Watchdog<'d>) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.watchdog.borrow_ref_mut(cs)))
+    }
+}
+
+/// The check-in handle of a task registered with a [TaskWatchdog].
+///
+/// Dropping the handle unregisters the task.
+#[cfg(feature = "time")]
+pub struct TaskHandle<'a, 'd, const N: usize> {
+    watchdog: &'a TaskWatchdog<'d, N>,
+    slot: usize,
+}
+
+#[cfg(feature = "time")]
+impl<const N: usize> TaskHandle<'_, '_, N> {
+    /// Check in, restarting the window of this task.
+    pub fn pet(&self) {
+        let now = embassy_time::Instant::now();
+        critical_section::with(|cs| {
+            if let Some(task) = &mut self.watchdog.slots.borrow_ref_mut(cs)[self.slot] {
+                task.last = now;
+            }
+        });
+    }
+
+    /// The slot of this task, as reported by [Stalled].
+    #[must_use]
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+}
+
+#[cfg(feature = "time")]
+impl<const N: usize> Drop for TaskHandle<'_, '_, N> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            self.watchdog.slots.borrow_ref_mut(cs)[self.slot] = None;
+        });
+    }
+}